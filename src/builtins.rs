@@ -1,6 +1,10 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::str::Chars;
 use std::rc::Rc;
 use std::cell::RefCell;
-use crate::reader::{Value, Function, Environment, EvalError};
+use crate::reader::{self, Value, Function, Environment, EvalError, DelayState};
 
 /// Creates a new standard environment with built-in functions
 pub fn standard_env() -> Rc<RefCell<Environment>> {
@@ -8,19 +12,33 @@ pub fn standard_env() -> Rc<RefCell<Environment>> {
     
     // Register all built-in functions
     register_arithmetic_ops(&env);
+    register_math_ops(&env);
+    register_random_ops(&env);
     register_comparison_ops(&env);
     register_logical_ops(&env);
+    register_predicate_ops(&env);
     register_list_ops(&env);
+    register_sequence_ops(&env);
+    register_map_ops(&env);
+    register_set_ops(&env);
+    register_string_ops(&env);
+    register_symbol_ops(&env);
+    register_error_ops(&env);
+    register_function_ops(&env);
+    register_delay_ops(&env);
+    register_load_ops(&env);
+    register_reader_ops(&env);
+    register_env_ops(&env);
     
     env
 }
 
-/// Register arithmetic operations (+, -, *, /)
+/// Register arithmetic operations (+, -, *, /, quot, rem, mod, inc, dec, abs, min, max)
 fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
     // Addition (+)
     env.borrow_mut().set(
         "+".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             let mut sum = 0.0;
             for arg in args {
                 match arg {
@@ -32,13 +50,13 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                 }
             }
             Ok(Value::Number(sum))
-        })),
+        }))),
     );
     
     // Subtraction (-)
     env.borrow_mut().set(
         "-".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.is_empty() {
                 return Err(EvalError::ArityMismatch {
                     expected: 1,
@@ -71,13 +89,13 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                     got: format!("{:?}", args[0]),
                 }),
             }
-        })),
+        }))),
     );
     
     // Multiplication (*)
     env.borrow_mut().set(
         "*".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             let mut product = 1.0;
             for arg in args {
                 match arg {
@@ -89,13 +107,13 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                 }
             }
             Ok(Value::Number(product))
-        })),
+        }))),
     );
     
     // Division (/)
     env.borrow_mut().set(
         "/".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.is_empty() {
                 return Err(EvalError::ArityMismatch {
                     expected: 1,
@@ -108,7 +126,7 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                     if args.len() == 1 {
                         // Reciprocal
                         if *first == 0.0 {
-                            return Err(EvalError::Other("Division by zero".to_string()));
+                            return Err(EvalError::DivisionByZero);
                         }
                         Ok(Value::Number(1.0 / first))
                     } else {
@@ -118,7 +136,7 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                             match arg {
                                 Value::Number(n) => {
                                     if *n == 0.0 {
-                                        return Err(EvalError::Other("Division by zero".to_string()));
+                                        return Err(EvalError::DivisionByZero);
                                     }
                                     result /= n;
                                 }
@@ -136,38 +154,159 @@ fn register_arithmetic_ops(env: &Rc<RefCell<Environment>>) {
                     got: format!("{:?}", args[0]),
                 }),
             }
-        })),
+        }))),
+    );
+    
+    // Quotient of a division, truncated towards zero
+    env.borrow_mut().set(
+        "quot".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (dividend, divisor) = division_operands(&args)?;
+            Ok(Value::Number((dividend / divisor).trunc() + 0.0))
+        }))),
+    );
+    
+    // Remainder of quot, with the sign of the dividend: (rem -7 3) is -1
+    env.borrow_mut().set(
+        "rem".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (dividend, divisor) = division_operands(&args)?;
+            Ok(Value::Number(dividend % divisor + 0.0))
+        }))),
+    );
+    
+    // Modulus of a floored division, with the sign of the divisor: (mod -7 3) is 2
+    env.borrow_mut().set(
+        "mod".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (dividend, divisor) = division_operands(&args)?;
+            let remainder = dividend % divisor;
+            if remainder != 0.0 && (remainder < 0.0) != (divisor < 0.0) {
+                Ok(Value::Number(remainder + divisor))
+            } else {
+                Ok(Value::Number(remainder + 0.0))
+            }
+        }))),
+    );
+    
+    // One more than a number
+    env.borrow_mut().set(
+        "inc".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)? + 1.0))
+        }))),
+    );
+    
+    // One less than a number
+    env.borrow_mut().set(
+        "dec".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)? - 1.0))
+        }))),
+    );
+    
+    // Absolute value
+    env.borrow_mut().set(
+        "abs".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.abs()))
+        }))),
+    );
+    
+    // Smallest of one or more numbers
+    env.borrow_mut().set(
+        "min".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            extreme(&args, |a, b| a < b)
+        }))),
+    );
+    
+    // Largest of one or more numbers
+    env.borrow_mut().set(
+        "max".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            extreme(&args, |a, b| a > b)
+        }))),
     );
 }
 
-/// Register comparison operations (=, <, >)
-fn register_comparison_ops(env: &Rc<RefCell<Environment>>) {
-    // Equality (=)
+/// Returns the number that wins every comparison against the ones before it,
+/// for min and max. As in Clojure, NaN is only compared, not special-cased:
+/// a NaN is kept until a later argument replaces it, so (max 1 NaN 2) is 2
+fn extreme(args: &[Value], wins: fn(f64, f64) -> bool) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: 0,
+        });
+    }
+    
+    let mut result = None;
+    for (i, arg) in args.iter().enumerate() {
+        let n = match arg {
+            Value::Number(n) => *n,
+            _ => return Err(EvalError::TypeError {
+                expected: format!("number at argument {}", i + 1),
+                got: format!("{:?}", arg),
+            }),
+        };
+        result = match result {
+            Some(current) if wins(current, n) => Some(current),
+            _ => Some(n),
+        };
+    }
+    
+    Ok(Value::Number(result.unwrap_or_default()))
+}
+
+
+/// Returns the single numeric argument of a builtin
+fn number_arg(args: &[Value]) -> Result<f64, EvalError> {
+    match single_arg(args)? {
+        Value::Number(n) => Ok(*n),
+        other => Err(EvalError::TypeError {
+            expected: "number".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+
+/// Returns the dividend and divisor of quot, rem and mod. Adding 0.0 to their
+/// results turns a negative zero into zero
+fn division_operands(args: &[Value]) -> Result<(f64, f64), EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        });
+    }
+    
+    match (&args[0], &args[1]) {
+        (Value::Number(_), Value::Number(divisor)) if *divisor == 0.0 => Err(EvalError::DivisionByZero),
+        (Value::Number(dividend), Value::Number(divisor)) => Ok((*dividend, *divisor)),
+        (a, b) => Err(EvalError::TypeError {
+            expected: "numbers".to_string(),
+            got: format!("{:?} and {:?}", a, b),
+        }),
+    }
+}
+
+
+/// Register math functions (sqrt, pow, floor, ceil, round, trunc)
+fn register_math_ops(env: &Rc<RefCell<Environment>>) {
+    // Square root. The root of a negative number is NaN, not an error
     env.borrow_mut().set(
-        "=".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
-            if args.len() < 2 {
-                return Err(EvalError::ArityMismatch {
-                    expected: 2,
-                    got: args.len(),
-                });
-            }
-            
-            let first = &args[0];
-            for arg in &args[1..] {
-                if first != arg {
-                    return Ok(Value::Boolean(false));
-                }
-            }
-            
-            Ok(Value::Boolean(true))
-        })),
+        "sqrt".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.sqrt()))
+        }))),
     );
     
-    // Less than (<)
+    // A base raised to a power
     env.borrow_mut().set(
-        "<".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        "pow".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.len() != 2 {
                 return Err(EvalError::ArityMismatch {
                     expected: 2,
@@ -176,43 +315,237 @@ fn register_comparison_ops(env: &Rc<RefCell<Environment>>) {
             }
             
             match (&args[0], &args[1]) {
-                (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a < b)),
-                _ => Err(EvalError::TypeError {
-                    expected: "number".to_string(),
-                    got: format!("{:?} and {:?}", args[0], args[1]),
+                (Value::Number(base), Value::Number(exponent)) => Ok(Value::Number(base.powf(*exponent))),
+                (a, b) => Err(EvalError::TypeError {
+                    expected: "numbers".to_string(),
+                    got: format!("{:?} and {:?}", a, b),
                 }),
             }
-        })),
+        }))),
     );
     
-    // Greater than (>)
+    // Largest integer not greater than a number
     env.borrow_mut().set(
-        ">".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
-            if args.len() != 2 {
-                return Err(EvalError::ArityMismatch {
-                    expected: 2,
+        "floor".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.floor()))
+        }))),
+    );
+    
+    // Smallest integer not less than a number
+    env.borrow_mut().set(
+        "ceil".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.ceil()))
+        }))),
+    );
+    
+    // Nearest integer, rounding halfway cases away from zero: (round 2.5) is 3
+    // and (round -2.5) is -3
+    env.borrow_mut().set(
+        "round".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.round()))
+        }))),
+    );
+    
+    // Integer part of a number, dropping the fraction
+    env.borrow_mut().set(
+        "trunc".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Number(number_arg(&args)?.trunc()))
+        }))),
+    );
+}
+
+/// Register random numbers (rand, rand-int, rand-nth, set-rand-seed!). The
+/// numbers come from the generator of the interpreter, which a host can seed
+/// with `EvalContext::seed_rng`
+fn register_random_ops(env: &Rc<RefCell<Environment>>) {
+    // A random number in [0, 1), or in [0, n) given n
+    env.borrow_mut().set(
+        "rand".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let scale = match args.as_slice() {
+                [] => 1.0,
+                [Value::Number(n)] => *n,
+                [other] => return Err(EvalError::TypeError {
+                    expected: "number".to_string(),
+                    got: format!("{:?}", other),
+                }),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 1,
                     got: args.len(),
+                }),
+            };
+            
+            Ok(Value::Number(env.borrow().context().random() * scale))
+        }))),
+    );
+    
+    // A random integer in [0, n), for a positive integer n
+    env.borrow_mut().set(
+        "rand-int".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let n = match single_arg(&args)? {
+                Value::Number(n) if *n >= 1.0 && n.fract() == 0.0 => *n,
+                other => return Err(EvalError::TypeError {
+                    expected: "positive integer".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            };
+            Ok(Value::Number((env.borrow().context().random() * n).floor()))
+        }))),
+    );
+    
+    // A random element of a list, vector or string
+    env.borrow_mut().set(
+        "rand-nth".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let items = match single_arg(&args)? {
+                Value::Nil => vec![],
+                Value::List(items) | Value::Vector(items) => items.clone(),
+                Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+                other => return Err(EvalError::TypeError {
+                    expected: "list, vector or string".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            };
+            
+            if items.is_empty() {
+                return Err(EvalError::IndexOutOfBounds {
+                    index: 0,
+                    length: 0,
                 });
             }
             
-            match (&args[0], &args[1]) {
-                (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a > b)),
-                _ => Err(EvalError::TypeError {
-                    expected: "number".to_string(),
-                    got: format!("{:?} and {:?}", args[0], args[1]),
+            let index = (env.borrow().context().random() * items.len() as f64) as usize;
+            Ok(items[index].clone())
+        }))),
+    );
+    
+    // Seed the generator with a non-negative integer, for reproducible numbers
+    env.borrow_mut().set(
+        "set-rand-seed!".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            match single_arg(&args)? {
+                Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => {
+                    env.borrow().context().seed_rng(*n as u64);
+                    Ok(Value::Nil)
+                }
+                other => Err(EvalError::TypeError {
+                    expected: "non-negative integer seed".to_string(),
+                    got: format!("{:?}", other),
                 }),
             }
-        })),
+        }))),
+    );
+}
+
+/// Register comparison operations (=, not=, <, >, <=, >=)
+fn register_comparison_ops(env: &Rc<RefCell<Environment>>) {
+    // Equality (=), true when all the arguments are equal
+    env.borrow_mut().set(
+        "=".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            all_equal(&args).map(Value::Boolean)
+        }))),
+    );
+    
+    // Inequality (not=), true when any two of the arguments differ
+    env.borrow_mut().set(
+        "not=".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            all_equal(&args).map(|equal| Value::Boolean(!equal))
+        }))),
+    );
+    
+    // Less than (<), true when the arguments strictly increase
+    env.borrow_mut().set(
+        "<".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            compare_chain(&args, |a, b| a < b)
+        }))),
+    );
+    
+    // Greater than (>), true when the arguments strictly decrease
+    env.borrow_mut().set(
+        ">".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            compare_chain(&args, |a, b| a > b)
+        }))),
+    );
+    
+    // Less than or equal (<=), true when the arguments never decrease
+    env.borrow_mut().set(
+        "<=".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            compare_chain(&args, |a, b| a <= b)
+        }))),
     );
+    
+    // Greater than or equal (>=), true when the arguments never increase
+    env.borrow_mut().set(
+        ">=".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            compare_chain(&args, |a, b| a >= b)
+        }))),
+    );
+}
+
+/// Whether all the arguments are equal, for = and not=. At least two are required
+fn all_equal(args: &[Value]) -> Result<bool, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        });
+    }
+    
+    let first = &args[0];
+    Ok(args[1..].iter().all(|arg| arg == first))
+}
+
+/// Checks that each pair of adjacent numeric arguments is ordered, as in
+/// (<= 1 2 2 3). Comparison stops at the first pair that is not, so later
+/// arguments are not type checked. A single argument is trivially ordered
+fn compare_chain(args: &[Value], ordered: fn(f64, f64) -> bool) -> Result<Value, EvalError> {
+    match args {
+        [] => Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: 0,
+        }),
+        [Value::Number(_)] => Ok(Value::Boolean(true)),
+        [other] => Err(EvalError::TypeError {
+            expected: "number".to_string(),
+            got: format!("{:?}", other),
+        }),
+        _ => {
+            for (i, pair) in args.windows(2).enumerate() {
+                match (&pair[0], &pair[1]) {
+                    (Value::Number(a), Value::Number(b)) => {
+                        if !ordered(*a, *b) {
+                            return Ok(Value::Boolean(false));
+                        }
+                    }
+                    (a, b) => return Err(EvalError::TypeError {
+                        expected: format!("numbers at arguments {} and {}", i + 1, i + 2),
+                        got: format!("{:?} and {:?}", a, b),
+                    }),
+                }
+            }
+            
+            Ok(Value::Boolean(true))
+        }
+    }
 }
 
-/// Register logical operations (not)
+/// Register logical operations (not, boolean)
 fn register_logical_ops(env: &Rc<RefCell<Environment>>) {
     // Logical not
     env.borrow_mut().set(
         "not".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.len() != 1 {
                 return Err(EvalError::ArityMismatch {
                     expected: 1,
@@ -220,29 +553,90 @@ fn register_logical_ops(env: &Rc<RefCell<Environment>>) {
                 });
             }
             
-            match &args[0] {
-                Value::Boolean(b) => Ok(Value::Boolean(!b)),
-                Value::Nil => Ok(Value::Boolean(true)),
-                _ => Ok(Value::Boolean(false)),
+            Ok(Value::Boolean(!args[0].is_truthy()))
+        }))),
+    );
+    
+    // Coerce a value to a boolean, by its truthiness
+    env.borrow_mut().set(
+        "boolean".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
             }
-        })),
+            
+            Ok(Value::Boolean(args[0].is_truthy()))
+        }))),
+    );
+}
+
+/// Register predicates on numbers (even?, odd?, zero?, pos?, neg?)
+fn register_predicate_ops(env: &Rc<RefCell<Environment>>) {
+    env.borrow_mut().set(
+        "even?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(integer_arg(&args)? % 2.0 == 0.0))
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "odd?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(integer_arg(&args)? % 2.0 != 0.0))
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "zero?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(number_arg(&args)? == 0.0))
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "pos?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(number_arg(&args)? > 0.0))
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "neg?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(number_arg(&args)? < 0.0))
+        }))),
     );
 }
 
-/// Register list operations (list, first, rest)
+/// Returns the single integral argument of a builtin, as for even? and odd?
+fn integer_arg(args: &[Value]) -> Result<f64, EvalError> {
+    match number_arg(args)? {
+        n if n.fract() == 0.0 => Ok(n),
+        n => Err(EvalError::TypeError {
+            expected: "integer".to_string(),
+            got: format!("{:?}", n),
+        }),
+    }
+}
+
+/// Register list operations (list, first, rest, count, empty?, conj, nth, second,
+/// last, butlast)
 fn register_list_ops(env: &Rc<RefCell<Environment>>) {
     // Create a list
     env.borrow_mut().set(
         "list".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             Ok(Value::List(args))
-        })),
+        }))),
     );
     
     // Get the first element of a list or vector
     env.borrow_mut().set(
         "first".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.len() != 1 {
                 return Err(EvalError::ArityMismatch {
                     expected: 1,
@@ -263,13 +657,13 @@ fn register_list_ops(env: &Rc<RefCell<Environment>>) {
                     got: format!("{:?}", args[0]),
                 }),
             }
-        })),
+        }))),
     );
     
     // Get all elements except the first one
     env.borrow_mut().set(
         "rest".to_string(),
-        Value::Function(Function::builtin(|args, _env| {
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
             if args.len() != 1 {
                 return Err(EvalError::ArityMismatch {
                     expected: 1,
@@ -297,7 +691,1941 @@ fn register_list_ops(env: &Rc<RefCell<Environment>>) {
                     got: format!("{:?}", args[0]),
                 }),
             }
-        })),
+        }))),
+    );
+    
+    // Number of elements of a collection or characters of a string, 0 for nil
+    env.borrow_mut().set(
+        "count".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let coll = single_arg(&args)?;
+            match coll.len() {
+                Some(len) => Ok(Value::Number(len as f64)),
+                None => Err(EvalError::TypeError {
+                    expected: "collection or string".to_string(),
+                    got: format!("{:?}", coll),
+                }),
+            }
+        }))),
+    );
+    
+    // Whether a collection or string has no elements, true for nil
+    env.borrow_mut().set(
+        "empty?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let coll = single_arg(&args)?;
+            match coll.is_empty() {
+                Some(empty) => Ok(Value::Boolean(empty)),
+                None => Err(EvalError::TypeError {
+                    expected: "collection or string".to_string(),
+                    got: format!("{:?}", coll),
+                }),
+            }
+        }))),
+    );
+    
+    // Add items to a collection where it is cheapest: at the end of a vector, at
+    // the front of a list (so they end up reversed), into a set, or as entries
+    // of a map. (conj nil x) is the list (x)
+    env.borrow_mut().set(
+        "conj".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.is_empty() {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: 0,
+                });
+            }
+            
+            let mut args = args.into_iter();
+            let mut coll = args.next().unwrap_or(Value::Nil);
+            match &mut coll {
+                Value::Nil => Ok(Value::List(args.rev().collect())),
+                Value::List(items) => {
+                    let mut added: Vec<Value> = args.rev().collect();
+                    added.append(items);
+                    Ok(Value::List(added))
+                }
+                Value::Vector(items) => {
+                    items.extend(args);
+                    Ok(coll)
+                }
+                Value::Set(items) => {
+                    items.extend(args);
+                    Ok(coll)
+                }
+                Value::Map(entries) => {
+                    for mut entry in args {
+                        match &mut entry {
+                            Value::Vector(pair) if pair.len() == 2 => {
+                                entries.insert(pair[0].clone(), pair[1].clone());
+                            }
+                            Value::Map(other) => entries.extend(other.drain()),
+                            other => return Err(EvalError::TypeError {
+                                expected: "[key value] vector or map to add to a map".to_string(),
+                                got: format!("{:?}", other),
+                            }),
+                        }
+                    }
+                    Ok(coll)
+                }
+                other => Err(EvalError::TypeError {
+                    expected: "collection".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    // Element at an index of a vector, list or string. Out of bounds indices are
+    // an error, unless a default value to return is given
+    env.borrow_mut().set(
+        "nth".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 2 && args.len() != 3 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                });
+            }
+            
+            let coll = &args[0];
+            if !matches!(coll, Value::Nil | Value::List(_) | Value::Vector(_) | Value::String(_)) {
+                return Err(EvalError::TypeError {
+                    expected: "vector, list or string".to_string(),
+                    got: format!("{:?}", coll),
+                });
+            }
+            let index = match &args[1] {
+                Value::Number(n) if n.fract() == 0.0 => *n as i64,
+                other => return Err(EvalError::TypeError {
+                    expected: "integer index".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            };
+            
+            let found = usize::try_from(index)
+                .ok()
+                .and_then(|index| coll.try_iter().and_then(|mut items| items.nth(index)));
+            match (found, args.get(2)) {
+                (Some(item), _) => Ok(item),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) => Err(EvalError::IndexOutOfBounds {
+                    index,
+                    length: coll.len().unwrap_or(0),
+                }),
+            }
+        }))),
+    );
+    
+    // Second element of a collection, nil when it is too short
+    env.borrow_mut().set(
+        "second".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(seq_arg(&args)?.nth(1).unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // Last element of a collection, nil when it is empty
+    env.borrow_mut().set(
+        "last".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(seq_arg(&args)?.last().unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // All the elements of a collection but the last, as a list, or nil when
+    // there are none
+    env.borrow_mut().set(
+        "butlast".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut items: Vec<Value> = seq_arg(&args)?.collect();
+            items.pop();
+            if items.is_empty() {
+                Ok(Value::Nil)
+            } else {
+                Ok(Value::List(items))
+            }
+        }))),
     );
 }
 
+/// Register operations over the elements of any collection or string (reverse,
+/// take, drop, take-while, drop-while, map, filter, remove, reduce, reduced, some,
+/// every?, not-any?, sort, sort-by).
+/// They return lists, and see maps as `[key value]` entries. Maps and sets have
+/// no order, so their elements come in an arbitrary one
+fn register_sequence_ops(env: &Rc<RefCell<Environment>>) {
+    // The elements in reverse order. The characters of a string are
+    // reversed as a list of one-character strings, and nil gives ()
+    env.borrow_mut().set(
+        "reverse".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut items: Vec<Value> = seq_arg(&args)?.collect();
+            items.reverse();
+            Ok(Value::List(items))
+        }))),
+    );
+    
+    // The first n elements, or all of them when there are fewer
+    env.borrow_mut().set(
+        "take".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (n, items) = count_and_seq(&args)?;
+            Ok(Value::List(items.take(n).collect()))
+        }))),
+    );
+    
+    // All but the first n elements
+    env.borrow_mut().set(
+        "drop".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (n, items) = count_and_seq(&args)?;
+            Ok(Value::List(items.skip(n).collect()))
+        }))),
+    );
+    
+    // The leading elements for which a predicate is truthy. The predicate is not
+    // called again after it returns a falsey value
+    env.borrow_mut().set(
+        "take-while".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            let context = env.borrow().context();
+            let mut taken = Vec::new();
+            for item in items {
+                context.consume_fuel(1)?;
+                if !reader::call(pred, vec![item.clone()], env)?.is_truthy() {
+                    break;
+                }
+                taken.push(item);
+            }
+            Ok(Value::List(taken))
+        }))),
+    );
+    
+    // The elements from the first one for which a predicate is falsey
+    env.borrow_mut().set(
+        "drop-while".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, mut items) = function_and_seq(&args)?;
+            let context = env.borrow().context();
+            let mut rest = Vec::new();
+            for item in items.by_ref() {
+                context.consume_fuel(1)?;
+                if !reader::call(pred, vec![item.clone()], env)?.is_truthy() {
+                    rest.push(item);
+                    break;
+                }
+            }
+            rest.extend(items);
+            Ok(Value::List(rest))
+        }))),
+    );
+    
+    // The results of calling a function on each element. Given several
+    // collections, the function is called with an element of each, and the
+    // result is as long as the shortest: (map + [1 2] [10 20 30]) is (11 22)
+    env.borrow_mut().set(
+        "map".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if args.len() < 2 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                });
+            }
+            
+            let f = &args[0];
+            let mut colls = args[1..].iter().map(seq).collect::<Result<Vec<_>, _>>()?;
+            let context = env.borrow().context();
+            let mut results = Vec::new();
+            loop {
+                let call_args: Option<Vec<Value>> = colls.iter_mut().map(|items| items.next()).collect();
+                match call_args {
+                    Some(call_args) => {
+                        context.consume_fuel(1)?;
+                        results.push(reader::call(f, call_args, env)?);
+                    }
+                    None => return Ok(Value::List(results)),
+                }
+            }
+        }))),
+    );
+    
+    // The elements for which a predicate is truthy
+    env.borrow_mut().set(
+        "filter".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            keep_matching(pred, items, true, env)
+        }))),
+    );
+    
+    // The elements for which a predicate is falsey
+    env.borrow_mut().set(
+        "remove".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            keep_matching(pred, items, false, env)
+        }))),
+    );
+    
+    // Combine the elements with a function, from an initial value or else the
+    // first element: (reduce + 0 [1 2 3]) is (+ (+ (+ 0 1) 2) 3). An empty
+    // collection without an initial value gives (f), and a single element
+    // without one is returned without calling f
+    env.borrow_mut().set(
+        "reduce".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (f, init, coll) = match args.as_slice() {
+                [f, coll] => (f, None, coll),
+                [f, init, coll] => (f, Some(init.clone()), coll),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            let mut items = seq(coll)?;
+            let mut acc = match init.or_else(|| items.next()) {
+                Some(acc) => acc,
+                None => return reader::call(f, vec![], env),
+            };
+            let context = env.borrow().context();
+            for item in items {
+                context.consume_fuel(1)?;
+                acc = reader::call(f, vec![acc, item], env)?;
+                // A reduced value ends the reduction
+                if let Value::Reduced(result) = &mut acc {
+                    return Ok(std::mem::take(&mut **result));
+                }
+            }
+            Ok(acc)
+        }))),
+    );
+    
+    // Wrap a value to return it from reduce right away
+    env.borrow_mut().set(
+        "reduced".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Reduced(Box::new(single_arg(&args)?.clone())))
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "reduced?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(matches!(single_arg(&args)?, Value::Reduced(_))))
+        }))),
+    );
+    
+    // The first truthy result of a predicate on the elements, or nil. With a
+    // set as the predicate, (some #{:a} ks) is the first element of ks in the set
+    env.borrow_mut().set(
+        "some".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            let context = env.borrow().context();
+            for item in items {
+                context.consume_fuel(1)?;
+                let result = reader::call(pred, vec![item], env)?;
+                if result.is_truthy() {
+                    return Ok(result);
+                }
+            }
+            Ok(Value::Nil)
+        }))),
+    );
+    
+    // Whether a predicate is truthy for every element, stopping at the first
+    // that it is not. True for no elements
+    env.borrow_mut().set(
+        "every?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            let context = env.borrow().context();
+            for item in items {
+                context.consume_fuel(1)?;
+                if !reader::call(pred, vec![item], env)?.is_truthy() {
+                    return Ok(Value::Boolean(false));
+                }
+            }
+            Ok(Value::Boolean(true))
+        }))),
+    );
+    
+    // Whether a predicate is falsey for every element, stopping at the first
+    // that it is not. True for no elements
+    env.borrow_mut().set(
+        "not-any?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (pred, items) = function_and_seq(&args)?;
+            let context = env.borrow().context();
+            for item in items {
+                context.consume_fuel(1)?;
+                if reader::call(pred, vec![item], env)?.is_truthy() {
+                    return Ok(Value::Boolean(false));
+                }
+            }
+            Ok(Value::Boolean(true))
+        }))),
+    );
+    
+    // The elements in order, with an optional comparator: (sort coll) or
+    // (sort cmp coll). Equal elements keep their order
+    env.borrow_mut().set(
+        "sort".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (cmp, coll) = match args.as_slice() {
+                [coll] => (None, coll),
+                [cmp, coll] => (Some(cmp), coll),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                }),
+            };
+            
+            let items = seq(coll)?.collect();
+            let sorted = sort_stable(items, &mut |a, b| compare_with(cmp, a, b, env))?;
+            Ok(Value::List(sorted))
+        }))),
+    );
+    
+    // The elements in the order of a key computed for each of them, with an
+    // optional comparator for the keys: (sort-by :age people) or
+    // (sort-by :age cmp people). Equal elements keep their order
+    env.borrow_mut().set(
+        "sort-by".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let (keyfn, cmp, coll) = match args.as_slice() {
+                [keyfn, coll] => (keyfn, None, coll),
+                [keyfn, cmp, coll] => (keyfn, Some(cmp), coll),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            // Each key is computed once, not at every comparison
+            let context = env.borrow().context();
+            let keyed = seq(coll)?
+                .map(|item| {
+                    context.consume_fuel(1)?;
+                    Ok((reader::call(keyfn, vec![item.clone()], env)?, item))
+                })
+                .collect::<Result<Vec<_>, EvalError>>()?;
+            let sorted = sort_stable(keyed, &mut |(a, _), (b, _)| compare_with(cmp, a, b, env))?;
+            Ok(Value::List(sorted.into_iter().map(|(_, item)| item).collect()))
+        }))),
+    );
+}
+
+/// Orders two values with a comparator, or in their natural order without one.
+/// A comparator returning a number puts a first when it is negative. As in
+/// Clojure, any other result is taken as whether a comes before b, so a
+/// predicate such as > works as a comparator too. Each comparison takes an
+/// evaluation step
+fn compare_with(
+    cmp: Option<&Value>,
+    a: &Value,
+    b: &Value,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Ordering, EvalError> {
+    env.borrow().context().consume_fuel(1)?;
+    
+    let cmp = match cmp {
+        Some(cmp) => cmp,
+        None => return a.try_cmp(b).ok_or_else(|| EvalError::TypeError {
+            expected: "comparable values".to_string(),
+            got: format!("{:?} and {:?}", a, b),
+        }),
+    };
+    
+    match reader::call(cmp, vec![a.clone(), b.clone()], env)? {
+        Value::Number(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+        before if before.is_truthy() => Ok(Ordering::Less),
+        _ if reader::call(cmp, vec![b.clone(), a.clone()], env)?.is_truthy() => Ok(Ordering::Greater),
+        _ => Ok(Ordering::Equal),
+    }
+}
+
+/// Sorts stably with a comparison that may fail, returning its first error.
+/// This is a merge sort rather than `sort_by`, which may panic when a user
+/// comparator is not a consistent order
+fn sort_stable<T>(
+    mut items: Vec<T>,
+    cmp: &mut impl FnMut(&T, &T) -> Result<Ordering, EvalError>,
+) -> Result<Vec<T>, EvalError> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    
+    let right = items.split_off(items.len() / 2);
+    let mut left = sort_stable(items, cmp)?.into_iter().peekable();
+    let mut right = sort_stable(right, cmp)?.into_iter().peekable();
+    
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Ties go to the left, which keeps equal elements in order
+        let from_right = cmp(b, a)? == Ordering::Less;
+        merged.extend(if from_right { right.next() } else { left.next() });
+    }
+    merged.extend(left);
+    merged.extend(right);
+    
+    Ok(merged)
+}
+
+/// Returns the list of elements whose predicate result has the given truthiness
+fn keep_matching(
+    pred: &Value,
+    items: impl Iterator<Item = Value>,
+    truthy: bool,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Value, EvalError> {
+    let context = env.borrow().context();
+    let mut kept = Vec::new();
+    for item in items {
+        context.consume_fuel(1)?;
+        if reader::call(pred, vec![item.clone()], env)?.is_truthy() == truthy {
+            kept.push(item);
+        }
+    }
+    Ok(Value::List(kept))
+}
+
+/// Register operations on maps and other associative collections (get, get-in,
+/// assoc, assoc-in, update, update-in, dissoc, keys, vals, contains?, find, merge,
+/// merge-with, select-keys, zipmap)
+fn register_map_ops(env: &Rc<RefCell<Environment>>) {
+    // The value of a key in a map, the element at an index of a vector or string,
+    // or the element itself if it is in a set. A missing key gives the default,
+    // or nil
+    env.borrow_mut().set(
+        "get".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (coll, key, default) = match args.as_slice() {
+                [coll, key] => (coll, key, None),
+                [coll, key, default] => (coll, key, Some(default)),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            Ok(lookup(coll, key).or_else(|| default.cloned()).unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // The value at a path of keys through nested collections:
+    // (get-in m [:a 0 :b]) is (get (get (get m :a) 0) :b). A missing key
+    // anywhere along the path gives the default, or nil
+    env.borrow_mut().set(
+        "get-in".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (coll, path, default) = match args.as_slice() {
+                [coll, path] => (coll, path, None),
+                [coll, path, default] => (coll, path, Some(default)),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            let mut found = Some(coll.clone());
+            for key in seq(path)? {
+                found = found.and_then(|coll| lookup(&coll, &key));
+            }
+            Ok(found.or_else(|| default.cloned()).unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // The collection with keys set to values: (assoc m k v & kvs). A vector
+    // index may be one past the end, to append
+    env.borrow_mut().set(
+        "assoc".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() < 3 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 3,
+                    got: args.len(),
+                });
+            }
+            if args.len() % 2 == 0 {
+                return Err(EvalError::Other("assoc expects a value for every key".to_string()));
+            }
+            
+            let mut args = args.into_iter();
+            let mut coll = args.next().unwrap_or(Value::Nil);
+            while let (Some(key), Some(value)) = (args.next(), args.next()) {
+                coll = assoc_entry(coll, key, value)?;
+            }
+            Ok(coll)
+        }))),
+    );
+    
+    // The collection with the value at a path of keys set, creating maps for
+    // the missing keys along the way: (assoc-in m [:a :b] v)
+    env.borrow_mut().set(
+        "assoc-in".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [coll, path, value] = <[Value; 3]>::try_from(args).map_err(|args| EvalError::ArityMismatch {
+                expected: 3,
+                got: args.len(),
+            })?;
+            let path: Vec<Value> = seq(&path)?.collect();
+            let mut value = Some(value);
+            update_path(coll, &path, &mut |_| Ok(value.take().unwrap_or(Value::Nil)))
+        }))),
+    );
+    
+    // The collection with the value of a key replaced by the result of calling
+    // f on it, and any extra arguments: (update m :count + 1). The value is nil
+    // when the key is missing
+    env.borrow_mut().set(
+        "update".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let ([coll, key, f], extra) = update_args(args)?;
+            update_path(coll, &[key], &mut |current| call_with(&f, current, &extra, env))
+        }))),
+    );
+    
+    // The collection with the value at a path of keys replaced by the result
+    // of calling f on it, creating maps for the missing keys along the way:
+    // (update-in m [:a :count] inc)
+    env.borrow_mut().set(
+        "update-in".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let ([coll, path, f], extra) = update_args(args)?;
+            let path: Vec<Value> = seq(&path)?.collect();
+            update_path(coll, &path, &mut |current| call_with(&f, current, &extra, env))
+        }))),
+    );
+    
+    // The map without the given keys: (dissoc m k & ks). Keys that are not in
+    // the map are ignored, and (dissoc nil k) is nil
+    env.borrow_mut().set(
+        "dissoc".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut args = args.into_iter();
+            let mut coll = args.next().ok_or(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            })?;
+            
+            match &mut coll {
+                Value::Nil => Ok(Value::Nil),
+                Value::Map(entries) => {
+                    for key in args {
+                        entries.remove(&key);
+                    }
+                    Ok(coll)
+                }
+                other => Err(EvalError::TypeError {
+                    expected: "map".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    // The keys of a map as a list, or nil when the map is empty, as in Clojure.
+    // keys and vals of the same map list its entries in the same order
+    env.borrow_mut().set(
+        "keys".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_column(single_arg(&args)?, |key, _| key)
+        }))),
+    );
+    
+    // The values of a map as a list, or nil when the map is empty
+    env.borrow_mut().set(
+        "vals".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_column(single_arg(&args)?, |_, value| value)
+        }))),
+    );
+    
+    // Whether a key is present: a key of a map, an element of a set, or an
+    // index in range of a vector or string. It checks indices, not values, so
+    // (contains? [:a :b] 1) is true and (contains? [:a :b] :a) is false
+    env.borrow_mut().set(
+        "contains?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [coll, key] = <[Value; 2]>::try_from(args).map_err(|args| EvalError::ArityMismatch {
+                expected: 2,
+                got: args.len(),
+            })?;
+            
+            match coll {
+                Value::Nil | Value::Map(_) | Value::Set(_) | Value::Vector(_) | Value::String(_) => {
+                    Ok(Value::Boolean(lookup(&coll, &key).is_some()))
+                }
+                other => Err(EvalError::TypeError {
+                    expected: "map, set, vector or string".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    // The entry for a key as a [key value] vector, or nil when it is missing.
+    // Vectors are looked up by index
+    env.borrow_mut().set(
+        "find".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [coll, key] = <[Value; 2]>::try_from(args).map_err(|args| EvalError::ArityMismatch {
+                expected: 2,
+                got: args.len(),
+            })?;
+            
+            match coll {
+                Value::Nil | Value::Map(_) | Value::Vector(_) => Ok(lookup(&coll, &key)
+                    .map(|value| Value::Vector(vec![key, value]))
+                    .unwrap_or(Value::Nil)),
+                other => Err(EvalError::TypeError {
+                    expected: "map or vector".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    // The maps merged from left to right, so the value of a key in a later map
+    // wins. nil arguments are skipped, and with no maps the result is nil
+    env.borrow_mut().set(
+        "merge".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            merge_maps(args, &mut |_, new| Ok(new))
+        }))),
+    );
+    
+    // Like merge, but a key present in more than one map gets the result of
+    // calling f on the value so far and the later value:
+    // (merge-with + {:a 1} {:a 2}) is {:a 3}
+    env.borrow_mut().set(
+        "merge-with".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let mut args = args.into_iter();
+            let f = args.next().ok_or(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            })?;
+            merge_maps(args.collect(), &mut |old, new| reader::call(&f, vec![old, new], env))
+        }))),
+    );
+    
+    // The map with only the given keys that are present in it:
+    // (select-keys {:a 1 :b 2} [:a :c]) is {:a 1}
+    env.borrow_mut().set(
+        "select-keys".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 2 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                });
+            }
+            
+            let coll = &args[0];
+            if !matches!(coll, Value::Nil | Value::Map(_)) {
+                return Err(EvalError::TypeError {
+                    expected: "map".to_string(),
+                    got: format!("{:?}", coll),
+                });
+            }
+            Ok(Value::Map(
+                seq(&args[1])?
+                    .filter_map(|key| lookup(coll, &key).map(|value| (key, value)))
+                    .collect(),
+            ))
+        }))),
+    );
+    
+    // A map pairing each key with the value at the same position, stopping at
+    // the shorter sequence. A repeated key takes its last value:
+    // (zipmap [:a :b] [1 2]) is {:a 1 :b 2}
+    env.borrow_mut().set(
+        "zipmap".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 2 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                });
+            }
+            
+            Ok(Value::Map(seq(&args[0])?.zip(seq(&args[1])?).collect()))
+        }))),
+    );
+}
+
+/// Merges maps from left to right, combining the values of a key found in more
+/// than one map with `combine`. nil maps are skipped, and the result is nil when
+/// there are no maps
+#[allow(clippy::mutable_key_type)]
+fn merge_maps(
+    maps: Vec<Value>,
+    combine: &mut dyn FnMut(Value, Value) -> Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    let mut merged: Option<HashMap<Value, Value>> = None;
+    for mut map in maps {
+        let entries = match &mut map {
+            Value::Nil => continue,
+            Value::Map(entries) => std::mem::take(entries),
+            other => return Err(EvalError::TypeError {
+                expected: "map".to_string(),
+                got: format!("{:?}", other),
+            }),
+        };
+        
+        let target = match merged.as_mut() {
+            Some(target) => target,
+            None => {
+                merged = Some(entries);
+                continue;
+            }
+        };
+        for (key, value) in entries {
+            let value = match target.remove(&key) {
+                Some(old) => combine(old, value)?,
+                None => value,
+            };
+            target.insert(key, value);
+        }
+    }
+    
+    Ok(merged.map(Value::Map).unwrap_or(Value::Nil))
+}
+
+/// Lists one part of each entry of a map, or returns nil for an empty map or nil
+fn map_column(coll: &Value, part: for<'a> fn(&'a Value, &'a Value) -> &'a Value) -> Result<Value, EvalError> {
+    match coll {
+        Value::Nil => Ok(Value::Nil),
+        Value::Map(entries) if entries.is_empty() => Ok(Value::Nil),
+        Value::Map(entries) => Ok(Value::List(
+            entries.iter().map(|(key, value)| part(key, value).clone()).collect(),
+        )),
+        other => Err(EvalError::TypeError {
+            expected: "map".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+/// Splits the arguments of update and update-in, (update m k f & args), into
+/// the first three and the extra arguments for f
+fn update_args(mut args: Vec<Value>) -> Result<([Value; 3], Vec<Value>), EvalError> {
+    if args.len() < 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 3,
+            got: args.len(),
+        });
+    }
+    
+    let extra = args.split_off(3);
+    let fixed = <[Value; 3]>::try_from(args).map_err(|args| EvalError::ArityMismatch {
+        expected: 3,
+        got: args.len(),
+    })?;
+    Ok((fixed, extra))
+}
+
+/// Calls f with a value followed by extra arguments
+fn call_with(f: &Value, value: Value, extra: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    let mut args = vec![value];
+    args.extend_from_slice(extra);
+    reader::call(f, args, env)
+}
+
+/// Sets a key of a map, or an index of a vector, to a value. An index one past
+/// the end of a vector appends, and nil is taken as an empty map
+fn assoc_entry(mut coll: Value, key: Value, value: Value) -> Result<Value, EvalError> {
+    match &mut coll {
+        Value::Nil => Ok(Value::Map(HashMap::from([(key, value)]))),
+        Value::Map(entries) => {
+            entries.insert(key, value);
+            Ok(coll)
+        }
+        Value::Vector(items) => {
+            let index = match key {
+                Value::Number(n) if n.fract() == 0.0 => n as i64,
+                other => return Err(EvalError::TypeError {
+                    expected: "integer index".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            };
+            match usize::try_from(index) {
+                Ok(i) if i < items.len() => items[i] = value,
+                Ok(i) if i == items.len() => items.push(value),
+                _ => return Err(EvalError::IndexOutOfBounds {
+                    index,
+                    length: items.len(),
+                }),
+            }
+            Ok(coll)
+        }
+        other => Err(EvalError::TypeError {
+            expected: "map or vector".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+/// Takes the value of a key of a map or an index of a vector out of it, leaving
+/// nil in its place, or returns nil when there is none. This lets nested
+/// updates rebuild each collection on the path without copying its contents
+fn take_entry(coll: &mut Value, key: &Value) -> Value {
+    match (coll, key) {
+        (Value::Map(entries), _) => entries.remove(key).unwrap_or(Value::Nil),
+        (Value::Vector(items), Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => items
+            .get_mut(*n as usize)
+            .map(|item| std::mem::replace(item, Value::Nil))
+            .unwrap_or(Value::Nil),
+        _ => Value::Nil,
+    }
+}
+
+/// Replaces the value at a path of keys through nested collections with the
+/// result of `f` on it, which is nil when it is missing. Missing collections
+/// along the path are created as maps, and an empty path replaces the whole
+/// collection
+fn update_path(
+    mut coll: Value,
+    path: &[Value],
+    f: &mut dyn FnMut(Value) -> Result<Value, EvalError>,
+) -> Result<Value, EvalError> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return f(coll),
+    };
+    
+    let current = take_entry(&mut coll, key);
+    let updated = update_path(current, rest, f)?;
+    assoc_entry(coll, key.clone(), updated)
+}
+
+/// Looks a key up in a map, an index up in a vector or string, or an element up
+/// in a set. Returns None when it is missing, or the value has no keys
+fn lookup(coll: &Value, key: &Value) -> Option<Value> {
+    let index = || match key {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+        _ => None,
+    };
+    
+    match coll {
+        Value::Map(entries) => entries.get(key).cloned(),
+        Value::Set(items) => items.get(key).cloned(),
+        Value::Vector(items) => items.get(index()?).cloned(),
+        Value::String(s) => s.chars().nth(index()?).map(|c| Value::String(c.to_string())),
+        _ => None,
+    }
+}
+
+/// Register operations on sets (union, intersection, difference, subset?,
+/// superset?)
+#[allow(clippy::mutable_key_type)]
+fn register_set_ops(env: &Rc<RefCell<Environment>>) {
+    // The set of the elements in any of the sets. With no sets it is #{}
+    env.borrow_mut().set(
+        "union".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut sets = set_args(args)?.into_iter();
+            let mut union = sets.next().unwrap_or_default();
+            for set in sets {
+                union.extend(set);
+            }
+            Ok(Value::Set(union))
+        }))),
+    );
+    
+    // The set of the elements in every one of the sets
+    env.borrow_mut().set(
+        "intersection".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut sets = set_args(args)?.into_iter();
+            let mut intersection = sets.next().ok_or(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            })?;
+            for set in sets {
+                intersection.retain(|item| set.contains(item));
+            }
+            Ok(Value::Set(intersection))
+        }))),
+    );
+    
+    // The set of the elements of the first set that are in none of the others
+    env.borrow_mut().set(
+        "difference".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let mut sets = set_args(args)?.into_iter();
+            let mut difference = sets.next().ok_or(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            })?;
+            for set in sets {
+                difference.retain(|item| !set.contains(item));
+            }
+            Ok(Value::Set(difference))
+        }))),
+    );
+    
+    // Whether every element of the first set is in the second
+    env.borrow_mut().set(
+        "subset?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [a, b] = set_pair(args)?;
+            Ok(Value::Boolean(a.is_subset(&b)))
+        }))),
+    );
+    
+    // Whether every element of the second set is in the first
+    env.borrow_mut().set(
+        "superset?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [a, b] = set_pair(args)?;
+            Ok(Value::Boolean(a.is_superset(&b)))
+        }))),
+    );
+}
+
+/// Takes the elements out of the arguments of a set operation, which must all
+/// be sets
+fn set_args(args: Vec<Value>) -> Result<Vec<HashSet<Value>>, EvalError> {
+    args.into_iter()
+        .map(|mut arg| match &mut arg {
+            Value::Set(items) => Ok(std::mem::take(items)),
+            other => Err(EvalError::TypeError {
+                expected: "set".to_string(),
+                got: format!("{:?}", other),
+            }),
+        })
+        .collect()
+}
+
+/// Takes the elements out of the two sets compared by subset? and superset?
+fn set_pair(args: Vec<Value>) -> Result<[HashSet<Value>; 2], EvalError> {
+    let got = args.len();
+    <[HashSet<Value>; 2]>::try_from(set_args(args)?).map_err(|_| EvalError::ArityMismatch {
+        expected: 2,
+        got,
+    })
+}
+
+/// Register string functions (subs, upper-case, lower-case, capitalize, trim,
+/// triml, trimr, str, split, join, replace, starts-with?, ends-with?,
+/// includes?, index-of, last-index-of, blank?, format). Indices count
+/// characters, not bytes
+fn register_string_ops(env: &Rc<RefCell<Environment>>) {
+    // The characters of a string from start up to, but not including, end, or
+    // to the end of the string: (subs "hello" 1 3) is "el"
+    env.borrow_mut().set(
+        "subs".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (s, start, end) = match args.as_slice() {
+                [s, start] => (string_value(s)?, index_value(start)?, None),
+                [s, start, end] => (string_value(s)?, index_value(start)?, Some(index_value(end)?)),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            let length = s.chars().count();
+            let end = end.unwrap_or(length as i64);
+            if start < 0 || start as usize > length {
+                return Err(EvalError::IndexOutOfBounds { index: start, length });
+            }
+            if end < start || end as usize > length {
+                return Err(EvalError::IndexOutOfBounds { index: end, length });
+            }
+            
+            let substring = s.chars().skip(start as usize).take((end - start) as usize).collect();
+            Ok(Value::String(substring))
+        }))),
+    );
+    
+    // The string in upper case
+    env.borrow_mut().set(
+        "upper-case".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, str::to_uppercase)
+        }))),
+    );
+    
+    // The string in lower case
+    env.borrow_mut().set(
+        "lower-case".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, str::to_lowercase)
+        }))),
+    );
+    
+    // The string with its first character in upper case and the rest in lower
+    // case
+    env.borrow_mut().set(
+        "capitalize".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, |s| {
+                let mut chars = s.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                    None => String::new(),
+                }
+            })
+        }))),
+    );
+    
+    // The string without whitespace at either end
+    env.borrow_mut().set(
+        "trim".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, |s| s.trim().to_string())
+        }))),
+    );
+    
+    // The string without whitespace at the start
+    env.borrow_mut().set(
+        "triml".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, |s| s.trim_start().to_string())
+        }))),
+    );
+    
+    // The string without whitespace at the end
+    env.borrow_mut().set(
+        "trimr".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            map_string(&args, |s| s.trim_end().to_string())
+        }))),
+    );
+    
+    // A vector of the parts of a string between occurrences of a separator,
+    // with at most limit parts when a limit is given. Empty parts are kept, and
+    // an empty separator splits the string into its characters:
+    // (split "a,b,,c" ",") is ["a" "b" "" "c"]
+    env.borrow_mut().set(
+        "split".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (s, separator, limit) = match args.as_slice() {
+                [s, separator] => (string_value(s)?, string_value(separator)?, usize::MAX),
+                [s, separator, limit] => {
+                    let limit = match limit {
+                        Value::Number(n) if *n >= 1.0 && n.fract() == 0.0 => *n as usize,
+                        other => return Err(EvalError::TypeError {
+                            expected: "positive integer limit".to_string(),
+                            got: format!("{:?}", other),
+                        }),
+                    };
+                    (string_value(s)?, string_value(separator)?, limit)
+                }
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            let parts = if separator.is_empty() {
+                // One part per character, with the rest of the string as the
+                // last part once the limit is reached
+                let mut parts = Vec::new();
+                let mut rest = s;
+                while let Some(c) = rest.chars().next().filter(|_| parts.len() + 1 < limit) {
+                    parts.push(c.to_string());
+                    rest = &rest[c.len_utf8()..];
+                }
+                if !rest.is_empty() || parts.is_empty() {
+                    parts.push(rest.to_string());
+                }
+                parts
+            } else {
+                s.splitn(limit, separator).map(str::to_string).collect()
+            };
+            Ok(Value::Vector(parts.into_iter().map(Value::String).collect()))
+        }))),
+    );
+    
+    // The arguments joined into a string: (str "n=" 5) is "n=5". Strings are
+    // joined without quotes and nil as an empty string
+    env.borrow_mut().set(
+        "str".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::String(args.iter().map(text_of).collect()))
+        }))),
+    );
+    
+    // The elements of a collection joined into a string, with an optional
+    // separator between them: (join ", " [1 2 3]) is "1, 2, 3". Strings are
+    // joined without quotes and nil as an empty string
+    env.borrow_mut().set(
+        "join".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (separator, coll) = match args.as_slice() {
+                [coll] => ("", coll),
+                [separator, coll] => (string_value(separator)?, coll),
+                _ => return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                }),
+            };
+            
+            let parts: Vec<String> = seq(coll)?.map(|item| text_of(&item)).collect();
+            Ok(Value::String(parts.join(separator)))
+        }))),
+    );
+    
+    // The string with every occurrence of a target string replaced:
+    // (replace "a-b-c" "-" "+") is "a+b+c"
+    env.borrow_mut().set(
+        "replace".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            match args.as_slice() {
+                [s, target, replacement] => Ok(Value::String(
+                    string_value(s)?.replace(string_value(target)?, string_value(replacement)?),
+                )),
+                _ => Err(EvalError::ArityMismatch {
+                    expected: 3,
+                    got: args.len(),
+                }),
+            }
+        }))),
+    );
+    
+    // Whether a string starts with another
+    env.borrow_mut().set(
+        "starts-with?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [s, prefix] = string_pair(&args)?;
+            Ok(Value::Boolean(s.starts_with(prefix)))
+        }))),
+    );
+    
+    // Whether a string ends with another
+    env.borrow_mut().set(
+        "ends-with?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [s, suffix] = string_pair(&args)?;
+            Ok(Value::Boolean(s.ends_with(suffix)))
+        }))),
+    );
+    
+    // Whether a string contains another
+    env.borrow_mut().set(
+        "includes?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let [s, part] = string_pair(&args)?;
+            Ok(Value::Boolean(s.contains(part)))
+        }))),
+    );
+    
+    // The index of the first occurrence of a string in another, starting from
+    // an optional index, or nil when there is none: (index-of "banana" "an" 2)
+    // is 3. A start before the beginning searches the whole string
+    env.borrow_mut().set(
+        "index-of".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (s, part, from) = search_args(&args)?;
+            let start = byte_offset(s, from.unwrap_or(0).max(0));
+            Ok(s[start..]
+                .find(part)
+                .map(|found| Value::Number(char_index(s, start + found) as f64))
+                .unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // The index of the last occurrence of a string in another, starting at or
+    // before an optional index, or nil when there is none:
+    // (last-index-of "banana" "an" 2) is 1
+    env.borrow_mut().set(
+        "last-index-of".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (s, part, from) = search_args(&args)?;
+            let last = match from {
+                Some(from) if from < 0 => return Ok(Value::Nil),
+                Some(from) => byte_offset(s, from),
+                None => s.len(),
+            };
+            // Search only the text a match starting at or before last can
+            // reach, so matches overlapping a later one are still found
+            let mut end = (last + part.len()).min(s.len());
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            Ok(s[..end].rfind(part)
+                .map(|found| Value::Number(char_index(s, found) as f64))
+                .unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // Whether a string is nil, empty or only whitespace
+    env.borrow_mut().set(
+        "blank?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            match single_arg(&args)? {
+                Value::Nil => Ok(Value::Boolean(true)),
+                other => Ok(Value::Boolean(string_value(other)?.trim().is_empty())),
+            }
+        }))),
+    );
+    
+    // A string built from a template with printf style directives filled in by
+    // the arguments in order: (format "%s scored %5.1f%%" "ann" 97.5) is
+    // "ann scored  97.5%". See format_string for the directives
+    env.borrow_mut().set(
+        "format".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let (template, args) = args.split_first().ok_or(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            })?;
+            Ok(Value::String(format_string(string_value(template)?, args)?))
+        }))),
+    );
+}
+
+/// The largest width or precision a format directive may have, so a template
+/// can't make format allocate without bound
+const MAX_FORMAT_WIDTH: usize = 4096;
+
+/// A directive of a format template, such as `%-10s` or `%08.2f`
+struct Directive {
+    /// The directive as written, for error messages
+    text: String,
+    /// Pad on the right instead of the left (the `-` flag)
+    left: bool,
+    /// Pad numbers with zeros instead of spaces (the `0` flag)
+    zeros: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+/// Fills in the directives of a format template with the arguments, in order.
+/// The directives are `%s` (any value, as join prints it), `%d` (an integer),
+/// `%f` (a number, with 6 decimals unless a precision such as `%.2f` is given)
+/// and `%%` (a percent sign). A width pads the result to that many characters,
+/// on the left, or on the right with the `-` flag, and the `0` flag pads
+/// numbers with zeros. `%.Ns` keeps at most N characters.
+///
+/// An argument of the wrong type, a directive without an argument, or an
+/// argument without a directive is an error naming the directive, counted from
+/// 1 as the arguments are.
+fn format_string(template: &str, args: &[Value]) -> Result<String, EvalError> {
+    let mut formatted = String::new();
+    let mut chars = template.chars().peekable();
+    let mut index = 0;
+    
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        
+        let directive = parse_directive(&mut chars)?;
+        if directive.conversion == '%' {
+            formatted.push('%');
+            continue;
+        }
+        
+        index += 1;
+        let arg = args.get(index - 1).ok_or_else(|| {
+            EvalError::Other(format!("No argument for format directive {} ({})", index, directive.text))
+        })?;
+        formatted.push_str(&format_directive(&directive, index, arg)?);
+    }
+    
+    if args.len() > index {
+        return Err(EvalError::Other(format!(
+            "Format template has {} directives, got {} arguments",
+            index,
+            args.len()
+        )));
+    }
+    Ok(formatted)
+}
+
+/// Parses the directive following a `%`: flags, width, precision and conversion
+fn parse_directive(chars: &mut Peekable<Chars>) -> Result<Directive, EvalError> {
+    let mut text = String::from("%");
+    
+    let (mut left, mut zeros) = (false, false);
+    while let Some(&flag @ ('-' | '0')) = chars.peek() {
+        left |= flag == '-';
+        zeros |= flag == '0';
+        take_char(chars, &mut text);
+    }
+    
+    let width = take_digits(chars, &mut text)?;
+    let precision = match chars.peek() {
+        Some('.') => {
+            take_char(chars, &mut text);
+            Some(take_digits(chars, &mut text)?)
+        }
+        _ => None,
+    };
+    
+    let conversion = take_char(chars, &mut text)
+        .ok_or_else(|| EvalError::Other(format!("Incomplete format directive {} at the end of the template", text)))?;
+    match conversion {
+        's' | 'd' | 'f' | '%' => {}
+        _ => return Err(EvalError::Other(format!("Unknown format directive {}", text))),
+    }
+    if conversion == 'd' && precision.is_some() {
+        return Err(EvalError::Other(format!("Format directive {} can't have a precision", text)));
+    }
+    
+    Ok(Directive {
+        text,
+        left,
+        zeros,
+        width,
+        precision,
+        conversion,
+    })
+}
+
+/// Takes the next character of a directive, adding it to the directive's text
+fn take_char(chars: &mut Peekable<Chars>, text: &mut String) -> Option<char> {
+    let c = chars.next()?;
+    text.push(c);
+    Some(c)
+}
+
+/// Takes the digits of a width or precision, which are 0 when there are none.
+/// Values above MAX_FORMAT_WIDTH are an error
+fn take_digits(chars: &mut Peekable<Chars>, text: &mut String) -> Result<usize, EvalError> {
+    let mut value = Some(0usize);
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        value = value
+            .and_then(|v| v.checked_mul(10))
+            .and_then(|v| v.checked_add(digit as usize));
+        take_char(chars, text);
+    }
+    
+    match value {
+        Some(value) if value <= MAX_FORMAT_WIDTH => Ok(value),
+        _ => Err(EvalError::Other(format!(
+            "Width or precision of format directive {} is above the maximum of {}",
+            text, MAX_FORMAT_WIDTH
+        ))),
+    }
+}
+
+/// Formats the argument of a directive and pads it to the directive's width
+fn format_directive(directive: &Directive, index: usize, arg: &Value) -> Result<String, EvalError> {
+    let type_error = |expected: &str| EvalError::TypeError {
+        expected: format!("{} for format directive {} ({})", expected, index, directive.text),
+        got: format!("{:?}", arg),
+    };
+    
+    let text = match directive.conversion {
+        's' => {
+            let text = text_of(arg);
+            match directive.precision {
+                Some(precision) => text.chars().take(precision).collect(),
+                None => text,
+            }
+        }
+        'd' => match arg {
+            Value::Number(n) if n.fract() == 0.0 => format!("{:.0}", n),
+            _ => return Err(type_error("integer")),
+        },
+        _ => match arg {
+            Value::Number(n) => format!("{:.*}", directive.precision.unwrap_or(6), n),
+            _ => return Err(type_error("number")),
+        },
+    };
+    
+    let length = text.chars().count();
+    if length >= directive.width {
+        return Ok(text);
+    }
+    
+    let fill = directive.width - length;
+    if directive.left {
+        Ok(format!("{}{}", text, " ".repeat(fill)))
+    } else if directive.zeros && directive.conversion != 's' {
+        // Zeros go between the sign and the digits
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", text.as_str()),
+        };
+        Ok(format!("{}{}{}", sign, "0".repeat(fill), digits))
+    } else {
+        Ok(format!("{}{}", " ".repeat(fill), text))
+    }
+}
+
+/// Returns the two string arguments of a string predicate
+fn string_pair(args: &[Value]) -> Result<[&str; 2], EvalError> {
+    match args {
+        [a, b] => Ok([string_value(a)?, string_value(b)?]),
+        _ => Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        }),
+    }
+}
+
+/// Returns the string, the string to search for and the optional start index of
+/// index-of and last-index-of
+fn search_args(args: &[Value]) -> Result<(&str, &str, Option<i64>), EvalError> {
+    match args {
+        [s, part] => Ok((string_value(s)?, string_value(part)?, None)),
+        [s, part, from] => Ok((string_value(s)?, string_value(part)?, Some(index_value(from)?))),
+        _ => Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        }),
+    }
+}
+
+/// Returns the byte offset of a character index in a string, or the length of
+/// the string for an index past its end
+fn byte_offset(s: &str, index: i64) -> usize {
+    s.char_indices()
+        .nth(index as usize)
+        .map(|(offset, _)| offset)
+        .unwrap_or(s.len())
+}
+
+/// Returns the character index of a byte offset in a string
+fn char_index(s: &str, offset: usize) -> usize {
+    s[..offset].chars().count()
+}
+
+/// Returns the text of a string argument
+fn string_value(value: &Value) -> Result<&str, EvalError> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(EvalError::TypeError {
+            expected: "string".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+/// Returns an integer index argument
+fn index_value(value: &Value) -> Result<i64, EvalError> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 => Ok(*n as i64),
+        other => Err(EvalError::TypeError {
+            expected: "integer index".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+/// Applies a function to the single string argument of a builtin
+fn map_string(args: &[Value], f: impl Fn(&str) -> String) -> Result<Value, EvalError> {
+    Ok(Value::String(f(string_value(single_arg(args)?)?)))
+}
+
+/// Returns the text of a value as it appears in a joined string: strings
+/// without quotes, nil as an empty string, and anything else as it is printed
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Nil => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Register symbol operations (gensym)
+fn register_symbol_ops(env: &Rc<RefCell<Environment>>) {
+    // Generate a unique symbol, with an optional prefix (G__ by default)
+    env.borrow_mut().set(
+        "gensym".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if args.len() > 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
+            }
+            
+            let prefix = match args.first() {
+                None => "G__".to_string(),
+                Some(Value::String(s)) | Some(Value::Symbol(s)) => s.clone(),
+                Some(other) => return Err(EvalError::TypeError {
+                    expected: "string or symbol".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            };
+            
+            let name = env.borrow().context().gensym(&prefix);
+            Ok(Value::Symbol(name))
+        }))),
+    );
+}
+
+/// Register error operations (throw, *assert*)
+fn register_error_ops(env: &Rc<RefCell<Environment>>) {
+    // Whether assert and :pre/:post conditions are checked. Dynamic, so checks
+    // can be turned off with (binding [*assert* false] ...) or set! globally
+    env.borrow_mut().set("*assert*".to_string(), Value::Boolean(true));
+    env.borrow().context().declare_dynamic("*assert*");
+    
+    // Raise a value as an error, to be caught by try
+    env.borrow_mut().set(
+        "throw".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
+            }
+            
+            Err(EvalError::Thrown(args[0].clone()))
+        }))),
+    );
+}
+
+/// Register function operations (apply, identity, constantly, partial, comp,
+/// complement)
+fn register_function_ops(env: &Rc<RefCell<Environment>>) {
+    // Call a function with the given arguments, the last of which is a sequence
+    // spread into the argument list: (apply f a b [c d]) is (f a b c d)
+    env.borrow_mut().set(
+        "apply".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if args.len() < 2 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 2,
+                    got: args.len(),
+                });
+            }
+            
+            let mut call_args = args[1..args.len() - 1].to_vec();
+            match &args[args.len() - 1] {
+                Value::Nil => {}
+                Value::List(items) | Value::Vector(items) => call_args.extend(items.iter().cloned()),
+                other => return Err(EvalError::TypeError {
+                    expected: "list or vector".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+            
+            reader::call(&args[0], call_args, env)
+        }))),
+    );
+    
+    // Return the argument
+    env.borrow_mut().set(
+        "identity".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            single_arg(&args).cloned()
+        }))),
+    );
+    
+    // A function that takes any arguments and always returns the value
+    env.borrow_mut().set(
+        "constantly".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let value = single_arg(&args)?.clone();
+            Ok(Value::Function(Rc::new(Function::builtin(move |_args, _env| Ok(value.clone())))))
+        }))),
+    );
+    
+    // A function that calls f with the given arguments before its own:
+    // ((partial + 1 2) 3) is (+ 1 2 3)
+    env.borrow_mut().set(
+        "partial".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.is_empty() {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: 0,
+                });
+            }
+            
+            let mut args = args.into_iter();
+            let f = args.next().unwrap_or(Value::Nil);
+            let captured: Vec<Value> = args.collect();
+            Ok(Value::Function(Rc::new(Function::builtin(move |args, env| {
+                let mut call_args = captured.clone();
+                call_args.extend(args);
+                reader::call(&f, call_args, env)
+            }))))
+        }))),
+    );
+    
+    // The composition of functions, applied right to left: ((comp f g) x) is
+    // (f (g x)). The last function may take any number of arguments, and the
+    // composition of no functions is identity
+    env.borrow_mut().set(
+        "comp".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let functions = args;
+            Ok(Value::Function(Rc::new(Function::builtin(move |args, env| {
+                let mut functions = functions.iter().rev();
+                let mut result = match functions.next() {
+                    Some(last) => reader::call(last, args, env)?,
+                    None => single_arg(&args)?.clone(),
+                };
+                for f in functions {
+                    result = reader::call(f, vec![result], env)?;
+                }
+                Ok(result)
+            }))))
+        }))),
+    );
+    
+    // A function returning the opposite truthiness of f, as a boolean
+    env.borrow_mut().set(
+        "complement".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            let f = single_arg(&args)?.clone();
+            Ok(Value::Function(Rc::new(Function::builtin(move |args, env| {
+                Ok(Value::Boolean(!reader::call(&f, args, env)?.is_truthy()))
+            }))))
+        }))),
+    );
+}
+
+/// Register operations on delays (force, deref, realized?, delay?)
+fn register_delay_ops(env: &Rc<RefCell<Environment>>) {
+    // The value of a delay, running its body the first time. Other values are
+    // returned as they are
+    env.borrow_mut().set(
+        "force".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            reader::force(single_arg(&args)?)
+        }))),
+    );
+    
+    // The value of a delay, or of a value wrapped by reduced
+    env.borrow_mut().set(
+        "deref".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            match single_arg(&args)? {
+                delay @ Value::Delay(_) => reader::force(delay),
+                Value::Reduced(value) => Ok((**value).clone()),
+                other => Err(EvalError::TypeError {
+                    expected: "delay or reduced value".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    // Whether a delay has been forced
+    env.borrow_mut().set(
+        "realized?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            match single_arg(&args)? {
+                Value::Delay(delay) => Ok(Value::Boolean(matches!(*delay.borrow(), DelayState::Realized(_)))),
+                other => Err(EvalError::TypeError {
+                    expected: "delay".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            }
+        }))),
+    );
+    
+    env.borrow_mut().set(
+        "delay?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            Ok(Value::Boolean(matches!(single_arg(&args)?, Value::Delay(_))))
+        }))),
+    );
+}
+
+/// Register operations that load other files (load-file, require)
+fn register_load_ops(env: &Rc<RefCell<Environment>>) {
+    // Evaluate the forms of a file, returning the value of the last one
+    env.borrow_mut().set(
+        "load-file".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if args.len() != 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
+            }
+            
+            match &args[0] {
+                Value::String(path) => reader::load_file(std::path::Path::new(path), env),
+                _ => Err(EvalError::TypeError {
+                    expected: "string".to_string(),
+                    got: format!("{:?}", args[0]),
+                }),
+            }
+        }))),
+    );
+    
+    // Load the file for each name from the load path, once
+    env.borrow_mut().set(
+        "require".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            for arg in &args {
+                match arg {
+                    Value::Symbol(name) | Value::String(name) => {
+                        reader::require(name, env)?;
+                    }
+                    _ => return Err(EvalError::TypeError {
+                        expected: "symbol".to_string(),
+                        got: format!("{:?}", arg),
+                    }),
+                }
+            }
+            
+            Ok(Value::Nil)
+        }))),
+    );
+}
+
+/// Register reading and evaluation of code as data
+fn register_reader_ops(env: &Rc<RefCell<Environment>>) {
+    // Read a string as data, without evaluating it. Several forms are read as a list
+    env.borrow_mut().set(
+        "read-string".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, _env| {
+            if args.len() != 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
+            }
+            
+            match &args[0] {
+                Value::String(source) => crate::read_str(source),
+                _ => Err(EvalError::TypeError {
+                    expected: "string".to_string(),
+                    got: format!("{:?}", args[0]),
+                }),
+            }
+        }))),
+    );
+    
+    // Evaluate a form. As with def, it is evaluated in the root environment, so
+    // the locals of the caller are not visible to it
+    env.borrow_mut().set(
+        "eval".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if args.len() != 1 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: args.len(),
+                });
+            }
+            
+            reader::eval(&args[0], &Environment::root(env))
+        }))),
+    );
+}
+
+/// Register introspection of the environment (resolve, bound?, env-keys)
+fn register_env_ops(env: &Rc<RefCell<Environment>>) {
+    // The value a symbol is bound to where it is called, or nil
+    env.borrow_mut().set(
+        "resolve".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let name = symbol_arg(&args)?;
+            Ok(env.borrow().get(name).unwrap_or(Value::Nil))
+        }))),
+    );
+    
+    // Whether a symbol is bound where it is called
+    env.borrow_mut().set(
+        "bound?".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let name = symbol_arg(&args)?;
+            Ok(Value::Boolean(env.borrow().get(name).is_some()))
+        }))),
+    );
+    
+    // The sorted names visible where it is called, as a vector of symbols
+    env.borrow_mut().set(
+        "env-keys".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            if !args.is_empty() {
+                return Err(EvalError::ArityMismatch {
+                    expected: 0,
+                    got: args.len(),
+                });
+            }
+            
+            let keys = env.borrow().all_keys();
+            Ok(Value::Vector(keys.into_iter().map(Value::Symbol).collect()))
+        }))),
+    );
+}
+
+/// Returns the name of the single symbol argument of a builtin
+fn symbol_arg(args: &[Value]) -> Result<&str, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: args.len(),
+        });
+    }
+    
+    match &args[0] {
+        Value::Symbol(name) => Ok(name),
+        _ => Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", args[0]),
+        }),
+    }
+}
+
+/// Iterates over the elements of a collection or the characters of a string
+fn seq(coll: &Value) -> Result<Box<dyn Iterator<Item = Value> + '_>, EvalError> {
+    coll.try_iter().ok_or_else(|| EvalError::TypeError {
+        expected: "collection or string".to_string(),
+        got: format!("{:?}", coll),
+    })
+}
+
+/// Iterates over the single argument of a builtin that takes a collection or string
+fn seq_arg(args: &[Value]) -> Result<Box<dyn Iterator<Item = Value> + '_>, EvalError> {
+    seq(single_arg(args)?)
+}
+
+/// Returns the count and the elements of the collection of take and drop. A
+/// negative count is taken as 0
+fn count_and_seq(args: &[Value]) -> Result<(usize, Box<dyn Iterator<Item = Value> + '_>), EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        });
+    }
+    
+    match &args[0] {
+        Value::Number(n) if n.fract() == 0.0 => Ok((n.max(0.0) as usize, seq(&args[1])?)),
+        other => Err(EvalError::TypeError {
+            expected: "integer count".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+}
+
+/// Returns the function and the elements of the collection of a builtin such as
+/// take-while, which calls a function on each element
+fn function_and_seq(args: &[Value]) -> Result<(&Value, Box<dyn Iterator<Item = Value> + '_>), EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: args.len(),
+        });
+    }
+    
+    Ok((&args[0], seq(&args[1])?))
+}
+
+/// Returns the only argument of a builtin that takes one
+fn single_arg(args: &[Value]) -> Result<&Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: args.len(),
+        });
+    }
+    
+    Ok(&args[0])
+}
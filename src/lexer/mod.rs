@@ -82,10 +82,11 @@ impl<'a> Lexer<'a> {
                 '"' => self.lex_string(),
                 '\\' => self.lex_character(),
                 ':' => self.lex_keyword(),
-                c if is_symbol_start(c) => self.lex_symbol(c),
-                c if c.is_ascii_digit() || (c == '-' && self.peek().map_or(false, |next| next.is_ascii_digit())) => {
+                // Checked before symbols, so -7 is a number rather than a symbol
+                c if c.is_ascii_digit() || (c == '-' && self.peek().is_some_and(|next| next.is_ascii_digit())) => {
                     self.lex_number(c)
                 }
+                c if is_symbol_start(c) => self.lex_symbol(c),
                 _c => {
                     // Handle unexpected character
                     TokenKind::Error
@@ -333,7 +334,7 @@ impl<'a> Lexer<'a> {
                     self.bump();
                     
                     // Ensure there's at least one digit after the decimal point
-                    if !self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                    if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
                         return TokenKind::Error;
                     }
                 }
@@ -347,7 +348,7 @@ impl<'a> Lexer<'a> {
                     }
                     
                     // Ensure there's at least one digit after the exponent
-                    if !self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                    if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
                         return TokenKind::Error;
                     }
                 }
@@ -366,7 +367,7 @@ impl<'a> Lexer<'a> {
                     self.bump();
                     
                     // Ensure there's at least one digit after the slash
-                    if !self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                    if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
                         return TokenKind::Error;
                     }
                     
@@ -431,15 +432,17 @@ impl<'a> Lexer<'a> {
 
 /// Checks if a character can start a symbol
 fn is_symbol_start(c: char) -> bool {
-    match c {
-        'a'..='z' | 'A'..='Z' | '!' | '?' | '-' | '+' | '<' | '>' | '=' | '$' | '*' | '%' | '_' | '/' => true,
-        _ => false,
-    }
+    matches!(
+        c,
+        'a'..='z' | 'A'..='Z' | '!' | '?' | '-' | '+' | '<' | '>' | '=' | '$' | '*' | '%' | '_' | '/' | '&'
+    )
 }
 
-/// Checks if a character can be part of a symbol
+/// Checks if a character can be part of a symbol. A `#` may follow the first
+/// character, for auto-gensym symbols such as `x#`, and a `.` for dotted names
+/// such as `my.util`
 fn is_symbol_char(c: char) -> bool {
-    is_symbol_start(c) || c.is_ascii_digit()
+    is_symbol_start(c) || c.is_ascii_digit() || c == '#' || c == '.'
 }
 
 
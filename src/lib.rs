@@ -22,10 +22,23 @@ pub fn tokenize(input: &str) -> Vec<lexer::Token> {
     lexer.tokenize()
 }
 
-/// Reads the given input and returns a Citrine value
+/// Parses the given input and returns a syntax tree along with any parse errors
+pub fn parse_with_errors(input: &str) -> (syntax::SyntaxNode, Vec<parser::ParserError>) {
+    let parser = parser::Parser::new(input);
+    parser.parse_with_errors()
+}
+
+/// Reads the given input and returns a Citrine value. Malformed input, such as
+/// an unclosed list, is reported as a syntax error
 pub fn read_str(input: &str) -> Result<reader::Value, reader::EvalError> {
-    let syntax = parse(input);
-    reader::read(&syntax)
+    let (syntax, errors) = parse_with_errors(input);
+    let result = match errors.first() {
+        Some(error) => Err(reader::EvalError::SyntaxError(error.to_string())),
+        None => reader::read(&syntax),
+    };
+
+    syntax::drop_tree(syntax);
+    result
 }
 
 /// Evaluates the given input in the given environment
@@ -34,6 +47,30 @@ pub fn eval_str(input: &str, env: &std::rc::Rc<std::cell::RefCell<reader::Enviro
     reader::eval(&value, env)
 }
 
+/// Evaluates the given input in the given environment, bounding the number of
+/// evaluation steps and the nesting depth. The environment's previous limits
+/// are restored afterwards
+pub fn eval_str_with_limits(
+    input: &str,
+    env: &std::rc::Rc<std::cell::RefCell<reader::Environment>>,
+    limits: reader::Limits,
+) -> Result<reader::Value, reader::EvalError> {
+    let context = env.borrow().context();
+    let previous_fuel = context.fuel();
+    let previous_depth = context.max_depth();
+
+    context.set_fuel(limits.max_steps);
+    if let Some(max_depth) = limits.max_depth {
+        context.set_max_depth(max_depth);
+    }
+
+    let result = eval_str(input, env);
+
+    context.set_fuel(previous_fuel);
+    context.set_max_depth(previous_depth);
+    result
+}
+
 /// Creates a new standard environment with built-in functions
 pub fn standard_env() -> std::rc::Rc<std::cell::RefCell<reader::Environment>> {
     builtins::standard_env()
@@ -17,15 +17,25 @@ pub enum ParserError {
     UnexpectedEof,
     #[error("unmatched delimiter: {0}")]
     UnmatchedDelimiter(String),
+    #[error("invalid token: {0}")]
+    InvalidToken(String),
 }
 
+/// Stack space left below which parsing a nested form continues on a fresh
+/// stack segment, and the size of that segment
+const STACK_RED_ZONE: usize = 64 * 1024;
+const STACK_SEGMENT_SIZE: usize = 1024 * 1024;
+
 /// A parser for the Citrine language
 pub struct Parser {
     /// The tokens to parse
     tokens: Peekable<IntoIter<Token>>,
     /// The builder for the syntax tree
     builder: GreenNodeBuilder<'static>,
-    // current field removed as it was unused
+    /// Nodes started but not yet finished in the current top-level form
+    open_nodes: usize,
+    /// Errors found so far
+    errors: Vec<ParserError>,
 }
 
 impl Parser {
@@ -37,20 +47,46 @@ impl Parser {
         Self {
             tokens,
             builder: GreenNodeBuilder::new(),
+            open_nodes: 0,
+            errors: Vec::new(),
         }
     }
 
-    /// Parses the input and returns a syntax tree
-    pub fn parse(mut self) -> SyntaxNode {
+    /// Parses the input and returns a syntax tree. The parser recovers from
+    /// errors, so the tree covers malformed input too; use `parse_with_errors`
+    /// to find out whether there were any.
+    pub fn parse(self) -> SyntaxNode {
+        self.parse_with_errors().0
+    }
+
+    /// Parses the input and returns a syntax tree along with the errors found,
+    /// in order
+    pub fn parse_with_errors(mut self) -> (SyntaxNode, Vec<ParserError>) {
         self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Root));
         
         while self.peek().is_some() {
+            self.open_nodes = 0;
             match self.parse_form() {
                 Ok(_) => {},
                 Err(e) => {
-                    // Handle error and try to recover
-                    eprintln!("Parse error: {}", e);
-                    self.skip_until_delimiter();
+                    self.errors.push(e);
+
+                    // Close the nodes of the form that failed, so the rest of
+                    // the input is parsed at the top level
+                    while self.open_nodes > 0 {
+                        self.finish_node();
+                    }
+
+                    // A stray closing delimiter can't start a form, so it is
+                    // kept in an error node to make progress
+                    match self.peek().map(|token| token.kind) {
+                        Some(TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace) => {
+                            self.start_node(SyntaxKind::Error);
+                            self.consume_token();
+                            self.finish_node();
+                        }
+                        _ => self.skip_until_delimiter(),
+                    }
                 }
             }
         }
@@ -58,11 +94,29 @@ impl Parser {
         self.builder.finish_node();
         
         let green: GreenNode = self.builder.finish();
-        SyntaxNode::new_root(green)
+        (SyntaxNode::new_root(green), self.errors)
     }
 
-    /// Parses a form
+    /// Starts a node of the given kind
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.builder.start_node(CitrineLanguage::kind_to_raw(kind));
+        self.open_nodes += 1;
+    }
+
+    /// Finishes the innermost node started with `start_node`
+    fn finish_node(&mut self) {
+        self.builder.finish_node();
+        self.open_nodes -= 1;
+    }
+
+    /// Parses a form. Nested forms are parsed by recursion, so the stack is
+    /// grown on the heap as needed for deeply nested input
     fn parse_form(&mut self) -> Result<(), ParserError> {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || self.parse_any_form())
+    }
+
+    /// Parses a form of any kind, dispatching on its first token
+    fn parse_any_form(&mut self) -> Result<(), ParserError> {
         match self.peek() {
             Some(token) => {
                 match token.kind {
@@ -93,9 +147,9 @@ impl Parser {
                     TokenKind::Keyword => self.parse_keyword(),
                     TokenKind::Symbol => self.parse_symbol(),
                     TokenKind::Comment => {
-                        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Comment));
+                        self.start_node(SyntaxKind::Comment);
                         self.consume_token();
-                        self.builder.finish_node();
+                        self.finish_node();
                         Ok(())
                     },
                     TokenKind::Whitespace => {
@@ -105,8 +159,15 @@ impl Parser {
                     TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
                         Err(ParserError::UnmatchedDelimiter(token.text.to_string()))
                     },
+                    TokenKind::Error => {
+                        // Keep invalid tokens in the tree, but report them
+                        let text = token.text.to_string();
+                        self.consume_token();
+                        self.errors.push(ParserError::InvalidToken(text));
+                        Ok(())
+                    }
                     _ => {
-                        // Skip invalid tokens
+                        // Skip other tokens
                         self.consume_token();
                         Ok(())
                     }
@@ -118,7 +179,7 @@ impl Parser {
 
     /// Parses a list
     fn parse_list(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::List));
+        self.start_node(SyntaxKind::List);
         
         // Consume the opening paren
         self.consume_token();
@@ -146,13 +207,13 @@ impl Parser {
             return Err(ParserError::UnexpectedEof);
         }
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a vector
     fn parse_vector(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Vector));
+        self.start_node(SyntaxKind::Vector);
         
         // Consume the opening bracket
         self.consume_token();
@@ -180,13 +241,13 @@ impl Parser {
             return Err(ParserError::UnexpectedEof);
         }
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a map
     fn parse_map(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Map));
+        self.start_node(SyntaxKind::Map);
         
         // Consume the opening brace
         self.consume_token();
@@ -229,13 +290,13 @@ impl Parser {
             return Err(ParserError::UnexpectedEof);
         }
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a set
     fn parse_set(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Set));
+        self.start_node(SyntaxKind::Set);
         
         // Consume the opening #{
         self.consume_token();
@@ -263,13 +324,13 @@ impl Parser {
             return Err(ParserError::UnexpectedEof);
         }
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a quote
     fn parse_quote(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Quote));
+        self.start_node(SyntaxKind::Quote);
         
         // Consume the quote
         self.consume_token();
@@ -277,13 +338,13 @@ impl Parser {
         // Parse the quoted form
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a backtick
     fn parse_backtick(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Backtick));
+        self.start_node(SyntaxKind::Backtick);
         
         // Consume the backtick
         self.consume_token();
@@ -291,13 +352,13 @@ impl Parser {
         // Parse the backquoted form
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses an unquote
     fn parse_unquote(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Comma));
+        self.start_node(SyntaxKind::Comma);
         
         // Consume the comma
         self.consume_token();
@@ -305,13 +366,13 @@ impl Parser {
         // Parse the unquoted form
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses an unquote-splicing
     fn parse_unquote_splicing(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::CommaAt));
+        self.start_node(SyntaxKind::CommaAt);
         
         // Consume the comma-at
         self.consume_token();
@@ -319,13 +380,13 @@ impl Parser {
         // Parse the unquote-spliced form
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a meta
     fn parse_meta(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Meta));
+        self.start_node(SyntaxKind::Meta);
         
         // Consume the caret
         self.consume_token();
@@ -336,13 +397,13 @@ impl Parser {
         // Parse the form with metadata
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a tag
     fn parse_tag(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Tag));
+        self.start_node(SyntaxKind::Tag);
         
         // Consume the hash
         self.consume_token();
@@ -350,13 +411,13 @@ impl Parser {
         // Parse the tag
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a discard
     fn parse_discard(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::Discard));
+        self.start_node(SyntaxKind::Discard);
         
         // Consume the hash
         self.consume_token();
@@ -378,47 +439,47 @@ impl Parser {
         // Parse the discarded form
         self.parse_form()?;
         
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a string
     fn parse_string(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::StringLit));
+        self.start_node(SyntaxKind::StringLit);
         self.consume_token();
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a number
     fn parse_number(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::NumberLit));
+        self.start_node(SyntaxKind::NumberLit);
         self.consume_token();
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a character
     fn parse_character(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::CharacterLit));
+        self.start_node(SyntaxKind::CharacterLit);
         self.consume_token();
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a keyword
     fn parse_keyword(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::KeywordLit));
+        self.start_node(SyntaxKind::KeywordLit);
         self.consume_token();
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
     /// Parses a symbol
     fn parse_symbol(&mut self) -> Result<(), ParserError> {
-        self.builder.start_node(CitrineLanguage::kind_to_raw(SyntaxKind::SymbolLit));
+        self.start_node(SyntaxKind::SymbolLit);
         self.consume_token();
-        self.builder.finish_node();
+        self.finish_node();
         Ok(())
     }
 
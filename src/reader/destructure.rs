@@ -0,0 +1,249 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{eval, Environment, EvalError, Value};
+
+/// Destructures a value against a binding pattern and returns the flat list of
+/// (symbol, value) bindings it produces, in binding order.
+///
+/// Supported patterns are:
+/// - symbols, which bind the whole value
+/// - vectors, which bind positionally over lists, vectors, strings and nil, with
+///   `& rest` for the remaining elements and `:as name` for the whole value
+/// - maps, with `:keys`, `:strs` and `:syms` shorthands, explicit
+///   `{pattern key}` entries, `:or` defaults and `:as name`
+///
+/// Patterns nest arbitrarily. `:or` default expressions are evaluated in `env`,
+/// and only when the key is missing.
+pub fn destructure(
+    pattern: &Value,
+    value: Value,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Vec<(String, Value)>, EvalError> {
+    let mut bindings = Vec::new();
+    bind_pattern(pattern, value, env, &mut bindings)?;
+    Ok(bindings)
+}
+
+/// Binds a single pattern, appending the bindings it produces
+fn bind_pattern(
+    pattern: &Value,
+    value: Value,
+    env: &Rc<RefCell<Environment>>,
+    bindings: &mut Vec<(String, Value)>,
+) -> Result<(), EvalError> {
+    match pattern {
+        Value::Symbol(name) => {
+            bindings.push((name.clone(), value));
+            Ok(())
+        }
+        Value::Vector(patterns) => bind_sequential(pattern, patterns, value, env, bindings),
+        Value::Map(entries) => bind_associative(pattern, entries, value, env, bindings),
+        _ => Err(EvalError::TypeError {
+            expected: "symbol, vector or map binding pattern".to_string(),
+            got: format!("{:?}", pattern),
+        }),
+    }
+}
+
+/// Binds a vector pattern such as `[a [b c] & rest :as all]`
+fn bind_sequential(
+    pattern: &Value,
+    patterns: &[Value],
+    value: Value,
+    env: &Rc<RefCell<Environment>>,
+    bindings: &mut Vec<(String, Value)>,
+) -> Result<(), EvalError> {
+    let items = match &value {
+        Value::Nil => vec![],
+        Value::List(items) | Value::Vector(items) => items.clone(),
+        Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+        _ => return Err(EvalError::TypeError {
+            expected: format!("sequential collection to destructure with {:?}", pattern),
+            got: format!("{:?}", value),
+        }),
+    };
+
+    let mut position = 0;
+    let mut i = 0;
+    while i < patterns.len() {
+        match &patterns[i] {
+            Value::Symbol(s) if s == "&" => {
+                let rest_pattern = patterns.get(i + 1).ok_or_else(|| {
+                    EvalError::SyntaxError(format!("& must be followed by a binding pattern in {:?}", pattern))
+                })?;
+
+                // The remaining elements as a list, or nil when there are none
+                let rest = items.get(position..).unwrap_or(&[]).to_vec();
+                position = items.len();
+                let rest_value = if rest.is_empty() {
+                    Value::Nil
+                } else {
+                    Value::List(rest)
+                };
+
+                bind_pattern(rest_pattern, rest_value, env, bindings)?;
+                i += 2;
+            }
+            Value::Keyword(k) if k == "as" => {
+                bind_as(pattern, patterns.get(i + 1), &value, bindings)?;
+                i += 2;
+            }
+            item_pattern => {
+                let item = items.get(position).cloned().unwrap_or(Value::Nil);
+                bind_pattern(item_pattern, item, env, bindings)?;
+                position += 1;
+                i += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Binds a map pattern such as `{:keys [a b] :or {b 0} :as m}`
+#[allow(clippy::mutable_key_type)]
+fn bind_associative(
+    pattern: &Value,
+    entries: &HashMap<Value, Value>,
+    value: Value,
+    env: &Rc<RefCell<Environment>>,
+    bindings: &mut Vec<(String, Value)>,
+) -> Result<(), EvalError> {
+    // A list is treated as a sequence of key/value pairs, as with `& {:keys [...]}`
+    let source = match &value {
+        Value::List(items) => {
+            if items.len() % 2 != 0 {
+                return Err(EvalError::TypeError {
+                    expected: format!("even number of key/value forms to destructure with {:?}", pattern),
+                    got: format!("{:?}", value),
+                });
+            }
+            let mut map = HashMap::new();
+            for pair in items.chunks(2) {
+                map.insert(pair[0].clone(), pair[1].clone());
+            }
+            Value::Map(map)
+        }
+        Value::Nil | Value::Map(_) | Value::Vector(_) => value.clone(),
+        _ => return Err(EvalError::TypeError {
+            expected: format!("map to destructure with {:?}", pattern),
+            got: format!("{:?}", value),
+        }),
+    };
+
+    // Default expressions, by the name of the local they apply to
+    let mut defaults = HashMap::new();
+    if let Some(or) = entries.get(&Value::Keyword("or".to_string())) {
+        match or {
+            Value::Map(or) => {
+                for (name, default) in or {
+                    match name {
+                        Value::Symbol(name) => {
+                            defaults.insert(name.clone(), default.clone());
+                        }
+                        _ => return Err(EvalError::TypeError {
+                            expected: "symbol in :or defaults".to_string(),
+                            got: format!("{:?}", name),
+                        }),
+                    }
+                }
+            }
+            _ => return Err(EvalError::TypeError {
+                expected: "map of :or defaults".to_string(),
+                got: format!("{:?}", or),
+            }),
+        }
+    }
+
+    for (local, key) in entries {
+        match local {
+            Value::Keyword(k) if k == "keys" || k == "strs" || k == "syms" => {
+                let names = match key {
+                    Value::Vector(names) => names,
+                    _ => return Err(EvalError::TypeError {
+                        expected: format!("vector of names after :{}", k),
+                        got: format!("{:?}", key),
+                    }),
+                };
+
+                for name in names {
+                    let name = match name {
+                        Value::Symbol(name) => name,
+                        _ => return Err(EvalError::TypeError {
+                            expected: format!("symbol in :{}", k),
+                            got: format!("{:?}", name),
+                        }),
+                    };
+
+                    let lookup_key = match k.as_str() {
+                        "keys" => Value::Keyword(name.clone()),
+                        "strs" => Value::String(name.clone()),
+                        _ => Value::Symbol(name.clone()),
+                    };
+
+                    let found = lookup_or_default(&source, &lookup_key, name, &defaults, env)?;
+                    bindings.push((name.clone(), found));
+                }
+            }
+            Value::Keyword(k) if k == "as" => {
+                bind_as(pattern, Some(key), &value, bindings)?;
+            }
+            Value::Keyword(k) if k == "or" => {}
+            _ => {
+                let found = match local {
+                    Value::Symbol(name) => lookup_or_default(&source, key, name, &defaults, env)?,
+                    _ => lookup(&source, key).unwrap_or(Value::Nil),
+                };
+                bind_pattern(local, found, env, bindings)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Binds the name following `:as` to the whole value
+fn bind_as(
+    pattern: &Value,
+    name: Option<&Value>,
+    value: &Value,
+    bindings: &mut Vec<(String, Value)>,
+) -> Result<(), EvalError> {
+    match name {
+        Some(Value::Symbol(name)) => {
+            bindings.push((name.clone(), value.clone()));
+            Ok(())
+        }
+        _ => Err(EvalError::SyntaxError(format!(":as must be followed by a symbol in {:?}", pattern))),
+    }
+}
+
+/// Looks a key up in a map or a vector index
+fn lookup(source: &Value, key: &Value) -> Option<Value> {
+    match (source, key) {
+        (Value::Map(map), _) => map.get(key).cloned(),
+        (Value::Vector(items), Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => {
+            items.get(*n as usize).cloned()
+        }
+        _ => None,
+    }
+}
+
+/// Looks a key up, falling back to the local's `:or` default when it is missing
+fn lookup_or_default(
+    source: &Value,
+    key: &Value,
+    name: &str,
+    defaults: &HashMap<String, Value>,
+    env: &Rc<RefCell<Environment>>,
+) -> Result<Value, EvalError> {
+    match lookup(source, key) {
+        Some(found) => Ok(found),
+        None => match defaults.get(name) {
+            Some(default) => eval(default, env),
+            None => Ok(Value::Nil),
+        },
+    }
+}
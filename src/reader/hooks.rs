@@ -0,0 +1,91 @@
+use std::cell::{Cell, RefCell};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Duration;
+
+use super::{Environment, EvalError, Value};
+
+/// Callbacks around the evaluation of forms, for debuggers, profilers and tracing.
+///
+/// Hooks are installed on an environment's context with `EvalContext::set_hooks`.
+/// `before_eval` is called before a form is evaluated, and `after_eval` once it
+/// has a result. A form in tail position reuses the evaluation of the form that
+/// led to it, so it is not reported separately. Returning an error from either
+/// callback aborts evaluation: the error reaches the host wrapped in
+/// `EvalError::Aborted`, which `try` does not catch.
+pub trait EvalHooks {
+    /// Called before evaluating a form, at the given nesting depth
+    fn before_eval(&self, _form: &Value, _env: &Rc<RefCell<Environment>>, _depth: usize) -> Result<(), EvalError> {
+        Ok(())
+    }
+
+    /// Called after evaluating a form, with its result and how long it took
+    fn after_eval(
+        &self,
+        _form: &Value,
+        _result: &Result<Value, EvalError>,
+        _duration: Duration,
+    ) -> Result<(), EvalError> {
+        Ok(())
+    }
+}
+
+/// Hooks that print each function call, indented by nesting, and its result
+pub struct Tracer {
+    out: RefCell<Box<dyn Write>>,
+    /// Number of calls being traced, for indentation
+    level: Cell<usize>,
+}
+
+impl Tracer {
+    /// Create a tracer printing to the given writer
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Tracer {
+            out: RefCell::new(out),
+            level: Cell::new(0),
+        }
+    }
+
+    /// Create a tracer printing to standard error
+    pub fn stderr() -> Self {
+        Self::new(Box::new(std::io::stderr()))
+    }
+
+    /// Writes a line at the current indentation. Tracing is best effort, so
+    /// write errors are ignored
+    fn write_line(&self, line: &str) {
+        let indent = "  ".repeat(self.level.get());
+        let _ = writeln!(self.out.borrow_mut(), "{}{}", indent, line);
+    }
+}
+
+/// Returns whether a form is a function call, as opposed to a literal or symbol
+fn is_call(form: &Value) -> bool {
+    matches!(form, Value::List(items) if !items.is_empty())
+}
+
+impl EvalHooks for Tracer {
+    fn before_eval(&self, form: &Value, _env: &Rc<RefCell<Environment>>, _depth: usize) -> Result<(), EvalError> {
+        if is_call(form) {
+            self.write_line(&format!("{}", form));
+            self.level.set(self.level.get() + 1);
+        }
+        Ok(())
+    }
+
+    fn after_eval(
+        &self,
+        form: &Value,
+        result: &Result<Value, EvalError>,
+        _duration: Duration,
+    ) -> Result<(), EvalError> {
+        if is_call(form) {
+            self.level.set(self.level.get().saturating_sub(1));
+            match result {
+                Ok(value) => self.write_line(&format!("=> {}", value)),
+                Err(error) => self.write_line(&format!("!! {}", error)),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::{eval, read_forms, Environment, EvalError, Value};
+
+/// Provides the source code of the files loaded by `load-file` and `require`.
+///
+/// The interpreter reads files from the file system by default. Embedders can
+/// install their own loader with `EvalContext::set_loader`, for example to
+/// serve sources from memory.
+pub trait SourceLoader {
+    /// Returns the source code at the path. A missing file is reported with an
+    /// error of kind `io::ErrorKind::NotFound`, so `require` can try the next
+    /// directory of the load path.
+    fn read_source(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Loads sources from the file system
+#[derive(Debug, Default)]
+pub struct FileLoader;
+
+impl SourceLoader for FileLoader {
+    fn read_source(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Extension of the files that `require` looks for
+pub const SOURCE_EXTENSION: &str = "ctr";
+
+/// Reads, parses and evaluates the forms of a file in order, in the root
+/// environment. Returns the value of the last form.
+pub fn load_file(path: &Path, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    let loader = env.borrow().context().loader();
+    let source = loader
+        .read_source(path)
+        .map_err(|e| EvalError::Other(format!("Could not load {}: {}", path.display(), e)))?;
+
+    eval_source(&source, env)
+}
+
+/// Loads the file for a name such as `my.util` from the first directory of the
+/// load path that has it (`my/util.ctr`). A name that was already required is
+/// not loaded again.
+pub fn require(name: &str, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    let context = env.borrow().context();
+    if context.is_loaded(name) {
+        return Ok(Value::Nil);
+    }
+
+    // A name required again while it is still loading is a cycle
+    if let Some(chain) = context.start_loading(name) {
+        return Err(EvalError::Other(format!("Circular require: {}", chain.join(" -> "))));
+    }
+
+    let result = load_required(name, env);
+    context.finish_loading(name, result.is_ok());
+    result.map(|_| Value::Nil)
+}
+
+/// Finds and evaluates the source of a required name
+fn load_required(name: &str, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    let context = env.borrow().context();
+    let loader = context.loader();
+    let relative = PathBuf::from(format!("{}.{}", name.replace('.', "/"), SOURCE_EXTENSION));
+
+    let mut attempted = Vec::new();
+    for dir in context.load_path() {
+        let path = dir.join(&relative);
+        match loader.read_source(&path) {
+            Ok(source) => return eval_source(&source, env),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => attempted.push(path.display().to_string()),
+            Err(e) => return Err(EvalError::Other(format!("Could not load {}: {}", path.display(), e))),
+        }
+    }
+
+    Err(EvalError::Other(format!(
+        "Could not find {} to require, tried: {}",
+        name,
+        attempted.join(", ")
+    )))
+}
+
+/// Evaluates every form of a source in the root environment
+fn eval_source(source: &str, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    let root = Environment::root(env);
+    let (syntax, errors) = crate::parse_with_errors(source);
+    let forms = match errors.first() {
+        Some(error) => Err(EvalError::SyntaxError(error.to_string())),
+        None => read_forms(&syntax),
+    };
+    crate::syntax::drop_tree(syntax);
+
+    let mut result = Value::Nil;
+    for form in forms? {
+        result = eval(&form, &root)?;
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,924 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::destructure::destructure;
+use super::{
+    apply, at_call, bind_letfn, bind_recur, check_expansion_limit, eval_assert, eval_binding, eval_def,
+    eval_defn, eval_fn, eval_set, eval_setq, eval_try, eval_with_meta, expand_if_let, expand_if_not,
+    expand_macro, expand_when, quasiquote, recur_target_for, bind_arguments, DelayState, Environment,
+    EvalContext, EvalError, Function, RecurTarget, Value,
+};
+
+/// A form being evaluated. Forms are borrowed from the value `eval` was called
+/// with, or shared with the function body, loop body or macro expansion that
+/// contains them, so evaluating a form never copies it.
+#[derive(Clone)]
+enum Form<'a> {
+    Borrowed(&'a Value),
+    Shared(Shared),
+}
+
+/// A form inside shared forms, found by following a path of indices through
+/// nested lists and vectors
+#[derive(Clone)]
+struct Shared {
+    forms: Rc<Vec<Value>>,
+    path: Rc<[usize]>,
+}
+
+impl Shared {
+    /// Returns the form the path leads to
+    fn value(&self) -> &Value {
+        let mut value = &self.forms[self.path[0]];
+        for &index in &self.path[1..] {
+            value = match value {
+                Value::List(items) | Value::Vector(items) => &items[index],
+                _ => unreachable!("paths only lead through lists and vectors"),
+            };
+        }
+        value
+    }
+
+    /// Returns the path to an element of the form the path leads to
+    fn child(&self, index: usize) -> Shared {
+        let mut path = Vec::with_capacity(self.path.len() + 1);
+        path.extend_from_slice(&self.path);
+        path.push(index);
+
+        Shared {
+            forms: self.forms.clone(),
+            path: path.into(),
+        }
+    }
+}
+
+impl<'a> Form<'a> {
+    /// A form produced during evaluation
+    fn owned(value: Value) -> Self {
+        Form::Shared(Shared {
+            forms: Rc::new(vec![value]),
+            path: Rc::new([0]),
+        })
+    }
+
+    fn value(&self) -> &Value {
+        match self {
+            Form::Borrowed(value) => value,
+            Form::Shared(shared) => shared.value(),
+        }
+    }
+}
+
+/// The elements of a list form, or of a body, that frames refer back to while
+/// their subforms are evaluated
+#[derive(Clone)]
+enum Forms<'a> {
+    Borrowed(&'a [Value]),
+    /// Elements from the given index on, of the shared list or vector the path
+    /// leads to, or of the shared forms themselves when the path is empty
+    Shared(Shared, usize),
+}
+
+impl<'a> Forms<'a> {
+    /// The elements of a shared body, like the body of a function
+    fn body(body: Rc<Vec<Value>>) -> Self {
+        Forms::Shared(
+            Shared {
+                forms: body,
+                path: Rc::new([]),
+            },
+            0,
+        )
+    }
+
+    /// The elements of a list or vector form
+    fn of(form: Form<'a>) -> Option<Self> {
+        match form {
+            Form::Borrowed(Value::List(items) | Value::Vector(items)) => Some(Forms::Borrowed(items)),
+            Form::Borrowed(_) => None,
+            Form::Shared(shared) => match shared.value() {
+                Value::List(_) | Value::Vector(_) => Some(Forms::Shared(shared, 0)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the element at the index
+    fn get(&self, index: usize) -> Form<'a> {
+        match self {
+            Forms::Borrowed(items) => {
+                let items: &'a [Value] = items;
+                Form::Borrowed(&items[index])
+            }
+            Forms::Shared(shared, offset) if shared.path.is_empty() => Form::Shared(Shared {
+                forms: shared.forms.clone(),
+                path: Rc::new([offset + index]),
+            }),
+            Forms::Shared(shared, offset) => Form::Shared(shared.child(offset + index)),
+        }
+    }
+
+    /// Returns the elements from the index on
+    fn skip(&self, start: usize) -> Self {
+        match self {
+            Forms::Borrowed(items) => {
+                let items: &'a [Value] = items;
+                Forms::Borrowed(&items[start..])
+            }
+            Forms::Shared(shared, offset) => Forms::Shared(shared.clone(), offset + start),
+        }
+    }
+}
+
+impl Deref for Forms<'_> {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        match self {
+            Forms::Borrowed(items) => items,
+            Forms::Shared(shared, offset) if shared.path.is_empty() => &shared.forms[*offset..],
+            Forms::Shared(shared, offset) => match shared.value() {
+                Value::List(items) | Value::Vector(items) => &items[*offset..],
+                _ => unreachable!("shared elements are those of a list or vector"),
+            },
+        }
+    }
+}
+
+/// The kind of a collection literal
+#[derive(Clone, Copy)]
+enum Collection {
+    Vector,
+    Map,
+    Set,
+}
+
+/// Work waiting for the value of a subform, on the evaluator's explicit stack
+enum Frame<'a> {
+    /// The end of a nested evaluation. The evaluation counts towards the depth
+    /// limit until it returns, and is reported to the hooks when they are set
+    Nested { hooked: Option<(Form<'a>, Instant)> },
+    /// An `if` waiting for the value of its test
+    If {
+        items: Forms<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    },
+    /// A body waiting for the value of one of its forms before the last
+    Body {
+        forms: Forms<'a>,
+        index: usize,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    },
+    /// A `let` or `loop` waiting for the value of one of its bindings
+    Bind {
+        items: Forms<'a>,
+        bindings: Forms<'a>,
+        index: usize,
+        env: Rc<RefCell<Environment>>,
+        bind_env: Rc<RefCell<Environment>>,
+        is_loop: bool,
+        target: Option<RecurTarget>,
+    },
+    /// A call waiting for the value of its head or of one of its arguments
+    Call {
+        items: Forms<'a>,
+        func: Option<Value>,
+        args: Vec<Value>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+        expansions: usize,
+    },
+    /// A `recur` waiting for the value of one of its arguments
+    Recur {
+        items: Forms<'a>,
+        args: Vec<Value>,
+        env: Rc<RefCell<Environment>>,
+        target: RecurTarget,
+    },
+    /// A vector, map or set literal waiting for the value of an element. Map
+    /// keys and values alternate
+    Collection {
+        kind: Collection,
+        forms: std::vec::IntoIter<Form<'a>>,
+        values: Vec<Value>,
+        env: Rc<RefCell<Environment>>,
+    },
+}
+
+/// What the evaluator does next
+enum Step<'a> {
+    /// Evaluate a form. `expansions` counts the macro expansions in a row that
+    /// produced it, for the expansion limit
+    Eval {
+        form: Form<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+        expansions: usize,
+    },
+    /// Pass a value to the frame on top of the stack
+    Return(Value),
+}
+
+impl<'a> Step<'a> {
+    /// Evaluates a form that was not produced by a macro expansion
+    fn eval(form: Form<'a>, env: Rc<RefCell<Environment>>, target: Option<RecurTarget>) -> Self {
+        Step::Eval {
+            form,
+            env,
+            target,
+            expansions: 0,
+        }
+    }
+}
+
+/// Stack space left below which a new evaluator continues on a fresh stack
+/// segment, and the size of that segment
+const STACK_RED_ZONE: usize = 128 * 1024;
+const STACK_SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Evaluates a form with an explicit stack of frames instead of recursion, so
+/// the Rust stack does not grow with the nesting of the form. Subforms that
+/// special forms evaluate through `eval`, like the value of a `def` or the body
+/// of a `try`, and functions called back by builtins such as `apply` still start
+/// an evaluator of their own. Evaluators grow the Rust stack on the heap as
+/// they nest, so they are bounded by the depth limit alone, in every build
+/// profile.
+pub(super) fn run(
+    value: &Value,
+    env: &Rc<RefCell<Environment>>,
+    target: Option<RecurTarget>,
+) -> Result<Value, EvalError> {
+    let mut machine = Machine {
+        context: env.borrow().context(),
+        stack: Vec::new(),
+    };
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+        machine.run(Form::Borrowed(value), env.clone(), target)
+    })
+}
+
+struct Machine<'a> {
+    context: Rc<EvalContext>,
+    stack: Vec<Frame<'a>>,
+}
+
+impl<'a> Machine<'a> {
+    fn run(
+        &mut self,
+        form: Form<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Value, EvalError> {
+        let mut step = match self.nested(form, env, target) {
+            Ok(step) => step,
+            Err(error) => return Err(self.unwind(error)),
+        };
+
+        loop {
+            let next = match step {
+                Step::Eval {
+                    form,
+                    env,
+                    target,
+                    expansions,
+                } => self.eval(form, env, target, expansions),
+                Step::Return(value) => match self.stack.pop() {
+                    Some(frame) => self.resume(frame, value),
+                    None => return Ok(value),
+                },
+            };
+
+            step = match next {
+                Ok(next) => next,
+                Err(error) => return Err(self.unwind(error)),
+            };
+        }
+    }
+
+    /// Starts a nested evaluation of a form, as for a call to `eval`
+    fn nested(
+        &mut self,
+        form: Form<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Step<'a>, EvalError> {
+        self.context.enter()?;
+
+        let mut hooked = None;
+        if self.context.has_hooks() {
+            if let Some(hooks) = self.context.hooks() {
+                if let Err(error) = hooks.before_eval(form.value(), &env, self.context.depth()) {
+                    self.context.leave();
+                    return Err(aborted(error));
+                }
+                hooked = Some((form.clone(), Instant::now()));
+            }
+        }
+        self.stack.push(Frame::Nested { hooked });
+
+        Ok(Step::eval(form, env, target))
+    }
+
+    /// Starts a nested evaluation of the element of a list, without copying
+    /// shared elements that evaluate in a single step
+    fn nested_element(&mut self, forms: &Forms<'a>, index: usize, env: &Rc<RefCell<Environment>>) -> Result<Step<'a>, EvalError> {
+        let form = &forms[index];
+        if self.context.has_hooks() || matches!(form, Value::List(_) | Value::Vector(_) | Value::Map(_) | Value::Set(_)) {
+            return self.nested(forms.get(index), env.clone(), None);
+        }
+
+        self.context.enter()?;
+        self.stack.push(Frame::Nested { hooked: None });
+        self.context.consume_fuel(1)?;
+        eval_atom(form, env).map(Step::Return)
+    }
+
+    /// Finishes a nested evaluation, reporting its result to the hooks
+    fn finish_nested(
+        &mut self,
+        hooked: Option<(Form<'a>, Instant)>,
+        result: Result<Value, EvalError>,
+    ) -> Result<Value, EvalError> {
+        let result = match (hooked, self.context.hooks()) {
+            (Some((form, start)), Some(hooks)) => match hooks.after_eval(form.value(), &result, start.elapsed()) {
+                Ok(()) => result,
+                Err(error) => Err(aborted(error)),
+            },
+            _ => result,
+        };
+
+        self.context.leave();
+        result
+    }
+
+    /// Pops every frame after an error, finishing the nested evaluations they
+    /// belong to, and returns the error to report
+    fn unwind(&mut self, error: EvalError) -> EvalError {
+        let mut result = Err(error);
+        while let Some(frame) = self.stack.pop() {
+            if let Frame::Nested { hooked } = frame {
+                result = self.finish_nested(hooked, result);
+            }
+        }
+
+        match result {
+            Err(error) => error,
+            Ok(_) => unreachable!("hooks cannot turn an error into a value"),
+        }
+    }
+
+    /// Takes a step of evaluation of a form
+    fn eval(
+        &mut self,
+        form: Form<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+        expansions: usize,
+    ) -> Result<Step<'a>, EvalError> {
+        // Every form evaluated takes a step of the budget, including tail calls
+        self.context.consume_fuel(1)?;
+
+        match form.value() {
+            Value::Vector(_) | Value::Map(_) | Value::Set(_) => return self.eval_collection(form, env),
+            Value::List(items) if !items.is_empty() => {}
+            Value::List(_) => return Ok(Step::Return(Value::List(vec![]))),
+            atom => return eval_atom(atom, &env).map(Step::Return),
+        }
+        let items = match Forms::of(form) {
+            Some(items) => items,
+            None => unreachable!("the form is a list"),
+        };
+
+        // Check for special forms. A head that is not a symbol is evaluated as a function
+        let name = match &items[0] {
+            Value::Symbol(name) => name.as_str(),
+            _ => "",
+        };
+
+        match name {
+            // Special form: setq
+            "setq" => eval_setq(&items, &env).map(Step::Return),
+
+            // Special form: set!
+            "set!" => eval_set(&items, &env).map(Step::Return),
+
+            // Special form: def
+            "def" => eval_def(&items, &env).map(Step::Return),
+
+            // Special forms: defn and defmacro
+            "defn" | "defmacro" => eval_defn(name, &items, &env).map(Step::Return),
+
+            // Special form: if
+            "if" => {
+                if items.len() != 3 && items.len() != 4 {
+                    return Err(EvalError::ArityMismatch {
+                        expected: 3,
+                        got: items.len() - 1,
+                    });
+                }
+
+                self.stack.push(Frame::If {
+                    items: items.clone(),
+                    env: env.clone(),
+                    target,
+                });
+                self.nested_element(&items, 1, &env)
+            }
+
+            // Special forms: when and when-not
+            "when" | "when-not" => Ok(Step::eval(Form::owned(expand_when(name, &items)?), env, target)),
+
+            // Special form: if-not
+            "if-not" => Ok(Step::eval(Form::owned(expand_if_not(&items)?), env, target)),
+
+            // Special forms: if-let and when-let
+            "if-let" | "when-let" => {
+                Ok(Step::eval(Form::owned(expand_if_let(name, &items, &env)?), env, target))
+            }
+
+            // Special form: quote
+            "quote" => {
+                if items.len() != 2 {
+                    return Err(EvalError::ArityMismatch {
+                        expected: 1,
+                        got: items.len() - 1,
+                    });
+                }
+
+                Ok(Step::Return(items[1].clone()))
+            }
+
+            // Special form: comment, which ignores its body and returns nil
+            "comment" => Ok(Step::Return(Value::Nil)),
+
+            // Special form: delay, which defers its body until it is forced
+            "delay" => Ok(Step::Return(Value::Delay(Rc::new(RefCell::new(DelayState::Pending {
+                body: Rc::new(items[1..].to_vec()),
+                env: env.clone(),
+            }))))),
+
+            // Special form: quasiquote
+            "quasiquote" => {
+                if items.len() != 2 {
+                    return Err(EvalError::ArityMismatch {
+                        expected: 1,
+                        got: items.len() - 1,
+                    });
+                }
+
+                // Auto-gensyms are shared by the whole template, but not across templates
+                let mut gensyms = HashMap::new();
+                quasiquote(&items[1], &env, &mut gensyms).map(Step::Return)
+            }
+
+            // Special form: do, which evaluates each form in order
+            "do" => self.body(items.skip(1), env, target),
+
+            // Special forms: let and loop
+            "let" | "loop" => self.eval_bindings(name == "loop", items, env, target),
+
+            // Special form: letfn
+            "letfn" => {
+                let letfn_env = bind_letfn(&items, &env)?;
+                self.body(items.skip(2), letfn_env, target)
+            }
+
+            // Special form: recur
+            "recur" => {
+                let target = match target {
+                    Some(target) => target,
+                    None => return Err(EvalError::SyntaxError(
+                        "Can only recur from tail position of a loop or fn".to_string(),
+                    )),
+                };
+
+                if items.len() == 1 {
+                    return self.jump(target, vec![]);
+                }
+
+                self.stack.push(Frame::Recur {
+                    items: items.clone(),
+                    args: Vec::new(),
+                    env: env.clone(),
+                    target,
+                });
+                self.nested_element(&items, 1, &env)
+            }
+
+            // Special form: with-meta
+            "with-meta" => Ok(Step::eval(Form::owned(eval_with_meta(&items, &env)?), env, target)),
+
+            // Special form: binding
+            "binding" => eval_binding(&items, &env).map(Step::Return),
+
+            // Special form: assert
+            "assert" => eval_assert(&items, &env).map(Step::Return),
+
+            // Special form: try
+            "try" => eval_try(&items[1..], &env).map(Step::Return),
+
+            // Special forms: fn and macro
+            "fn" | "macro" => eval_fn(name, &items, &env).map(Step::Return),
+
+            // Regular function call or macro application, starting with the head
+            _ => {
+                self.stack.push(Frame::Call {
+                    items: items.clone(),
+                    func: None,
+                    args: Vec::new(),
+                    env: env.clone(),
+                    target,
+                    expansions,
+                });
+                self.nested_element(&items, 0, &env)
+            }
+        }
+    }
+
+    /// Continues the work of a frame with the value of the subform it was waiting for
+    fn resume(&mut self, frame: Frame<'a>, value: Value) -> Result<Step<'a>, EvalError> {
+        match frame {
+            Frame::Nested { hooked } => self.finish_nested(hooked, Ok(value)).map(Step::Return),
+
+            Frame::If { items, env, target } => {
+                let branch = if value.is_truthy() {
+                    items.get(2)
+                } else if items.len() == 4 {
+                    items.get(3)
+                } else {
+                    Form::owned(Value::Nil)
+                };
+                Ok(Step::eval(branch, env, target))
+            }
+
+            Frame::Body {
+                forms,
+                index,
+                env,
+                target,
+            } => self.body_from(forms, index + 1, env, target),
+
+            Frame::Bind {
+                items,
+                bindings,
+                index,
+                env,
+                bind_env,
+                is_loop,
+                target,
+            } => {
+                for (name, value) in destructure(&bindings[index], value, &bind_env)? {
+                    bind_env.borrow_mut().set(name, value);
+                }
+
+                let index = index + 2;
+                if index < bindings.len() {
+                    let value_forms = bindings.clone();
+                    self.stack.push(Frame::Bind {
+                        items,
+                        bindings,
+                        index,
+                        env,
+                        bind_env: bind_env.clone(),
+                        is_loop,
+                        target,
+                    });
+                    return self.nested_element(&value_forms, index + 1, &bind_env);
+                }
+
+                self.bound(is_loop, items, &bindings, env, bind_env, target)
+            }
+
+            Frame::Call {
+                items,
+                func: None,
+                args,
+                env,
+                target,
+                expansions,
+            } => {
+                // Macros receive their argument forms unevaluated, and their expansion
+                // is evaluated in place of the call, in the caller's environment
+                if let Value::Macro(mac) = &value {
+                    let expansions = expansions + 1;
+                    check_expansion_limit(expansions, mac, &env)?;
+
+                    return Ok(Step::Eval {
+                        form: Form::owned(expand_macro(mac, &items[1..])?),
+                        env,
+                        target,
+                        expansions,
+                    });
+                }
+
+                self.next_argument(items, value, args, env, target, expansions)
+            }
+
+            Frame::Call {
+                items,
+                func: Some(func),
+                mut args,
+                env,
+                target,
+                expansions,
+            } => {
+                args.push(value);
+                self.next_argument(items, func, args, env, target, expansions)
+            }
+
+            Frame::Recur {
+                items,
+                mut args,
+                env,
+                target,
+            } => {
+                args.push(value);
+
+                let index = args.len() + 1;
+                if index < items.len() {
+                    let forms = items.clone();
+                    self.stack.push(Frame::Recur { items, args, env: env.clone(), target });
+                    return self.nested_element(&forms, index, &env);
+                }
+
+                self.jump(target, args)
+            }
+
+            Frame::Collection {
+                kind,
+                mut forms,
+                mut values,
+                env,
+            } => {
+                values.push(value);
+
+                match forms.next() {
+                    Some(form) => {
+                        self.stack.push(Frame::Collection {
+                            kind,
+                            forms,
+                            values,
+                            env: env.clone(),
+                        });
+                        self.nested(form, env, None)
+                    }
+                    None => Ok(Step::Return(build_collection(kind, values))),
+                }
+            }
+        }
+    }
+
+    /// Evaluates the next argument of a call, or applies the function once
+    /// they are all evaluated
+    fn next_argument(
+        &mut self,
+        items: Forms<'a>,
+        func: Value,
+        args: Vec<Value>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+        expansions: usize,
+    ) -> Result<Step<'a>, EvalError> {
+        let index = args.len() + 1;
+        if index < items.len() {
+            let forms = items.clone();
+            self.stack.push(Frame::Call {
+                items,
+                func: Some(func),
+                args,
+                env: env.clone(),
+                target,
+                expansions,
+            });
+            return self.nested_element(&forms, index, &env);
+        }
+
+        match &func {
+            // Calls to user-defined functions continue on the function body, so
+            // calls in tail position do not grow the stack
+            Value::Function(f) if !f.is_builtin => {
+                let func_env = bind_arguments_at(f, args, &items)?;
+                let target = recur_target_for(f);
+                self.body(Forms::body(f.body.clone()), func_env, Some(target))
+            }
+            _ => apply(&func, args, &env)
+                .map(Step::Return)
+                .map_err(|e| at_call(e, &items)),
+        }
+    }
+
+    /// Evaluates a body as an implicit `do`: all but the last form for their
+    /// side effects, and the last one in tail position. An empty body is nil
+    fn body(
+        &mut self,
+        forms: Forms<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Step<'a>, EvalError> {
+        self.body_from(forms, 0, env, target)
+    }
+
+    /// Continues a body from the form at the index
+    fn body_from(
+        &mut self,
+        forms: Forms<'a>,
+        index: usize,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Step<'a>, EvalError> {
+        match forms.len() {
+            0 => Ok(Step::eval(Form::owned(Value::Nil), env, target)),
+            len if index + 1 == len => Ok(Step::eval(forms.get(index), env, target)),
+            _ => {
+                let init = forms.clone();
+                self.stack.push(Frame::Body {
+                    forms,
+                    index,
+                    env: env.clone(),
+                    target,
+                });
+                self.nested_element(&init, index, &env)
+            }
+        }
+    }
+
+    /// Starts a `let` or `loop`, evaluating its bindings vector sequentially in a
+    /// new child environment, so later bindings can see earlier ones
+    fn eval_bindings(
+        &mut self,
+        is_loop: bool,
+        items: Forms<'a>,
+        env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Step<'a>, EvalError> {
+        if items.len() < 2 {
+            return Err(EvalError::ArityMismatch {
+                expected: 1,
+                got: 0,
+            });
+        }
+
+        let bindings = match Forms::of(items.get(1)) {
+            Some(bindings) if matches!(items[1], Value::Vector(_)) => bindings,
+            _ => return Err(EvalError::TypeError {
+                expected: "vector".to_string(),
+                got: format!("{:?}", items[1]),
+            }),
+        };
+
+        if bindings.len() % 2 != 0 {
+            return Err(EvalError::SyntaxError(format!(
+                "{} requires an even number of forms in the bindings vector",
+                if is_loop { "loop" } else { "let" }
+            )));
+        }
+
+        let bind_env = Rc::new(RefCell::new(Environment::with_outer(env.clone())));
+        if bindings.is_empty() {
+            return self.bound(is_loop, items, &bindings, env, bind_env, target);
+        }
+
+        let value_forms = bindings.clone();
+        self.stack.push(Frame::Bind {
+            items,
+            bindings,
+            index: 0,
+            env,
+            bind_env: bind_env.clone(),
+            is_loop,
+            target,
+        });
+        self.nested_element(&value_forms, 1, &bind_env)
+    }
+
+    /// Evaluates the body of a `let` or `loop` once its bindings are bound. The
+    /// body of a loop becomes the target of any recur in tail position
+    fn bound(
+        &mut self,
+        is_loop: bool,
+        items: Forms<'a>,
+        bindings: &[Value],
+        env: Rc<RefCell<Environment>>,
+        bind_env: Rc<RefCell<Environment>>,
+        target: Option<RecurTarget>,
+    ) -> Result<Step<'a>, EvalError> {
+        let target = if is_loop {
+            Some(RecurTarget {
+                params: bindings.iter().step_by(2).cloned().collect(),
+                body: Rc::new(items[2..].to_vec()),
+                env,
+            })
+        } else {
+            target
+        };
+
+        self.body(items.skip(2), bind_env, target)
+    }
+
+    /// Rebinds the parameters of a recur target and jumps back to its body
+    fn jump(&mut self, target: RecurTarget, args: Vec<Value>) -> Result<Step<'a>, EvalError> {
+        let recur_env = bind_recur(&target, args)?;
+        self.body(Forms::body(target.body.clone()), recur_env, Some(target))
+    }
+
+    /// Starts evaluating the elements of a vector, map or set literal
+    fn eval_collection(&mut self, form: Form<'a>, env: Rc<RefCell<Environment>>) -> Result<Step<'a>, EvalError> {
+        let (kind, forms): (Collection, Vec<Form<'a>>) = match &form {
+            Form::Borrowed(value) => {
+                let value: &'a Value = value;
+                match value {
+                    Value::Vector(items) => (Collection::Vector, items.iter().map(Form::Borrowed).collect()),
+                    Value::Set(items) => (Collection::Set, items.iter().map(Form::Borrowed).collect()),
+                    Value::Map(entries) => (
+                        Collection::Map,
+                        entries.iter().flat_map(|(k, v)| [Form::Borrowed(k), Form::Borrowed(v)]).collect(),
+                    ),
+                    other => return Ok(Step::Return(other.clone())),
+                }
+            }
+            // Elements of shared maps and sets cannot be reached by index, so
+            // they are copied
+            Form::Shared(shared) => match shared.value() {
+                Value::Vector(items) => (
+                    Collection::Vector,
+                    (0..items.len()).map(|i| Form::Shared(shared.child(i))).collect(),
+                ),
+                Value::Set(items) => (Collection::Set, items.iter().cloned().map(Form::owned).collect()),
+                Value::Map(entries) => (
+                    Collection::Map,
+                    entries
+                        .iter()
+                        .flat_map(|(k, v)| [Form::owned(k.clone()), Form::owned(v.clone())])
+                        .collect(),
+                ),
+                other => return Ok(Step::Return(other.clone())),
+            },
+        };
+
+        let mut forms = forms.into_iter();
+        match forms.next() {
+            Some(form) => {
+                self.stack.push(Frame::Collection {
+                    kind,
+                    forms,
+                    values: Vec::new(),
+                    env: env.clone(),
+                });
+                self.nested(form, env, None)
+            }
+            None => Ok(Step::Return(build_collection(kind, vec![]))),
+        }
+    }
+}
+
+/// Marks an error returned by a hook as an abort, unless it already is one from
+/// a nested evaluation
+fn aborted(error: EvalError) -> EvalError {
+    match error {
+        EvalError::Aborted(_) => error,
+        error => EvalError::Aborted(Box::new(error)),
+    }
+}
+
+/// Evaluates a form that is neither a list nor a collection literal
+fn eval_atom(form: &Value, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    match form {
+        // Symbol lookup
+        Value::Symbol(name) => env.borrow().get(name).ok_or_else(|| EvalError::UnboundSymbol(name.clone())),
+
+        // Everything else evaluates to itself
+        _ => Ok(form.clone()),
+    }
+}
+
+/// Builds a collection from the values of its elements
+#[allow(clippy::mutable_key_type)]
+fn build_collection(kind: Collection, values: Vec<Value>) -> Value {
+    match kind {
+        Collection::Vector => Value::Vector(values),
+        Collection::Set => Value::Set(values.into_iter().collect::<HashSet<_>>()),
+        Collection::Map => {
+            let mut map = HashMap::new();
+            let mut values = values.into_iter();
+            while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                map.insert(key, value);
+            }
+            Value::Map(map)
+        }
+    }
+}
+
+/// Binds the arguments of a call to a user-defined function, naming the call
+/// in arity errors
+fn bind_arguments_at(
+    f: &Function,
+    args: Vec<Value>,
+    items: &[Value],
+) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    bind_arguments(&f.params, &f.rest, &f.env, args).map_err(|e| at_call(e, items))
+}
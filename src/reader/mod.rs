@@ -1,393 +1,1261 @@
+mod destructure;
+mod hooks;
+mod loader;
+mod machine;
+mod random;
 mod value;
 
 
 pub use value::*;
+pub use hooks::{EvalHooks, Tracer};
+pub use loader::{load_file, require, FileLoader, SourceLoader, SOURCE_EXTENSION};
 
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::cell::RefCell;
-use crate::syntax::{SyntaxKind, SyntaxNode};
+use crate::syntax::{CitrineLanguage, SyntaxKind, SyntaxNode};
+use rowan::SyntaxNodeChildren;
+use destructure::destructure;
 
-/// Reads a syntax node and converts it to a Citrine value
+/// Reads a syntax node and converts it to a Citrine value. Nested forms are
+/// read with an explicit stack rather than recursion, so the Rust stack does
+/// not grow with the nesting of the input
 pub fn read(node: &SyntaxNode) -> Result<Value, EvalError> {
-    match node.kind() {
-        SyntaxKind::Root => {
-            // Process all forms in the root node
-            let mut forms = Vec::new();
-            for child in node.children() {
-                if child.kind() != SyntaxKind::Eof {
-                    forms.push(read(&child)?);
+    let mut stack: Vec<PendingNode> = Vec::new();
+    let mut next = Some(node.clone());
+
+    loop {
+        let value = match next.take() {
+            Some(node) => match read_literal(&node)? {
+                Some(value) => value,
+                None => {
+                    stack.push(PendingNode::new(node));
+                    continue;
                 }
+            },
+            None => {
+                let pending = stack.last_mut().expect("a node is pending until the root is read");
+                if let Some(child) = pending.next_child() {
+                    next = Some(child);
+                    continue;
+                }
+                let pending = stack.pop().expect("the node was just looked at");
+                pending.finish()?
             }
-            
-            // If there's only one form, return it directly
-            if forms.len() == 1 {
-                Ok(forms.remove(0))
-            } else {
-                Ok(Value::List(forms))
-            }
+        };
+
+        match stack.last_mut() {
+            Some(parent) => parent.values.push(value),
+            None => return Ok(value),
         }
-        
-        // Literals
+    }
+}
+
+/// Reads a literal, or returns None for a node whose value is read from its
+/// children
+fn read_literal(node: &SyntaxNode) -> Result<Option<Value>, EvalError> {
+    let value = match node.kind() {
         SyntaxKind::NumberLit => {
             let text = node.text().to_string();
             let number = text.parse::<f64>().map_err(|_| {
                 EvalError::SyntaxError(format!("Invalid number: {}", text))
             })?;
-            Ok(Value::Number(number))
+            Value::Number(number)
         }
         SyntaxKind::StringLit => {
             let text = node.text().to_string();
             // Remove the quotes
             let content = text[1..text.len() - 1].to_string();
-            Ok(Value::String(content))
+            Value::String(content)
         }
         SyntaxKind::SymbolLit => {
             let text = node.text().to_string();
-            Ok(Value::Symbol(text))
+            match text.as_str() {
+                "nil" => Value::Nil,
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                _ => Value::Symbol(text),
+            }
         }
         SyntaxKind::KeywordLit => {
             let text = node.text().to_string();
             // Remove the leading colon
             let content = text[1..].to_string();
-            Ok(Value::Keyword(content))
-        }
-        
-        // Collections
-        SyntaxKind::List => {
-            let mut items = Vec::new();
-            for child in node.children() {
-                if !is_delimiter(child.kind()) {
-                    items.push(read(&child)?);
-                }
-            }
-            Ok(Value::List(items))
+            Value::Keyword(content)
         }
-        SyntaxKind::Vector => {
-            let mut items = Vec::new();
-            for child in node.children() {
-                if !is_delimiter(child.kind()) {
-                    items.push(read(&child)?);
-                }
-            }
-            Ok(Value::Vector(items))
-        }
-        SyntaxKind::Map => {
-            let mut map = HashMap::new();
-            let mut key = None;
-            
-            for child in node.children() {
-                if !is_delimiter(child.kind()) {
-                    if let Some(k) = key.take() {
-                        let v = read(&child)?;
-                        map.insert(k, v);
-                    } else {
-                        key = Some(read(&child)?);
-                    }
-                }
-            }
-            
-            // Check if we have an odd number of elements
-            if key.is_some() {
-                return Err(EvalError::SyntaxError("Map literal must have an even number of forms".to_string()));
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// A node being read by `read`, with the values of the children read so far
+struct PendingNode {
+    node: SyntaxNode,
+    children: SyntaxNodeChildren<CitrineLanguage>,
+    values: Vec<Value>,
+}
+
+impl PendingNode {
+    fn new(node: SyntaxNode) -> Self {
+        PendingNode {
+            children: node.children(),
+            node,
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the next child that is a form
+    fn next_child(&mut self) -> Option<SyntaxNode> {
+        let kind = self.node.kind();
+        self.children.by_ref().find(|child| match kind {
+            SyntaxKind::Root => child.kind() != SyntaxKind::Eof,
+            SyntaxKind::List | SyntaxKind::Vector | SyntaxKind::Map | SyntaxKind::Set => {
+                !is_delimiter(child.kind())
             }
-            
-            Ok(Value::Map(map))
-        }
-        SyntaxKind::Set => {
-            let mut set = HashSet::new();
-            for child in node.children() {
-                if !is_delimiter(child.kind()) {
-                    set.insert(read(&child)?);
+            _ => true,
+        })
+    }
+
+    /// Builds the value of the node from the values of its children
+    #[allow(clippy::mutable_key_type)]
+    fn finish(self) -> Result<Value, EvalError> {
+        let mut values = self.values;
+        match self.node.kind() {
+            // If there's only one top-level form, return it directly
+            SyntaxKind::Root if values.len() == 1 => Ok(values.remove(0)),
+            SyntaxKind::Root => Ok(Value::List(values)),
+
+            // Collections
+            SyntaxKind::List => Ok(Value::List(values)),
+            SyntaxKind::Vector => Ok(Value::Vector(values)),
+            SyntaxKind::Map => {
+                // Check if we have an odd number of elements
+                if !values.len().is_multiple_of(2) {
+                    return Err(EvalError::SyntaxError("Map literal must have an even number of forms".to_string()));
                 }
-            }
-            Ok(Value::Set(set))
-        }
-        
-        // Reader macros
-        SyntaxKind::Quote => {
-            let mut items = Vec::new();
-            items.push(Value::Symbol("quote".to_string()));
-            
-            for child in node.children() {
-                if child.kind() != SyntaxKind::Quote {
-                    items.push(read(&child)?);
+
+                let mut map = HashMap::new();
+                let mut values = values.into_iter();
+                while let (Some(k), Some(v)) = (values.next(), values.next()) {
+                    map.insert(k, v);
                 }
+                Ok(Value::Map(map))
             }
-            
-            Ok(Value::List(items))
-        }
-        SyntaxKind::Backtick => {
-            let mut items = Vec::new();
-            items.push(Value::Symbol("quasiquote".to_string()));
-            
-            for child in node.children() {
-                if child.kind() != SyntaxKind::Backtick {
-                    items.push(read(&child)?);
+            SyntaxKind::Set => Ok(Value::Set(values.into_iter().collect())),
+
+            // Reader macros. The quote token is not a node, so the only child
+            // is the quoted form
+            SyntaxKind::Quote => Ok(reader_macro("quote", values)),
+            SyntaxKind::Backtick => Ok(reader_macro("quasiquote", values)),
+            SyntaxKind::Comma => Ok(reader_macro("unquote", values)),
+            SyntaxKind::CommaAt => Ok(reader_macro("unquote-splicing", values)),
+
+            SyntaxKind::Meta => {
+                // ^meta form reads as (with-meta form meta). A keyword is shorthand for
+                // {:keyword true}, and a symbol or string for {:tag tag}
+                if values.len() != 2 {
+                    return Err(EvalError::SyntaxError("^ must be followed by metadata and a form".to_string()));
                 }
+
+                let form = values.pop().unwrap();
+                let meta = match values.pop().unwrap() {
+                    meta @ Value::Map(_) => meta,
+                    keyword @ Value::Keyword(_) => {
+                        let mut meta = HashMap::new();
+                        meta.insert(keyword, Value::Boolean(true));
+                        Value::Map(meta)
+                    }
+                    tag @ (Value::Symbol(_) | Value::String(_)) => {
+                        let mut meta = HashMap::new();
+                        meta.insert(Value::Keyword("tag".to_string()), tag);
+                        Value::Map(meta)
+                    }
+                    other => return Err(EvalError::TypeError {
+                        expected: "map, keyword, symbol or string metadata".to_string(),
+                        got: format!("{:?}", other),
+                    }),
+                };
+
+                Ok(Value::List(vec![Value::Symbol("with-meta".to_string()), form, meta]))
             }
-            
-            Ok(Value::List(items))
-        }
-        SyntaxKind::Comma => {
-            let mut items = Vec::new();
-            items.push(Value::Symbol("unquote".to_string()));
-            
-            for child in node.children() {
-                if child.kind() != SyntaxKind::Comma {
-                    items.push(read(&child)?);
+
+            // For other node types, use the values of their children
+            _ => {
+                if values.len() == 1 {
+                    Ok(values.remove(0))
+                } else if values.is_empty() {
+                    Ok(Value::Nil)
+                } else {
+                    Ok(Value::List(values))
                 }
             }
-            
-            Ok(Value::List(items))
-        }
-        SyntaxKind::CommaAt => {
-            let mut items = Vec::new();
-            items.push(Value::Symbol("unquote-splicing".to_string()));
-            
-            for child in node.children() {
-                if child.kind() != SyntaxKind::CommaAt {
-                    items.push(read(&child)?);
-                }
+        }
+    }
+}
+
+/// Builds the form a reader macro such as `'x` stands for, like `(quote x)`
+fn reader_macro(name: &str, values: Vec<Value>) -> Value {
+    let mut items = vec![Value::Symbol(name.to_string())];
+    items.extend(values);
+    Value::List(items)
+}
+
+/// Reads every top-level form of a root syntax node, in order
+pub fn read_forms(root: &SyntaxNode) -> Result<Vec<Value>, EvalError> {
+    let mut forms = Vec::new();
+    for child in root.children() {
+        if child.kind() != SyntaxKind::Eof {
+            forms.push(read(&child)?);
+        }
+    }
+    Ok(forms)
+}
+
+/// Evaluates a Citrine value in the given environment
+pub fn eval(value: &Value, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    eval_with_target(value, env, None)
+}
+
+/// The innermost `loop` (or function body) that a `recur` jumps back to
+#[derive(Clone)]
+struct RecurTarget {
+    /// Binding patterns rebound by `recur`
+    params: Vec<Value>,
+    body: Rc<Vec<Value>>,
+    env: Rc<RefCell<Environment>>,
+}
+
+/// Evaluates a value with an optional recur target.
+///
+/// A value evaluated with a target is in tail position with respect to it. Special
+/// forms whose result is the result of one of their subforms (`if`, `do`, `let`,
+/// `loop`, ...) keep the target and continue on that subform, so `recur` can
+/// rebind the target's parameters and jump back without growing the stack.
+/// Non-tail subforms are evaluated without a target, so a `recur` there is
+/// rejected.
+fn eval_with_target(
+    value: &Value,
+    env: &Rc<RefCell<Environment>>,
+    target: Option<RecurTarget>,
+) -> Result<Value, EvalError> {
+    machine::run(value, env, target)
+}
+
+/// Longest printed argument forms kept in a call error
+const MAX_CALL_ARGS_LENGTH: usize = 40;
+
+/// Adds the call form to the `NotCallable` and `ArityMismatch` errors raised by
+/// the call being evaluated. Errors raised by calls nested in it already carry
+/// the call they were raised at: calls in a function body have their own form,
+/// and functions that a builtin calls back are marked by `call`
+fn at_call(error: EvalError, items: &[Value]) -> EvalError {
+    call_error(error, || (items[0].to_string(), print_args(&items[1..])))
+}
+
+/// Adds the called function to the `NotCallable` and `ArityMismatch` errors
+/// raised by calling it from a builtin, so they are not taken for errors of the
+/// builtin's own call. The arguments are shown when they were not consumed by
+/// the call
+fn at_callback(error: EvalError, func: &Value, args: Option<&[Value]>) -> EvalError {
+    call_error(error, || {
+        let function = match func {
+            Value::Function(f) => f.name.clone().unwrap_or_else(|| func.to_string()),
+            _ => func.to_string(),
+        };
+        (function, args.map_or_else(|| "...".to_string(), print_args))
+    })
+}
+
+/// Wraps a `NotCallable` or `ArityMismatch` error with the printed function and
+/// arguments of the call that raised it, leaving other errors as they are
+fn call_error(error: EvalError, call: impl FnOnce() -> (String, String)) -> EvalError {
+    match error {
+        EvalError::NotCallable(_) | EvalError::ArityMismatch { .. } => {
+            let (function, args) = call();
+            EvalError::Call {
+                function,
+                args,
+                error: Box::new(error),
             }
-            
-            Ok(Value::List(items))
-        }
-        
-        // Other node types
-        _ => {
-            // For other node types, try to process their children
-            let mut forms = Vec::new();
-            for child in node.children() {
-                forms.push(read(&child)?);
+        }
+        error => error,
+    }
+}
+
+/// Prints the arguments of a call, truncated to MAX_CALL_ARGS_LENGTH characters
+fn print_args(args: &[Value]) -> String {
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(" ");
+    match args.char_indices().nth(MAX_CALL_ARGS_LENGTH) {
+        Some((end, _)) => format!("{}...", &args[..end]),
+        None => args,
+    }
+}
+
+/// Evaluates `(setq name value)`, setting the name in the current environment.
+/// Inside a function or `let` this creates a new local that shadows any outer
+/// binding; use `set!` to update the outer binding instead.
+fn eval_setq(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: items.len() - 1,
+        });
+    }
+
+    let symbol = match &items[1] {
+        Value::Symbol(s) => s.clone(),
+        _ => return Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", items[1]),
+        }),
+    };
+
+    let value = eval(&items[2], env)?;
+    env.borrow_mut().set(symbol, value.clone());
+
+    Ok(value)
+}
+
+/// Evaluates `(set! name value)`, updating the binding of the name in the
+/// nearest environment that has it. Unlike `setq`, it never creates a binding,
+/// so a closure can update a local it captured.
+fn eval_set(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: items.len() - 1,
+        });
+    }
+
+    let symbol = match &items[1] {
+        Value::Symbol(s) => s,
+        _ => return Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", items[1]),
+        }),
+    };
+
+    let value = eval(&items[2], env)?;
+    if env.borrow_mut().assign(symbol, value.clone()) {
+        Ok(value)
+    } else {
+        Err(EvalError::UnboundSymbol(symbol.clone()))
+    }
+}
+
+/// Evaluates `(def name value)`, defining the name in the root environment
+fn eval_def(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: items.len() - 1,
+        });
+    }
+
+    // The name may carry metadata, as in (def ^:dynamic *x* 1)
+    let (symbol, meta) = match &items[1] {
+        Value::Symbol(s) => (s.clone(), None),
+        Value::List(meta_form) if meta_form.len() == 3
+            && meta_form[0] == Value::Symbol("with-meta".to_string()) =>
+        {
+            match (&meta_form[1], &meta_form[2]) {
+                (Value::Symbol(s), Value::Map(meta)) => (s.clone(), Some(meta)),
+                _ => return Err(EvalError::TypeError {
+                    expected: "symbol".to_string(),
+                    got: format!("{:?}", meta_form[1]),
+                }),
             }
-            
-            if forms.len() == 1 {
-                Ok(forms.remove(0))
-            } else if forms.is_empty() {
-                Ok(Value::Nil)
-            } else {
-                Ok(Value::List(forms))
+        }
+        _ => return Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", items[1]),
+        }),
+    };
+
+    // Definitions always go to the root environment, even when
+    // evaluated inside a function body or a let
+    let value = eval(&items[2], env)?;
+    let root = Environment::root(env);
+    root.borrow_mut().set(symbol.clone(), value);
+
+    let dynamic = meta
+        .and_then(|meta| meta.get(&Value::Keyword("dynamic".to_string())))
+        .is_some_and(|dynamic| dynamic.is_truthy());
+    if dynamic {
+        root.borrow().context().declare_dynamic(&symbol);
+    }
+
+    Ok(Value::Symbol(symbol))
+}
+
+/// Evaluates `(defn name doc? [params] body*)` or `(defmacro name doc? [params] body*)`
+fn eval_defn(name: &str, items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() < 4 {
+        return Err(EvalError::ArityMismatch {
+            expected: 3,
+            got: items.len() - 1,
+        });
+    }
+
+    let symbol = match &items[1] {
+        Value::Symbol(s) => s.clone(),
+        _ => return Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", items[1]),
+        }),
+    };
+
+    // An optional docstring may precede the params vector
+    let (doc, rest) = match &items[2] {
+        Value::String(doc) => (Some(doc.clone()), &items[3..]),
+        _ => (None, &items[2..]),
+    };
+
+    // (defn name [params] body*) is sugar for (def name (fn [params] body*)),
+    // and (defmacro name [params] body*) for (def name (macro [params] body*))
+    let special_form = if name == "defn" { "fn" } else { "macro" };
+    let mut fn_form = vec![Value::Symbol(special_form.to_string())];
+    fn_form.extend_from_slice(rest);
+
+    let mut value = eval(&Value::List(fn_form), env)?;
+    match &mut value {
+        Value::Function(f) => {
+            let f_mut = Rc::make_mut(f);
+            f_mut.name = Some(symbol.clone());
+            f_mut.doc = doc;
+        }
+        Value::Macro(mac) => {
+            let mac_mut = Rc::make_mut(mac);
+            mac_mut.name = Some(symbol.clone());
+            mac_mut.doc = doc;
+        }
+        _ => {}
+    }
+    Environment::root(env).borrow_mut().set(symbol.clone(), value);
+
+    Ok(Value::Symbol(symbol))
+}
+
+/// Expands `(when test body*)` to `(if test (do body*))`, and when-not to the
+/// same with the branches swapped
+fn expand_when(name: &str, items: &[Value]) -> Result<Value, EvalError> {
+    if items.len() < 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let mut body = vec![Value::Symbol("do".to_string())];
+    body.extend_from_slice(&items[2..]);
+
+    Ok(if name == "when" {
+        Value::List(vec![Value::Symbol("if".to_string()), items[1].clone(), Value::List(body)])
+    } else {
+        Value::List(vec![Value::Symbol("if".to_string()), items[1].clone(), Value::Nil, Value::List(body)])
+    })
+}
+
+/// Expands `(if-not test then else?)` to `(if test else then)`
+fn expand_if_not(items: &[Value]) -> Result<Value, EvalError> {
+    if items.len() != 3 && items.len() != 4 {
+        return Err(EvalError::ArityMismatch {
+            expected: 3,
+            got: items.len() - 1,
+        });
+    }
+
+    let else_branch = items.get(3).cloned().unwrap_or(Value::Nil);
+    Ok(Value::List(vec![
+        Value::Symbol("if".to_string()),
+        items[1].clone(),
+        else_branch,
+        items[2].clone(),
+    ]))
+}
+
+/// Expands `(if-let [pattern test] then else?)` to
+/// `(let [tmp test] (if tmp (let [pattern tmp] then) else))`, and when-let to the
+/// same with a body and no else branch. The value is held in a generated local,
+/// so the pattern is not bound in the else branch.
+fn expand_if_let(name: &str, items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if name == "if-let" && items.len() != 3 && items.len() != 4 {
+        return Err(EvalError::ArityMismatch {
+            expected: 3,
+            got: items.len() - 1,
+        });
+    }
+
+    let (pattern, test) = match items.get(1) {
+        Some(Value::Vector(binding)) if binding.len() == 2 => (&binding[0], &binding[1]),
+        _ => return Err(EvalError::SyntaxError(format!(
+            "{} requires a vector with exactly one binding pair",
+            name
+        ))),
+    };
+
+    let temp = Value::Symbol(env.borrow().context().gensym("temp__"));
+    let mut bound = vec![
+        Value::Symbol("let".to_string()),
+        Value::Vector(vec![pattern.clone(), temp.clone()]),
+    ];
+    let mut branches = Vec::new();
+    if name == "if-let" {
+        bound.push(items[2].clone());
+        branches.push(items.get(3).cloned().unwrap_or(Value::Nil));
+    } else {
+        bound.extend_from_slice(&items[2..]);
+    }
+
+    let mut conditional = vec![Value::Symbol("if".to_string()), temp.clone(), Value::List(bound)];
+    conditional.extend(branches);
+
+    Ok(Value::List(vec![
+        Value::Symbol("let".to_string()),
+        Value::Vector(vec![temp, test.clone()]),
+        Value::List(conditional),
+    ]))
+}
+
+/// Creates the environment of a `letfn` form, with its functions installed
+fn bind_letfn(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    if items.len() < 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let specs = match &items[1] {
+        Value::Vector(specs) => specs,
+        _ => return Err(EvalError::TypeError {
+            expected: "vector".to_string(),
+            got: format!("{:?}", items[1]),
+        }),
+    };
+
+    // All the functions close over the same environment, which is
+    // created before any of them, so they can call each other
+    let letfn_env = Rc::new(RefCell::new(Environment::with_outer(env.clone())));
+    for spec in specs {
+        let (fn_name, fn_tail) = match spec {
+            Value::List(spec) if spec.len() >= 3 => match &spec[0] {
+                Value::Symbol(fn_name) => (fn_name.clone(), &spec[1..]),
+                _ => return Err(EvalError::TypeError {
+                    expected: "function name symbol in letfn".to_string(),
+                    got: format!("{:?}", spec[0]),
+                }),
+            },
+            _ => return Err(EvalError::TypeError {
+                expected: "(name [params] body*) in letfn".to_string(),
+                got: format!("{:?}", spec),
+            }),
+        };
+
+        let mut fn_form = vec![Value::Symbol("fn".to_string())];
+        fn_form.extend_from_slice(fn_tail);
+
+        let mut value = eval(&Value::List(fn_form), &letfn_env)?;
+        if let Value::Function(f) = &mut value {
+            Rc::make_mut(f).name = Some(fn_name.clone());
+        }
+        letfn_env.borrow_mut().set(fn_name, value);
+    }
+
+    Ok(letfn_env)
+}
+
+/// Binds the evaluated arguments of a `recur` to the target's parameters in a
+/// fresh environment
+fn bind_recur(recur_target: &RecurTarget, args: Vec<Value>) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    if args.len() != recur_target.params.len() {
+        return Err(EvalError::ArityMismatch {
+            expected: recur_target.params.len(),
+            got: args.len(),
+        });
+    }
+
+    let recur_env = Rc::new(RefCell::new(Environment::with_outer(recur_target.env.clone())));
+    for (param, arg) in recur_target.params.iter().zip(args) {
+        for (name, value) in destructure(param, arg, &recur_env)? {
+            recur_env.borrow_mut().set(name, value);
+        }
+    }
+
+    Ok(recur_env)
+}
+
+/// Checks the metadata of `(with-meta form meta)` and returns the form
+fn eval_with_meta(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() != 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: items.len() - 1,
+        });
+    }
+
+    // Values do not carry metadata, so it is only checked and then dropped.
+    // Metadata matters to the forms that read it, like def
+    match eval(&items[2], env)? {
+        Value::Map(_) | Value::Nil => {}
+        other => return Err(EvalError::TypeError {
+            expected: "map".to_string(),
+            got: format!("{:?}", other),
+        }),
+    }
+
+    Ok(items[1].clone())
+}
+
+/// Evaluates `(binding [var value*] body*)` with the dynamic vars rebound
+fn eval_binding(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() < 2 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: 0,
+        });
+    }
+
+    let bindings = match &items[1] {
+        Value::Vector(bindings) if bindings.len() % 2 == 0 => bindings,
+        _ => return Err(EvalError::SyntaxError(
+            "binding requires a vector with an even number of forms".to_string(),
+        )),
+    };
+
+    // All the values are evaluated before any of the vars is rebound
+    let context = env.borrow().context();
+    let mut frame = HashMap::new();
+    for pair in bindings.chunks(2) {
+        let name = match &pair[0] {
+            Value::Symbol(name) if context.is_dynamic(name) => name.clone(),
+            Value::Symbol(name) => return Err(EvalError::Other(format!(
+                "Can't dynamically bind non-dynamic var: {}",
+                name
+            ))),
+            other => return Err(EvalError::TypeError {
+                expected: "symbol".to_string(),
+                got: format!("{:?}", other),
+            }),
+        };
+        frame.insert(name, eval(&pair[1], env)?);
+    }
+
+    // The body is not in tail position, since the bindings must be
+    // popped once it finishes, whether it fails or not
+    context.push_bindings(frame);
+    let result = eval_body(&items[2..], env).and_then(|last| eval(&last, env));
+    context.pop_bindings();
+
+    result
+}
+
+/// Evaluates `(fn name? [params] body*)` or `(macro [params] body*)`, closing over `env`.
+///
+/// A named fn closes over an environment of its own, where the name is bound
+/// to the function itself so that it can call itself without a global definition.
+fn eval_fn(name: &str, items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    // Drop the name, so the params are at the same place in both forms
+    let (fn_name, items) = match items.get(1) {
+        Some(Value::Symbol(fn_name)) if name == "fn" => (Some(fn_name.clone()), &items[1..]),
+        _ => (None, items),
+    };
+
+    if items.len() < 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 2,
+            got: items.len() - 1,
+        });
+    }
+
+    let params = parse_params(&items[1])?;
+    let body = if name == "fn" {
+        params.wrap_body(&add_conditions(&items[2..])?)
+    } else {
+        params.wrap_body(&items[2..])
+    };
+
+    if name == "macro" {
+        let mut mac = Macro::new(params.names, body, env.clone());
+        mac.rest = params.rest;
+        return Ok(Value::Macro(Rc::new(mac)));
+    }
+
+    let mut function = Function::new(params.names, body, env.clone());
+    function.rest = params.rest;
+
+    let fn_name = match fn_name {
+        Some(fn_name) => fn_name,
+        None => return Ok(Value::Function(Rc::new(function))),
+    };
+
+    // The function is created before the environment it closes over can bind it
+    let fn_env = Rc::new(RefCell::new(Environment::with_outer(env.clone())));
+    function.env = fn_env.clone();
+    function.name = Some(fn_name.clone());
+    let value = Value::Function(Rc::new(function));
+    fn_env.borrow_mut().set(fn_name, value.clone());
+
+    Ok(value)
+}
+
+/// Adds the checks of a `{:pre [...] :post [...]}` map starting a function body
+/// around the rest of the body. Preconditions are asserted before the body runs,
+/// and postconditions after it, with `%` bound to its value. A map that is the
+/// whole body is the value returned, not a condition map.
+fn add_conditions(body: &[Value]) -> Result<Vec<Value>, EvalError> {
+    let (conditions, rest) = match body {
+        [Value::Map(conditions), rest @ ..] if !rest.is_empty() => (conditions, rest),
+        _ => return Ok(body.to_vec()),
+    };
+
+    // The conditions under a key, each wrapped in an assert
+    let checks = |key: &str| -> Result<Vec<Value>, EvalError> {
+        match conditions.get(&Value::Keyword(key.to_string())) {
+            None => Ok(vec![]),
+            Some(Value::Vector(forms)) => Ok(forms
+                .iter()
+                .map(|form| Value::List(vec![Value::Symbol("assert".to_string()), form.clone()]))
+                .collect()),
+            Some(other) => Err(EvalError::TypeError {
+                expected: format!("vector of conditions after :{}", key),
+                got: format!("{:?}", other),
+            }),
+        }
+    };
+
+    let mut checked = checks("pre")?;
+    let post = checks("post")?;
+    if post.is_empty() {
+        checked.extend_from_slice(rest);
+        return Ok(checked);
+    }
+
+    // (let [% (do body*)] post* %)
+    let mut result = vec![Value::Symbol("do".to_string())];
+    result.extend_from_slice(rest);
+    let mut let_form = vec![
+        Value::Symbol("let".to_string()),
+        Value::Vector(vec![Value::Symbol("%".to_string()), Value::List(result)]),
+    ];
+    let_form.extend(post);
+    let_form.push(Value::Symbol("%".to_string()));
+    checked.push(Value::List(let_form));
+
+    Ok(checked)
+}
+
+/// Evaluates `(assert test message?)`, failing with the printed test form when
+/// the test is falsey. Nothing is evaluated while `*assert*` is false.
+fn eval_assert(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    if items.len() != 2 && items.len() != 3 {
+        return Err(EvalError::ArityMismatch {
+            expected: 1,
+            got: items.len() - 1,
+        });
+    }
+
+    let enabled = env.borrow().get("*assert*").is_none_or(|enabled| enabled.is_truthy());
+    if !enabled || eval(&items[1], env)?.is_truthy() {
+        return Ok(Value::Nil);
+    }
+
+    let message = match items.get(2) {
+        Some(message) => match &eval(message, env)? {
+            Value::String(message) => Some(message.clone()),
+            other => Some(other.to_string()),
+        },
+        None => None,
+    };
+
+    Err(EvalError::AssertionFailed {
+        form: format!("{:?}", items[1]),
+        message,
+    })
+}
+
+/// Fails once a form has been macro-expanded more times in a row than the limit
+fn check_expansion_limit(expansions: usize, mac: &Macro, env: &Rc<RefCell<Environment>>) -> Result<(), EvalError> {
+    let limit = env.borrow().context().max_macro_expansions();
+    if expansions > limit {
+        return Err(EvalError::Other(format!(
+            "Macro expansion limit of {} exceeded expanding {}",
+            limit,
+            mac.name.as_deref().unwrap_or("an anonymous macro")
+        )));
+    }
+    Ok(())
+}
+
+/// Evaluates all but the last form of a body and returns the last form, so the
+/// caller can evaluate it in tail position. An empty body yields nil.
+fn eval_body(body: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    match body.split_last() {
+        Some((last, init)) => {
+            for expr in init {
+                eval(expr, env)?;
             }
+            Ok(last.clone())
         }
+        None => Ok(Value::Nil),
     }
 }
 
-/// Evaluates a Citrine value in the given environment
-pub fn eval(value: &Value, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
-    match value {
-        // Self-evaluating forms
-        Value::Nil | Value::Boolean(_) | Value::Number(_) | Value::String(_) | Value::Keyword(_) => {
-            Ok(value.clone())
-        }
-        
-        // Symbol lookup
-        Value::Symbol(name) => {
-            env.borrow().get(name).ok_or_else(|| EvalError::UnboundSymbol(name.clone()))
-        }
-        
-        // List evaluation (function call or special form)
-        Value::List(items) => {
-            if items.is_empty() {
-                return Ok(Value::List(vec![]));
+/// Evaluates the body of a `try` form: `(try body* (catch e handler*) (finally cleanup*))`.
+///
+/// An error raised by the body is caught by the catch clause, if there is one,
+/// which binds the thrown value (or the error map of an internal error) to `e`.
+/// The finally clause always runs last and its value is discarded; an error
+/// raised by it replaces the result.
+fn eval_try(forms: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    // Returns the clause if the form is a list headed by the given symbol
+    let clause = |form: &Value, name: &str| -> Option<Vec<Value>> {
+        match form {
+            Value::List(items) if items.first() == Some(&Value::Symbol(name.to_string())) => {
+                Some(items.clone())
             }
-            
-            // Get the first item (function or special form)
-            let first = &items[0];
-            
-            // Check for special forms
-            if let Value::Symbol(name) = first {
-                match name.as_str() {
-                    // Special form: setq
-                    "setq" => {
-                        if items.len() != 3 {
-                            return Err(EvalError::ArityMismatch {
-                                expected: 2,
-                                got: items.len() - 1,
-                            });
-                        }
-                        
-                        let symbol = match &items[1] {
-                            Value::Symbol(s) => s.clone(),
-                            _ => return Err(EvalError::TypeError {
-                                expected: "symbol".to_string(),
-                                got: format!("{:?}", items[1]),
-                            }),
-                        };
-                        
-                        let value = eval(&items[2], env)?;
-                        env.borrow_mut().set(symbol, value.clone());
-                        
-                        Ok(value)
-                    }
-                    
-                    // Special form: fn
-                    "fn" => {
-                        if items.len() < 3 {
-                            return Err(EvalError::ArityMismatch {
-                                expected: 2,
-                                got: items.len() - 1,
-                            });
-                        }
-                        
-                        let params = match &items[1] {
-                            Value::Vector(params) => {
-                                let mut param_names = Vec::new();
-                                for param in params {
-                                    match param {
-                                        Value::Symbol(name) => param_names.push(name.clone()),
-                                        _ => return Err(EvalError::TypeError {
-                                            expected: "symbol".to_string(),
-                                            got: format!("{:?}", param),
-                                        }),
-                                    }
-                                }
-                                param_names
-                            }
-                            _ => return Err(EvalError::TypeError {
-                                expected: "vector".to_string(),
-                                got: format!("{:?}", items[1]),
-                            }),
-                        };
-                        
-                        let body = items[2..].to_vec();
-                        
-                        Ok(Value::Function(Function::new(params, body, env.clone())))
-                    }
-                    
-                    // Special form: macro
-                    "macro" => {
-                        if items.len() < 3 {
-                            return Err(EvalError::ArityMismatch {
-                                expected: 2,
-                                got: items.len() - 1,
-                            });
-                        }
-                        
-                        let params = match &items[1] {
-                            Value::Vector(params) => {
-                                let mut param_names = Vec::new();
-                                for param in params {
-                                    match param {
-                                        Value::Symbol(name) => param_names.push(name.clone()),
-                                        _ => return Err(EvalError::TypeError {
-                                            expected: "symbol".to_string(),
-                                            got: format!("{:?}", param),
-                                        }),
-                                    }
-                                }
-                                param_names
-                            }
-                            _ => return Err(EvalError::TypeError {
-                                expected: "vector".to_string(),
-                                got: format!("{:?}", items[1]),
-                            }),
-                        };
-                        
-                        let body = items[2..].to_vec();
-                        
-                        Ok(Value::Macro(Macro::new(params, body, env.clone())))
-                    }
-                    
-                    // Regular function call
-                    _ => apply_function(items, env),
-                }
-            } else {
-                // First item is not a symbol, try to evaluate it as a function
-                apply_function(items, env)
+            _ => None,
+        }
+    };
+
+    let mut body = forms;
+    let finally = match body.split_last() {
+        Some((last, init)) => match clause(last, "finally") {
+            Some(finally) => {
+                body = init;
+                Some(finally)
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let catch = match body.split_last() {
+        Some((last, init)) => match clause(last, "catch") {
+            Some(catch) => {
+                body = init;
+                Some(catch)
+            }
+            None => None,
+        },
+        None => None,
+    };
+
+    if body.iter().any(|form| clause(form, "catch").is_some() || clause(form, "finally").is_some()) {
+        return Err(EvalError::SyntaxError(
+            "try takes at most one catch and one finally clause, after the body".to_string(),
+        ));
+    }
+
+    if catch.as_ref().is_some_and(|catch| catch.len() < 2) {
+        return Err(EvalError::SyntaxError("catch requires a binding for the error".to_string()));
+    }
+
+    let mut result = eval_body(body, env).and_then(|last| eval(&last, env));
+
+    // A hook's abort goes past every catch to the host, so a debugger or
+    // profiler can always stop the program
+    let caught = result.as_ref().err().filter(|error| !matches!(error, EvalError::Aborted(_)));
+    if let (Some(error), Some(catch)) = (caught, &catch) {
+        let catch_env = Rc::new(RefCell::new(Environment::with_outer(env.clone())));
+        result = destructure(&catch[1], error.to_value(), &catch_env).and_then(|bindings| {
+            for (name, value) in bindings {
+                catch_env.borrow_mut().set(name, value);
             }
+            let last = eval_body(&catch[2..], &catch_env)?;
+            eval(&last, &catch_env)
+        });
+    }
+
+    if let Some(finally) = finally {
+        let last = eval_body(&finally[1..], env)?;
+        eval(&last, env)?;
+    }
+
+    result
+}
+
+/// Expands a macro call by binding the unevaluated argument forms to the macro's
+/// parameters and evaluating its body in the macro's closure environment
+fn expand_macro(mac: &Macro, forms: &[Value]) -> Result<Value, EvalError> {
+    let macro_env = bind_arguments(&mac.params, &mac.rest, &mac.env, forms.to_vec())?;
+
+    let last = eval_body(&mac.body, &macro_env)?;
+    eval(&last, &macro_env)
+}
+
+/// Evaluates a quasiquoted form. Everything is quoted except `(unquote x)` forms,
+/// which are replaced by the value of `x`, and `(unquote-splicing x)` forms inside
+/// lists and vectors, which are replaced by the elements of the value of `x`.
+///
+/// Symbols ending in `#` are auto-gensyms: every occurrence of the same `x#` in a
+/// template is replaced by the same generated symbol, recorded in `gensyms`.
+#[allow(clippy::mutable_key_type)]
+fn quasiquote(
+    form: &Value,
+    env: &Rc<RefCell<Environment>>,
+    gensyms: &mut HashMap<String, String>,
+) -> Result<Value, EvalError> {
+    match form {
+        Value::Symbol(name) if name.len() > 1 && name.ends_with('#') => {
+            let generated = gensyms
+                .entry(name.clone())
+                .or_insert_with(|| {
+                    let prefix = format!("{}__", &name[..name.len() - 1]);
+                    format!("{}__auto__", env.borrow().context().gensym(&prefix))
+                })
+                .clone();
+            Ok(Value::Symbol(generated))
         }
-        
-        // Vector evaluation
-        Value::Vector(items) => {
-            let mut result = Vec::new();
-            for item in items {
-                result.push(eval(item, env)?);
+        Value::List(items) => {
+            if let Some(unquoted) = unquoted_form("unquote", form)? {
+                return eval(unquoted, env);
             }
-            Ok(Value::Vector(result))
+            Ok(Value::List(quasiquote_items(items, env, gensyms)?))
         }
-        
-        // Map evaluation
+        Value::Vector(items) => Ok(Value::Vector(quasiquote_items(items, env, gensyms)?)),
         Value::Map(entries) => {
             let mut result = HashMap::new();
             for (k, v) in entries {
-                let key = eval(k, env)?;
-                let value = eval(v, env)?;
-                result.insert(key, value);
+                result.insert(quasiquote(k, env, gensyms)?, quasiquote(v, env, gensyms)?);
             }
             Ok(Value::Map(result))
         }
-        
-        // Set evaluation
         Value::Set(items) => {
             let mut result = HashSet::new();
             for item in items {
-                result.insert(eval(item, env)?);
+                result.insert(quasiquote(item, env, gensyms)?);
             }
             Ok(Value::Set(result))
         }
-        
-        // Functions and macros evaluate to themselves
-        Value::Function(_) | Value::Macro(_) => Ok(value.clone()),
+        _ => Ok(form.clone()),
     }
 }
 
-/// Applies a function to arguments
-fn apply_function(items: &[Value], env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
-    if items.is_empty() {
-        return Err(EvalError::SyntaxError("Empty function application".to_string()));
+/// Quasiquotes the elements of a list or vector, splicing in unquote-splicing forms
+fn quasiquote_items(
+    items: &[Value],
+    env: &Rc<RefCell<Environment>>,
+    gensyms: &mut HashMap<String, String>,
+) -> Result<Vec<Value>, EvalError> {
+    let mut result = Vec::new();
+    for item in items {
+        match unquoted_form("unquote-splicing", item)? {
+            Some(spliced) => match &mut eval(spliced, env)? {
+                Value::Nil => {}
+                Value::List(values) | Value::Vector(values) => result.append(values),
+                other => return Err(EvalError::TypeError {
+                    expected: "list or vector to splice".to_string(),
+                    got: format!("{:?}", other),
+                }),
+            },
+            None => result.push(quasiquote(item, env, gensyms)?),
+        }
     }
-    
-    // Evaluate the function
-    let func = eval(&items[0], env)?;
-    
-    // Evaluate the arguments
-    let mut args = Vec::new();
-    for arg in &items[1..] {
-        args.push(eval(arg, env)?);
+
+    Ok(result)
+}
+
+/// Returns the operand of a `(name operand)` form, or None for any other form
+fn unquoted_form<'a>(name: &str, form: &'a Value) -> Result<Option<&'a Value>, EvalError> {
+    match form {
+        Value::List(items) if items.first() == Some(&Value::Symbol(name.to_string())) => {
+            if items.len() != 2 {
+                return Err(EvalError::ArityMismatch {
+                    expected: 1,
+                    got: items.len() - 1,
+                });
+            }
+            Ok(Some(&items[1]))
+        }
+        _ => Ok(None),
     }
-    
-    // Apply the function
+}
+
+/// A parsed parameter vector
+struct Params {
+    /// Positional parameter names
+    names: Vec<String>,
+    /// The parameter name following `&`, if any
+    rest: Option<String>,
+    /// `let` bindings destructuring the generated names of pattern parameters
+    destructured: Vec<Value>,
+}
+
+impl Params {
+    /// Wraps the body in a `let` destructuring the pattern parameters, if there are any
+    fn wrap_body(&self, body: &[Value]) -> Vec<Value> {
+        if self.destructured.is_empty() {
+            return body.to_vec();
+        }
+
+        let mut let_form = vec![
+            Value::Symbol("let".to_string()),
+            Value::Vector(self.destructured.clone()),
+        ];
+        let_form.extend_from_slice(body);
+        vec![Value::List(let_form)]
+    }
+}
+
+/// Parses a parameter vector into the positional parameter names and the
+/// optional rest parameter that follows `&`.
+///
+/// Like Clojure's `fn`, a parameter that is a destructuring pattern is replaced
+/// by a generated name, and destructured by a `let` around the body.
+fn parse_params(params: &Value) -> Result<Params, EvalError> {
+    let params = match params {
+        Value::Vector(params) => params,
+        _ => return Err(EvalError::TypeError {
+            expected: "vector".to_string(),
+            got: format!("{:?}", params),
+        }),
+    };
+
+    let mut parsed = Params {
+        names: Vec::new(),
+        rest: None,
+        destructured: Vec::new(),
+    };
+
+    // Returns the name to bind a parameter to, generating one for patterns
+    let param_name = |param: &Value, destructured: &mut Vec<Value>| match param {
+        Value::Symbol(name) => Ok(name.clone()),
+        Value::Vector(_) | Value::Map(_) => {
+            let name = format!("p__{}", destructured.len() / 2);
+            destructured.push(param.clone());
+            destructured.push(Value::Symbol(name.clone()));
+            Ok(name)
+        }
+        _ => Err(EvalError::TypeError {
+            expected: "symbol".to_string(),
+            got: format!("{:?}", param),
+        }),
+    };
+
+    for (i, param) in params.iter().enumerate() {
+        match param {
+            Value::Symbol(name) if name == "&" => {
+                // `&` must be followed by exactly one parameter
+                match params.get(i + 1) {
+                    Some(rest) if i + 2 == params.len() && *rest != Value::Symbol("&".to_string()) => {
+                        parsed.rest = Some(param_name(rest, &mut parsed.destructured)?);
+                        break;
+                    }
+                    _ => return Err(EvalError::SyntaxError(
+                        "& must be followed by exactly one parameter name".to_string(),
+                    )),
+                }
+            }
+            _ => {
+                let name = param_name(param, &mut parsed.destructured)?;
+                parsed.names.push(name);
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Checks the number of arguments of a call to a user-defined function. Variadic
+/// functions take at least as many arguments as they have positional parameters
+fn check_arity(params: &[String], rest: &Option<String>, got: usize) -> Result<(), EvalError> {
+    let arity_matches = match rest {
+        Some(_) => got >= params.len(),
+        None => got == params.len(),
+    };
+    if !arity_matches {
+        return Err(EvalError::ArityMismatch {
+            expected: params.len(),
+            got,
+        });
+    }
+    Ok(())
+}
+
+/// Creates the environment for a call to a user-defined function or macro, as a
+/// child of its closure environment, binding the arguments to the parameters
+fn bind_arguments(
+    params: &[String],
+    rest: &Option<String>,
+    closure_env: &Rc<RefCell<Environment>>,
+    mut args: Vec<Value>,
+) -> Result<Rc<RefCell<Environment>>, EvalError> {
+    check_arity(params, rest, args.len())?;
+
+    // Create a new environment for the call
+    let func_env = Rc::new(RefCell::new(Environment::with_outer(closure_env.clone())));
+
+    // Bind the remaining arguments to the rest parameter, nil when there are none
+    if let Some(rest) = rest {
+        let rest_args = args.split_off(params.len());
+        let rest_value = if rest_args.is_empty() {
+            Value::Nil
+        } else {
+            Value::List(rest_args)
+        };
+        func_env.borrow_mut().set(rest.clone(), rest_value);
+    }
+
+    // Bind the arguments to the parameters
+    for (param, arg) in params.iter().zip(args) {
+        func_env.borrow_mut().set(param.clone(), arg);
+    }
+
+    Ok(func_env)
+}
+
+/// Builds the recur target for a function body. A `recur` in a variadic function
+/// passes the rest argument explicitly, as one extra argument
+fn recur_target_for(f: &Function) -> RecurTarget {
+    let mut params: Vec<Value> = f.params.iter().cloned().map(Value::Symbol).collect();
+    if let Some(rest) = &f.rest {
+        params.push(Value::Symbol(rest.clone()));
+    }
+
+    RecurTarget {
+        params,
+        body: f.body.clone(),
+        env: f.env.clone(),
+    }
+}
+
+/// Applies an already evaluated function to already evaluated arguments.
+///
+/// This is how builtins call back into Citrine code: `func` may be a builtin or
+/// user-defined function, or any other callable value (keywords, maps, vectors
+/// and sets). `env` is the environment of the caller, passed on to builtins.
+///
+/// `NotCallable` and `ArityMismatch` errors raised by the call are returned as
+/// `EvalError::Call` errors naming `func`, so they are reported at this call
+/// rather than at the call of the builtin.
+pub fn call(func: &Value, args: Vec<Value>, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
+    match func {
+        Value::Function(f) if !f.is_builtin => {
+            check_arity(&f.params, &f.rest, args.len()).map_err(|e| at_callback(e, func, Some(&args)))?;
+            apply(func, args, env).map_err(|e| at_callback(e, func, None))
+        }
+        Value::Function(_) => apply(func, args, env).map_err(|e| at_callback(e, func, None)),
+        _ => apply_value(func, &args).map_err(|e| at_callback(e, func, Some(&args))),
+    }
+}
+
+/// Applies a function to arguments like `call`, but leaves the errors of the
+/// call itself for the caller to report
+fn apply(func: &Value, args: Vec<Value>, env: &Rc<RefCell<Environment>>) -> Result<Value, EvalError> {
     match func {
         Value::Function(f) => {
             if f.is_builtin {
                 // Call the built-in function
-                if let Some(builtin) = f.builtin_fn {
+                if let Some(builtin) = &f.builtin_fn {
                     return builtin(args, env);
                 } else {
                     return Err(EvalError::Other("Built-in function has no implementation".to_string()));
                 }
             }
-            
-            // Check arity
-            if f.params.len() != args.len() {
-                return Err(EvalError::ArityMismatch {
-                    expected: f.params.len(),
-                    got: args.len(),
+
+            let func_env = bind_arguments(&f.params, &f.rest, &f.env, args)?;
+
+            // Evaluate the body, with the function itself as the target of any
+            // recur in tail position
+            let last = eval_body(&f.body, &func_env)?;
+            eval_with_target(&last, &func_env, Some(recur_target_for(f)))
+        }
+        _ => apply_value(func, &args),
+    }
+}
+
+/// Applies a value that is not a function, such as a keyword or a map
+fn apply_value(func: &Value, args: &[Value]) -> Result<Value, EvalError> {
+    match func {
+        // Macros only make sense applied to unevaluated forms, in `eval`
+        Value::Macro(_) => {
+            Err(EvalError::Other("Cannot apply a macro to evaluated arguments".to_string()))
+        }
+
+        // (:key map) and (:key map default) look the keyword up in the map
+        Value::Keyword(_) => {
+            check_arity_range(args, 1, 2)?;
+            let found = match &args[0] {
+                Value::Map(map) => map.get(func).cloned(),
+                Value::Set(set) => set.get(func).cloned(),
+                _ => None,
+            };
+            Ok(found.or_else(|| args.get(1).cloned()).unwrap_or(Value::Nil))
+        }
+
+        // (map key) and (map key default) look the key up
+        Value::Map(map) => {
+            check_arity_range(args, 1, 2)?;
+            Ok(map.get(&args[0]).or(args.get(1)).cloned().unwrap_or(Value::Nil))
+        }
+
+        // (vector index) returns the element at the index
+        Value::Vector(items) => {
+            check_arity_range(args, 1, 1)?;
+            let index = match &args[0] {
+                Value::Number(n) if n.fract() == 0.0 => *n as i64,
+                _ => return Err(EvalError::TypeError {
+                    expected: "integer index".to_string(),
+                    got: format!("{:?}", args[0]),
+                }),
+            };
+            if index < 0 || index as usize >= items.len() {
+                return Err(EvalError::IndexOutOfBounds {
+                    index,
+                    length: items.len(),
                 });
             }
-            
-            // Create a new environment for the function call
-            let func_env = Rc::new(RefCell::new(Environment::with_outer(f.env.clone())));
-            
-            // Bind the arguments to the parameters
-            for (param, arg) in f.params.iter().zip(args) {
-                func_env.borrow_mut().set(param.clone(), arg);
-            }
-            
-            // Evaluate the body
-            let mut result = Value::Nil;
-            for expr in &f.body {
-                result = eval(expr, &func_env)?;
-            }
-            
-            Ok(result)
+            Ok(items[index as usize].clone())
         }
-        Value::Macro(_) => {
-            Err(EvalError::Other("Macro application not yet implemented".to_string()))
+
+        // (set x) returns x if it is in the set, nil otherwise
+        Value::Set(set) => {
+            check_arity_range(args, 1, 1)?;
+            Ok(set.get(&args[0]).cloned().unwrap_or(Value::Nil))
         }
-        _ => Err(EvalError::NotCallable(func)),
+
+        _ => Err(EvalError::NotCallable(func.clone())),
+    }
+}
+
+/// Forces a value made by `delay`, evaluating its body the first time and
+/// returning the cached result after that. Any other value is returned as is.
+///
+/// A body that raises an error leaves the delay pending, so forcing it again
+/// runs the body again.
+pub fn force(value: &Value) -> Result<Value, EvalError> {
+    let delay = match value {
+        Value::Delay(delay) => delay,
+        other => return Ok(other.clone()),
+    };
+
+    // The state is not borrowed while the body runs, as it may force the delay itself
+    let (body, env) = match &*delay.borrow() {
+        DelayState::Realized(result) => return Ok(result.clone()),
+        DelayState::Pending { body, env } => (body.clone(), env.clone()),
+    };
+
+    let last = eval_body(&body, &env)?;
+    let result = eval(&last, &env)?;
+
+    // If the body forced the delay itself, the first result is the one kept
+    let mut state = delay.borrow_mut();
+    if let DelayState::Realized(first) = &*state {
+        return Ok(first.clone());
+    }
+    *state = DelayState::Realized(result.clone());
+    Ok(result)
+}
+
+/// Checks that a call received between `min` and `max` arguments
+fn check_arity_range(args: &[Value], min: usize, max: usize) -> Result<(), EvalError> {
+    if args.len() < min || args.len() > max {
+        return Err(EvalError::ArityMismatch {
+            expected: if args.len() < min { min } else { max },
+            got: args.len(),
+        });
     }
+    Ok(())
 }
 
 /// Checks if a syntax kind is a delimiter (parentheses, brackets, braces)
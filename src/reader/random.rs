@@ -0,0 +1,47 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Random number generator of an interpreter, used by `rand` and friends.
+///
+/// This is SplitMix64: fast, with a single word of state, and good enough for
+/// simulations and shuffling, but not for cryptography. The same seed always
+/// produces the same numbers.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator that produces the numbers of the seed
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Creates a generator seeded from the randomly keyed hasher of the
+    /// standard library and the current time
+    pub(crate) fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        hasher.write_u128(nanos);
+        Rng::seeded(hasher.finish())
+    }
+
+    /// Returns the next 64 random bits
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in [0, 1), from the top 53 bits so every value is
+    /// exactly representable
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -1,11 +1,17 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+
+use super::hooks::{EvalHooks, Tracer};
+use super::loader::{FileLoader, SourceLoader};
+use super::random::Rng;
 
 /// Represents a Citrine value
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum Value {
+    #[default]
     Nil,
     Boolean(bool),
     Number(f64),
@@ -16,38 +22,113 @@ pub enum Value {
     Vector(Vec<Value>),
     Map(HashMap<Value, Value>),
     Set(HashSet<Value>),
-    Function(Function),
-    Macro(Macro),
+    Function(Rc<Function>),
+    Macro(Rc<Macro>),
+    /// A computation deferred by `delay`, run at most once by `force`
+    Delay(Rc<RefCell<DelayState>>),
+    /// A value wrapped by `reduced`, which ends a `reduce` early
+    Reduced(Box<Value>),
 }
 
 /// Represents a Citrine function
 #[derive(Clone)]
 pub struct Function {
+    pub name: Option<String>,
+    pub doc: Option<String>,
     pub params: Vec<String>,
-    pub body: Vec<Value>,
+    /// Name bound to the remaining arguments of a variadic function (`& rest`)
+    pub rest: Option<String>,
+    /// Shared so that looking a function up does not copy its body
+    pub body: Rc<Vec<Value>>,
     pub env: Rc<RefCell<Environment>>,
     pub is_builtin: bool,
     pub builtin_fn: Option<BuiltinFn>,
 }
 
+/// State of a `delay`: the body and the environment it closes over until it
+/// is forced, then the value the body evaluated to
+pub enum DelayState {
+    Pending {
+        body: Rc<Vec<Value>>,
+        env: Rc<RefCell<Environment>>,
+    },
+    Realized(Value),
+}
+
 /// Represents a Citrine macro
 #[derive(Clone)]
 pub struct Macro {
+    /// Name given by `defmacro`, used in error messages and when printing
+    pub name: Option<String>,
+    pub doc: Option<String>,
     pub params: Vec<String>,
-    pub body: Vec<Value>,
+    pub rest: Option<String>,
+    pub body: Rc<Vec<Value>>,
     pub env: Rc<RefCell<Environment>>,
 }
 
-/// Type for built-in functions
-pub type BuiltinFn = fn(Vec<Value>, &Rc<RefCell<Environment>>) -> Result<Value, EvalError>;
+/// Type for built-in functions. They are closures, so that builtins such as
+/// `partial` can return new functions that capture values
+pub type BuiltinFn = Rc<dyn Fn(Vec<Value>, &Rc<RefCell<Environment>>) -> Result<Value, EvalError>>;
 
 /// Environment for storing variable and function bindings
 #[derive(Clone)]
 pub struct Environment {
     bindings: HashMap<String, Value>,
     outer: Option<Rc<RefCell<Environment>>>,
+    /// Interpreter settings, shared with every environment nested in this one
+    context: Rc<EvalContext>,
+}
+
+/// Interpreter settings shared by a root environment and all of its children
+pub struct EvalContext {
+    max_macro_expansions: Cell<usize>,
+    /// Current nesting depth of evaluation
+    depth: Cell<usize>,
+    max_depth: Cell<usize>,
+    /// Evaluation steps left, when evaluation has a budget
+    fuel: Cell<Option<usize>>,
+    /// The budget the fuel was last set to, for error reporting
+    fuel_budget: Cell<usize>,
+    /// Callbacks around evaluation, if any. `has_hooks` mirrors whether they
+    /// are set, so evaluation without hooks only checks a flag
+    hooks: RefCell<Option<Rc<dyn EvalHooks>>>,
+    has_hooks: Cell<bool>,
+    /// Number of symbols generated so far, to keep generated symbols unique
+    gensym_counter: Cell<usize>,
+    /// Source of `rand`, `rand-int` and `rand-nth`, seeded from entropy until
+    /// `seed_rng` is called
+    rng: RefCell<Rng>,
+    /// Names of the vars defined with `^:dynamic`
+    dynamic_vars: RefCell<HashSet<String>>,
+    /// Frames of dynamic bindings pushed by `binding`, innermost last
+    dynamic_bindings: RefCell<Vec<HashMap<String, Value>>>,
+    /// Where `load-file` and `require` read sources from
+    loader: RefCell<Rc<dyn SourceLoader>>,
+    /// Directories `require` looks for files in, in order
+    load_path: RefCell<Vec<PathBuf>>,
+    /// Names already loaded by `require`
+    loaded: RefCell<HashSet<String>>,
+    /// Names being loaded by `require`, outermost first, to detect cycles
+    loading: RefCell<Vec<String>>,
 }
 
+/// Default number of times a form may be macro-expanded in a row
+pub const DEFAULT_MAX_MACRO_EXPANSIONS: usize = 1000;
+
+/// Bounds on the work an evaluation may do, see `eval_str_with_limits`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of evaluation steps, unlimited when None
+    pub max_steps: Option<usize>,
+    /// Maximum nesting depth, the context's current limit when None
+    pub max_depth: Option<usize>,
+}
+
+/// Default maximum nesting depth of evaluation. Nesting is kept on the heap, so
+/// the limit only bounds the memory used by runaway recursion
+pub const DEFAULT_MAX_DEPTH: usize = 100_000;
+
 /// Evaluation error
 #[derive(Debug, Clone)]
 pub enum EvalError {
@@ -55,8 +136,25 @@ pub enum EvalError {
     NotCallable(Value),
     ArityMismatch { expected: usize, got: usize },
     TypeError { expected: String, got: String },
+    IndexOutOfBounds { index: i64, length: usize },
+    DivisionByZero,
+    /// Evaluation nested deeper than the context's maximum depth
+    RecursionLimit { depth: usize },
+    /// Evaluation took more steps than its budget
+    BudgetExceeded { steps: usize },
     SyntaxError(String),
+    /// A value raised by `throw`
+    Thrown(Value),
+    /// A failed `assert` or `:pre`/`:post` condition, with the printed form
+    /// that was false and the message given to `assert`, if any
+    AssertionFailed { form: String, message: Option<String> },
     Other(String),
+    /// Evaluation stopped by an `EvalHooks` callback, with the error the callback
+    /// returned. Unlike other errors, it is not caught by `try`
+    Aborted(Box<EvalError>),
+    /// An error raised by calling a function, such as `NotCallable` or
+    /// `ArityMismatch`, with the printed function and argument forms of the call
+    Call { function: String, args: String, error: Box<EvalError> },
 }
 
 impl Environment {
@@ -65,24 +163,98 @@ impl Environment {
         Environment {
             bindings: HashMap::new(),
             outer: None,
+            context: Rc::new(EvalContext::new()),
         }
     }
 
     /// Create a new environment with the given outer environment
     pub fn with_outer(outer: Rc<RefCell<Environment>>) -> Self {
+        let context = outer.borrow().context.clone();
         Environment {
             bindings: HashMap::new(),
             outer: Some(outer),
+            context,
         }
     }
 
+    /// Returns the interpreter settings this environment evaluates with
+    pub fn context(&self) -> Rc<EvalContext> {
+        self.context.clone()
+    }
+
     /// Set a value in the environment
     pub fn set(&mut self, key: String, val: Value) {
         self.bindings.insert(key, val);
     }
 
+    /// Updates the existing binding of a name, in this environment or the
+    /// nearest enclosing one that has it. Returns false, leaving every
+    /// environment unchanged, when the name is not bound anywhere.
+    pub fn assign(&mut self, key: &str, val: Value) -> bool {
+        // As with `get`, a dynamically bound var is updated in its innermost
+        // binding rather than at the root
+        let val = if self.outer.is_none() {
+            match self.context.assign_dynamic(key, val) {
+                Ok(()) => return true,
+                Err(val) => val,
+            }
+        } else {
+            val
+        };
+
+        match self.bindings.get_mut(key) {
+            Some(binding) => {
+                *binding = val;
+                true
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow_mut().assign(key, val),
+                None => false,
+            },
+        }
+    }
+
+    /// Returns the names bound directly in this environment, in no particular order
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.bindings.keys()
+    }
+
+    /// Returns the names visible from this environment, including those of the
+    /// enclosing environments, sorted and without duplicates
+    pub fn all_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.keys().cloned().collect();
+        let mut outer = self.outer.clone();
+        while let Some(env) = outer {
+            keys.extend(env.borrow().keys().cloned());
+            outer = env.borrow().outer.clone();
+        }
+
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Returns the root (global) environment reachable from the given environment
+    pub fn root(env: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut current = env.clone();
+        loop {
+            let outer = current.borrow().outer.clone();
+            match outer {
+                Some(outer) => current = outer,
+                None => return current,
+            }
+        }
+    }
+
     /// Get a value from the environment
     pub fn get(&self, key: &str) -> Option<Value> {
+        // Dynamic bindings take precedence over the root bindings, but not over locals
+        if self.outer.is_none() {
+            if let Some(val) = self.context.dynamic_binding(key) {
+                return Some(val);
+            }
+        }
+
         match self.bindings.get(key) {
             Some(val) => Some(val.clone()),
             None => match &self.outer {
@@ -93,12 +265,253 @@ impl Environment {
     }
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvalContext {
+    /// Create the default interpreter settings
+    pub fn new() -> Self {
+        EvalContext {
+            max_macro_expansions: Cell::new(DEFAULT_MAX_MACRO_EXPANSIONS),
+            depth: Cell::new(0),
+            max_depth: Cell::new(DEFAULT_MAX_DEPTH),
+            fuel: Cell::new(None),
+            fuel_budget: Cell::new(0),
+            hooks: RefCell::new(None),
+            has_hooks: Cell::new(false),
+            gensym_counter: Cell::new(0),
+            rng: RefCell::new(Rng::from_entropy()),
+            dynamic_vars: RefCell::new(HashSet::new()),
+            dynamic_bindings: RefCell::new(Vec::new()),
+            loader: RefCell::new(Rc::new(FileLoader)),
+            load_path: RefCell::new(vec![PathBuf::from(".")]),
+            loaded: RefCell::new(HashSet::new()),
+            loading: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Maximum number of times a form may be macro-expanded in a row, for
+    /// example when a macro expands to a call to another macro
+    pub fn max_macro_expansions(&self) -> usize {
+        self.max_macro_expansions.get()
+    }
+
+    /// Set the maximum number of times a form may be macro-expanded in a row
+    pub fn set_max_macro_expansions(&self, limit: usize) {
+        self.max_macro_expansions.set(limit);
+    }
+
+    /// Maximum nesting depth of evaluation, such as non-tail recursive calls
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.get()
+    }
+
+    /// Set the maximum nesting depth of evaluation
+    pub fn set_max_depth(&self, limit: usize) {
+        self.max_depth.set(limit);
+    }
+
+    /// Enters a nested evaluation, failing when it would exceed the maximum depth
+    pub(crate) fn enter(&self) -> Result<(), EvalError> {
+        let depth = self.depth.get() + 1;
+        if depth > self.max_depth.get() {
+            return Err(EvalError::RecursionLimit { depth });
+        }
+        self.depth.set(depth);
+        Ok(())
+    }
+
+    /// Current nesting depth of evaluation
+    pub fn depth(&self) -> usize {
+        self.depth.get()
+    }
+
+    /// Leaves a nested evaluation entered with `enter`
+    pub(crate) fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Returns the hooks called around evaluation, if any
+    pub fn hooks(&self) -> Option<Rc<dyn EvalHooks>> {
+        self.hooks.borrow().clone()
+    }
+
+    /// Set the hooks called around evaluation, or None to remove them
+    pub fn set_hooks(&self, hooks: Option<Rc<dyn EvalHooks>>) {
+        self.has_hooks.set(hooks.is_some());
+        *self.hooks.borrow_mut() = hooks;
+    }
+
+    /// Returns whether hooks are installed
+    pub fn has_hooks(&self) -> bool {
+        self.has_hooks.get()
+    }
+
+    /// Turns tracing of function calls to standard error on or off. Tracing
+    /// replaces any other hooks
+    pub fn set_trace(&self, trace: bool) {
+        let hooks: Option<Rc<dyn EvalHooks>> = if trace { Some(Rc::new(Tracer::stderr())) } else { None };
+        self.set_hooks(hooks);
+    }
+
+    /// Evaluation steps left before evaluation fails, or None when unlimited
+    pub fn fuel(&self) -> Option<usize> {
+        self.fuel.get()
+    }
+
+    /// Set the number of evaluation steps allowed from now on, or None to
+    /// remove the budget. Every evaluated form takes a step, and the collection
+    /// builtins that call a function per element (map, filter, remove, reduce,
+    /// some, every?, not-any?, take-while, drop-while) take one per element.
+    /// sort and sort-by take one per comparison
+    pub fn set_fuel(&self, steps: Option<usize>) {
+        self.fuel.set(steps);
+        self.fuel_budget.set(steps.unwrap_or(0));
+    }
+
+    /// Consumes evaluation steps, failing once the budget is exhausted
+    pub fn consume_fuel(&self, steps: usize) -> Result<(), EvalError> {
+        if let Some(fuel) = self.fuel.get() {
+            if fuel < steps {
+                self.fuel.set(Some(0));
+                return Err(EvalError::BudgetExceeded {
+                    steps: self.fuel_budget.get(),
+                });
+            }
+            self.fuel.set(Some(fuel - steps));
+        }
+        Ok(())
+    }
+
+    /// Generates a symbol name that is unique within this context, made of the
+    /// prefix followed by a number
+    pub fn gensym(&self, prefix: &str) -> String {
+        let n = self.gensym_counter.get() + 1;
+        self.gensym_counter.set(n);
+        format!("{}{}", prefix, n)
+    }
+
+    /// Seeds the random number generator, so the numbers returned by `rand`,
+    /// `rand-int` and `rand-nth` are the same on every run
+    pub fn seed_rng(&self, seed: u64) {
+        *self.rng.borrow_mut() = Rng::seeded(seed);
+    }
+
+    /// Returns a random number in [0, 1)
+    pub fn random(&self) -> f64 {
+        self.rng.borrow_mut().next_f64()
+    }
+
+    /// Marks a root-level var as dynamic, so it can be rebound with `binding`
+    pub fn declare_dynamic(&self, name: &str) {
+        self.dynamic_vars.borrow_mut().insert(name.to_string());
+    }
+
+    /// Returns whether a var was defined as dynamic
+    pub fn is_dynamic(&self, name: &str) -> bool {
+        self.dynamic_vars.borrow().contains(name)
+    }
+
+    /// Pushes a frame of dynamic bindings, which shadow the root bindings of
+    /// the vars until the frame is popped
+    pub(crate) fn push_bindings(&self, frame: HashMap<String, Value>) {
+        self.dynamic_bindings.borrow_mut().push(frame);
+    }
+
+    /// Updates the innermost dynamic binding of a var, giving the value back
+    /// when the var is not dynamically bound
+    pub(crate) fn assign_dynamic(&self, name: &str, val: Value) -> Result<(), Value> {
+        let mut frames = self.dynamic_bindings.borrow_mut();
+        match frames.iter_mut().rev().find_map(|frame| frame.get_mut(name)) {
+            Some(binding) => {
+                *binding = val;
+                Ok(())
+            }
+            None => Err(val),
+        }
+    }
+
+    /// Pops the innermost frame of dynamic bindings
+    pub(crate) fn pop_bindings(&self) {
+        self.dynamic_bindings.borrow_mut().pop();
+    }
+
+    /// Returns the loader `load-file` and `require` read sources with
+    pub fn loader(&self) -> Rc<dyn SourceLoader> {
+        self.loader.borrow().clone()
+    }
+
+    /// Set the loader `load-file` and `require` read sources with
+    pub fn set_loader(&self, loader: Rc<dyn SourceLoader>) {
+        *self.loader.borrow_mut() = loader;
+    }
+
+    /// Returns the directories `require` looks for files in
+    pub fn load_path(&self) -> Vec<PathBuf> {
+        self.load_path.borrow().clone()
+    }
+
+    /// Set the directories `require` looks for files in, in order. The
+    /// default is the current directory
+    pub fn set_load_path(&self, load_path: Vec<PathBuf>) {
+        *self.load_path.borrow_mut() = load_path;
+    }
+
+    /// Returns whether a name was already loaded by `require`
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.borrow().contains(name)
+    }
+
+    /// Records that a name is being loaded. If it is already being loaded,
+    /// returns the chain of requires that leads back to it instead
+    pub(crate) fn start_loading(&self, name: &str) -> Option<Vec<String>> {
+        let mut loading = self.loading.borrow_mut();
+        if let Some(start) = loading.iter().position(|n| n == name) {
+            let mut chain = loading[start..].to_vec();
+            chain.push(name.to_string());
+            return Some(chain);
+        }
+
+        loading.push(name.to_string());
+        None
+    }
+
+    /// Records that a name finished loading, and whether it loaded successfully
+    pub(crate) fn finish_loading(&self, name: &str, loaded: bool) {
+        self.loading.borrow_mut().retain(|n| n != name);
+        if loaded {
+            self.loaded.borrow_mut().insert(name.to_string());
+        }
+    }
+
+    /// Returns the innermost dynamic binding of a var, if it is rebound
+    fn dynamic_binding(&self, name: &str) -> Option<Value> {
+        self.dynamic_bindings
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(name).cloned())
+    }
+}
+
+impl Default for EvalContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Function {
     /// Create a new user-defined function
     pub fn new(params: Vec<String>, body: Vec<Value>, env: Rc<RefCell<Environment>>) -> Self {
         Function {
+            name: None,
+            doc: None,
             params,
-            body,
+            rest: None,
+            body: Rc::new(body),
             env,
             is_builtin: false,
             builtin_fn: None,
@@ -106,13 +519,18 @@ impl Function {
     }
 
     /// Create a new built-in function
-    pub fn builtin(builtin_fn: BuiltinFn) -> Self {
+    pub fn builtin(
+        builtin_fn: impl Fn(Vec<Value>, &Rc<RefCell<Environment>>) -> Result<Value, EvalError> + 'static,
+    ) -> Self {
         Function {
+            name: None,
+            doc: None,
             params: vec![],
-            body: vec![],
+            rest: None,
+            body: Rc::new(vec![]),
             env: Rc::new(RefCell::new(Environment::new())),
             is_builtin: true,
-            builtin_fn: Some(builtin_fn),
+            builtin_fn: Some(Rc::new(builtin_fn)),
         }
     }
 }
@@ -121,8 +539,11 @@ impl Macro {
     /// Create a new macro
     pub fn new(params: Vec<String>, body: Vec<Value>, env: Rc<RefCell<Environment>>) -> Self {
         Macro {
+            name: None,
+            doc: None,
             params,
-            body,
+            rest: None,
+            body: Rc::new(body),
             env,
         }
     }
@@ -178,7 +599,15 @@ impl fmt::Debug for Value {
                 write!(f, "}}")
             }
             Value::Function(_) => write!(f, "#<function>"),
-            Value::Macro(_) => write!(f, "#<macro>"),
+            Value::Macro(m) => match &m.name {
+                Some(name) => write!(f, "#<macro {}>", name),
+                None => write!(f, "#<macro>"),
+            },
+            Value::Delay(d) => match &*d.borrow() {
+                DelayState::Pending { .. } => write!(f, "#<delay :pending>"),
+                DelayState::Realized(v) => write!(f, "#<delay {:?}>", v),
+            },
+            Value::Reduced(v) => write!(f, "#<reduced {:?}>", v),
         }
     }
 }
@@ -189,6 +618,132 @@ impl fmt::Display for Value {
     }
 }
 
+impl Value {
+    /// Returns whether the value counts as true in a condition. Only nil and
+    /// false are falsey; everything else, including 0, "" and empty
+    /// collections, is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// Compares two values in their natural order, as `sort` does: numbers by
+    /// value, strings, keywords and symbols alphabetically, false before true,
+    /// and vectors by length and then element by element. nil comes before
+    /// everything else. Returns None for values that are not comparable, such as
+    /// a number and a string, or NaN
+    pub fn try_cmp(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        use std::cmp::Ordering;
+
+        match (self, other) {
+            (Value::Nil, Value::Nil) => Some(Ordering::Equal),
+            (Value::Nil, _) => Some(Ordering::Less),
+            (_, Value::Nil) => Some(Ordering::Greater),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b))
+            | (Value::Keyword(a), Value::Keyword(b))
+            | (Value::Symbol(a), Value::Symbol(b)) => Some(a.cmp(b)),
+            (Value::Vector(a), Value::Vector(b)) => {
+                if a.len() != b.len() {
+                    return Some(a.len().cmp(&b.len()));
+                }
+                for (x, y) in a.iter().zip(b) {
+                    match x.try_cmp(y)? {
+                        Ordering::Equal => {}
+                        ordering => return Some(ordering),
+                    }
+                }
+                Some(Ordering::Equal)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the number of elements of a collection, of characters of a
+    /// string, or 0 for nil. Other values have no length
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Value::Nil => Some(0),
+            Value::String(s) => Some(s.chars().count()),
+            Value::List(items) | Value::Vector(items) => Some(items.len()),
+            Value::Map(entries) => Some(entries.len()),
+            Value::Set(items) => Some(items.len()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a collection or string has no elements, with nil
+    /// counting as empty. Other values have no length
+    pub fn is_empty(&self) -> Option<bool> {
+        match self {
+            Value::String(s) => Some(s.is_empty()),
+            other => other.len().map(|len| len == 0),
+        }
+    }
+
+    /// Iterates over the elements of a collection, the characters of a string as
+    /// one-character strings, or nothing for nil. The entries of a map are
+    /// `[key value]` vectors. Returns None for values that are not sequences
+    pub fn try_iter(&self) -> Option<Box<dyn Iterator<Item = Value> + '_>> {
+        match self {
+            Value::Nil => Some(Box::new(std::iter::empty())),
+            Value::String(s) => Some(Box::new(s.chars().map(|c| Value::String(c.to_string())))),
+            Value::List(items) | Value::Vector(items) => Some(Box::new(items.iter().cloned())),
+            Value::Map(entries) => Some(Box::new(
+                entries.iter().map(|(k, v)| Value::Vector(vec![k.clone(), v.clone()])),
+            )),
+            Value::Set(items) => Some(Box::new(items.iter().cloned())),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether a value holds other values, which may hold values in turn
+fn holds_values(value: &Value) -> bool {
+    match value {
+        Value::List(items) | Value::Vector(items) => !items.is_empty(),
+        Value::Map(entries) => !entries.is_empty(),
+        Value::Set(items) => !items.is_empty(),
+        Value::Reduced(_) => true,
+        _ => false,
+    }
+}
+
+impl Drop for Value {
+    /// Takes nested collections apart onto a stack on the heap instead of
+    /// dropping them recursively, so dropping deeply nested code, such as a
+    /// generated expression, does not overflow the Rust stack
+    fn drop(&mut self) {
+        let nested = match self {
+            Value::List(items) | Value::Vector(items) => items.iter().any(holds_values),
+            Value::Map(entries) => entries.iter().any(|(k, v)| holds_values(k) || holds_values(v)),
+            Value::Set(items) => items.iter().any(holds_values),
+            Value::Reduced(value) => holds_values(value),
+            _ => false,
+        };
+        if !nested {
+            return;
+        }
+
+        // Each value popped has its elements moved out before it is dropped,
+        // so its own drop returns straight away
+        let mut stack = vec![std::mem::take(self)];
+        while let Some(mut value) = stack.pop() {
+            match &mut value {
+                Value::List(items) | Value::Vector(items) => stack.extend(items.drain(..).filter(holds_values)),
+                Value::Map(entries) => {
+                    for (key, value) in entries.drain() {
+                        stack.extend([key, value].into_iter().filter(holds_values));
+                    }
+                }
+                Value::Set(items) => stack.extend(items.drain().filter(holds_values)),
+                Value::Reduced(inner) => stack.push(std::mem::take(&mut **inner)),
+                _ => {}
+            }
+        }
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -202,6 +757,8 @@ impl PartialEq for Value {
             (Value::Vector(a), Value::Vector(b)) => a == b,
             (Value::Map(a), Value::Map(b)) => a == b,
             (Value::Set(a), Value::Set(b)) => a == b,
+            (Value::Delay(a), Value::Delay(b)) => Rc::ptr_eq(a, b),
+            (Value::Reduced(a), Value::Reduced(b)) => a == b,
             // Functions and macros are compared by identity
             _ => false,
         }
@@ -210,6 +767,10 @@ impl PartialEq for Value {
 
 impl Eq for Value {}
 
+// Clippy's mutable_key_type lint flags maps and sets keyed on `Value`, because
+// functions, macros and delays hold cells. The hash never looks inside them:
+// functions and macros hash by address, and delays by the `Rc` they share, so
+// changing what a cell holds does not change the hash of a key.
 impl std::hash::Hash for Value {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         match self {
@@ -246,7 +807,27 @@ impl std::hash::Hash for Value {
                     item.hash(state);
                 }
             }
-            // Maps and sets can't be hashed in a meaningful way
+            // Delays are equal only to clones of themselves
+            Value::Delay(delay) => {
+                8.hash(state);
+                Rc::as_ptr(delay).hash(state);
+            }
+            Value::Reduced(value) => {
+                9.hash(state);
+                value.hash(state);
+            }
+            // Entries are combined with a wrapping sum of their own hashes, so
+            // equal maps and sets hash the same whatever their iteration order
+            Value::Map(entries) => {
+                10.hash(state);
+                entries.len().hash(state);
+                unordered_hash(entries).hash(state);
+            }
+            Value::Set(items) => {
+                11.hash(state);
+                items.len().hash(state);
+                unordered_hash(items).hash(state);
+            }
             // Functions and macros can't be hashed in a meaningful way
             _ => {
                 // Use the pointer address as a fallback
@@ -256,6 +837,16 @@ impl std::hash::Hash for Value {
     }
 }
 
+/// Returns the wrapping sum of the hashes of a collection's elements, which does
+/// not depend on the order they are visited in
+fn unordered_hash<T: std::hash::Hash>(items: impl IntoIterator<Item = T>) -> u64 {
+    items.into_iter().fold(0u64, |sum, item| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        item.hash(&mut hasher);
+        sum.wrapping_add(std::hash::Hasher::finish(&hasher))
+    })
+}
+
 impl fmt::Display for EvalError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -267,11 +858,71 @@ impl fmt::Display for EvalError {
             EvalError::TypeError { expected, got } => {
                 write!(f, "Type error: expected {}, got {}", expected, got)
             }
+            EvalError::IndexOutOfBounds { index, length } => {
+                write!(f, "Index out of bounds: index {}, length {}", index, length)
+            }
+            EvalError::DivisionByZero => write!(f, "Division by zero"),
+            EvalError::RecursionLimit { depth } => {
+                write!(f, "Recursion limit exceeded: depth {}", depth)
+            }
+            EvalError::BudgetExceeded { steps } => {
+                write!(f, "Evaluation budget of {} steps exceeded", steps)
+            }
             EvalError::SyntaxError(s) => write!(f, "Syntax error: {}", s),
+            EvalError::Thrown(v) => write!(f, "Uncaught exception: {}", v),
+            EvalError::AssertionFailed { form, message } => match message {
+                Some(message) => write!(f, "Assert failed: {}: {}", message, form),
+                None => write!(f, "Assert failed: {}", form),
+            },
             EvalError::Other(s) => write!(f, "Error: {}", s),
+            EvalError::Aborted(error) => write!(f, "Evaluation aborted: {}", error),
+            EvalError::Call { function, args, error } => {
+                if args.is_empty() {
+                    write!(f, "{} in call ({})", error, function)
+                } else {
+                    write!(f, "{} in call ({} {})", error, function, args)
+                }
+            }
         }
     }
 }
 
 impl std::error::Error for EvalError {}
 
+impl EvalError {
+    /// Returns the error without the call it was raised at, if any
+    pub fn cause(&self) -> &EvalError {
+        match self {
+            EvalError::Call { error, .. } => error.cause(),
+            error => error,
+        }
+    }
+
+    /// Converts the error to the value bound by a `catch` clause: the thrown value
+    /// for `throw`, and a map with the error's `:type` and `:message` otherwise
+    #[allow(clippy::mutable_key_type)]
+    pub fn to_value(&self) -> Value {
+        let error_type = match self.cause() {
+            EvalError::Thrown(v) => return v.clone(),
+            EvalError::UnboundSymbol(_) => "unbound-symbol",
+            EvalError::NotCallable(_) => "not-callable",
+            EvalError::ArityMismatch { .. } => "arity-mismatch",
+            EvalError::TypeError { .. } => "type-error",
+            EvalError::IndexOutOfBounds { .. } => "index-out-of-bounds",
+            EvalError::DivisionByZero => "division-by-zero",
+            EvalError::RecursionLimit { .. } => "recursion-limit",
+            EvalError::BudgetExceeded { .. } => "budget-exceeded",
+            EvalError::SyntaxError(_) => "syntax-error",
+            EvalError::AssertionFailed { .. } => "assertion-failed",
+            EvalError::Other(_) => "error",
+            EvalError::Aborted(_) => "aborted",
+            EvalError::Call { .. } => unreachable!("cause() unwraps call errors"),
+        };
+
+        let mut map = HashMap::new();
+        map.insert(Value::Keyword("type".to_string()), Value::Keyword(error_type.to_string()));
+        map.insert(Value::Keyword("message".to_string()), Value::String(self.to_string()));
+        Value::Map(map)
+    }
+}
+
@@ -71,7 +71,8 @@ pub enum SyntaxKind {
 impl SyntaxKind {
     /// Returns true if this syntax kind is a token
     pub fn is_token(&self) -> bool {
-        match self {
+        matches!(
+            self,
             SyntaxKind::LeftParen |
             SyntaxKind::RightParen |
             SyntaxKind::LeftBracket |
@@ -93,9 +94,8 @@ impl SyntaxKind {
             SyntaxKind::CommentToken |
             SyntaxKind::WhitespaceToken |
             SyntaxKind::ErrorToken |
-            SyntaxKind::Eof => true,
-            _ => false,
-        }
+            SyntaxKind::Eof
+        )
     }
 
     /// Returns true if this syntax kind is trivia (whitespace or comment)
@@ -170,6 +170,19 @@ impl Language for CitrineLanguage {
 /// The syntax node type for Citrine
 pub type SyntaxNode = rowan::SyntaxNode<CitrineLanguage>;
 
+/// Drops a syntax tree one node at a time. Dropping the root directly frees
+/// each node's children recursively, which overflows the stack for deeply
+/// nested input
+pub fn drop_tree(root: SyntaxNode) {
+    let mut pending = vec![root.green().into_owned()];
+    drop(root);
+    while let Some(node) = pending.pop() {
+        // The children are kept alive past their parent, so freeing the parent
+        // does not free them
+        pending.extend(node.children().filter_map(|child| child.into_node()).map(|child| child.to_owned()));
+    }
+}
+
 /// The syntax token type for Citrine
 pub type SyntaxToken = rowan::SyntaxToken<CitrineLanguage>;
 
@@ -0,0 +1,1466 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use citrine::{eval_str, standard_env};
+use citrine::reader::{self, Environment, EvalError, Value};
+
+/// Checks that each form evaluates to the same value as its expected form
+fn assert_evals(env: &Rc<RefCell<Environment>>, cases: &[(&str, &str)]) {
+    for (source, expected) in cases {
+        assert_eq!(eval_str(source, env).unwrap(), eval_str(expected, env).unwrap(), "{}", source);
+    }
+}
+
+#[test]
+fn test_less_or_equal_and_greater_or_equal() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(<= 1 2)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(<= 2 2)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(<= 3 2)", &env).unwrap(), Value::Boolean(false));
+    assert_eq!(eval_str("(>= 3 2)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(>= 2 3)", &env).unwrap(), Value::Boolean(false));
+
+    // Chained comparisons check every adjacent pair
+    assert_eq!(eval_str("(<= 1 2 2 3)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(<= 1 3 2)", &env).unwrap(), Value::Boolean(false));
+    assert_eq!(eval_str("(>= 3 3 2 1)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(>= 3 1 2)", &env).unwrap(), Value::Boolean(false));
+
+    // A single argument is always ordered
+    assert_eq!(eval_str("(<= 1)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(>= 1)", &env).unwrap(), Value::Boolean(true));
+    assert!(matches!(eval_str("(<=)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_comparison_type_errors_name_the_argument() {
+    let env = standard_env();
+
+    match eval_str("(<= 1 2 \"three\")", &env) {
+        Err(EvalError::TypeError { expected, got }) => {
+            assert_eq!(expected, "numbers at arguments 2 and 3");
+            assert_eq!(got, "2 and \"three\"");
+        }
+        other => panic!("expected a type error, got {:?}", other),
+    }
+    assert!(matches!(eval_str("(>= :a 1)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_chained_comparisons() {
+    let env = standard_env();
+
+    // Each row is an argument list and whether <, <=, > and >= hold for it
+    let table = [
+        ("1 2 3 4", [true, true, false, false]),
+        ("1 2 2 3", [false, true, false, false]),
+        ("4 3 2 1", [false, false, true, true]),
+        ("3 3 2 1", [false, false, false, true]),
+        ("2 2 2", [false, true, false, true]),
+        ("1 3 2", [false, false, false, false]),
+        ("7", [true, true, true, true]),
+    ];
+
+    for (args, expected) in table {
+        for (op, expected) in ["<", "<=", ">", ">="].iter().zip(expected) {
+            let source = format!("({} {})", op, args);
+            assert_eq!(eval_str(&source, &env).unwrap(), Value::Boolean(expected), "{}", source);
+        }
+    }
+}
+
+#[test]
+fn test_chained_comparison_stops_at_first_failure() {
+    let env = standard_env();
+
+    // Once a pair is out of order, later arguments are not compared
+    assert_eq!(eval_str("(< 2 1 :a)", &env).unwrap(), Value::Boolean(false));
+    match eval_str("(< 1 2 :a)", &env) {
+        Err(EvalError::TypeError { expected, got }) => {
+            assert_eq!(expected, "numbers at arguments 2 and 3");
+            assert_eq!(got, "2 and :a");
+        }
+        other => panic!("expected a type error, got {:?}", other),
+    }
+    assert!(matches!(eval_str("(> :a)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_equality() {
+    let env = standard_env();
+
+    // Each row is an argument list and whether = holds for it; not= is the complement
+    let table = [
+        ("1 1", true),
+        ("1 2", false),
+        ("1 1 1", true),
+        ("1 1 2", false),
+        ("2 1 1", false),
+        ("\"a\" \"a\"", true),
+        ("[1 2] [1 2]", true),
+        ("{:a 1} {:a 1}", true),
+        ("nil nil", true),
+        // Values of different types are never equal
+        ("1 \"1\"", false),
+        ("nil false", false),
+        (":a \"a\"", false),
+        ("'a :a", false),
+    ];
+
+    for (args, equal) in table {
+        let source = format!("(= {})", args);
+        assert_eq!(eval_str(&source, &env).unwrap(), Value::Boolean(equal), "{}", source);
+        let source = format!("(not= {})", args);
+        assert_eq!(eval_str(&source, &env).unwrap(), Value::Boolean(!equal), "{}", source);
+    }
+
+    for source in ["(= 1)", "(not= 1)", "(not=)"] {
+        assert!(
+            matches!(eval_str(source, &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn test_quot_rem_and_mod() {
+    let env = standard_env();
+
+    // Each row is a dividend and a divisor, and the expected quot, rem and mod
+    let table = [
+        ("7 3", [2.0, 1.0, 1.0]),
+        ("-7 3", [-2.0, -1.0, 2.0]),
+        ("7 -3", [-2.0, 1.0, -2.0]),
+        ("-7 -3", [2.0, -1.0, -1.0]),
+        ("6 3", [2.0, 0.0, 0.0]),
+        ("-6 3", [-2.0, 0.0, 0.0]),
+        ("7.5 2", [3.0, 1.5, 1.5]),
+    ];
+
+    for (args, expected) in table {
+        for (op, expected) in ["quot", "rem", "mod"].iter().zip(expected) {
+            let source = format!("({} {})", op, args);
+            assert_eq!(eval_str(&source, &env).unwrap(), Value::Number(expected), "{}", source);
+        }
+    }
+
+    // Results are never a negative zero
+    assert_eq!(eval_str("(rem -6 3)", &env).unwrap().to_string(), "0");
+    assert_eq!(eval_str("(quot -1 3)", &env).unwrap().to_string(), "0");
+}
+
+#[test]
+fn test_quot_rem_and_mod_errors() {
+    let env = standard_env();
+
+    for op in ["quot", "rem", "mod", "/"] {
+        let source = format!("({} 1 0)", op);
+        assert!(matches!(eval_str(&source, &env), Err(EvalError::DivisionByZero)), "{}", source);
+    }
+    assert!(matches!(eval_str("(mod 1 :a)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(rem 1)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_inc_and_dec() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(inc 1)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(dec 1)", &env).unwrap(), Value::Number(0.0));
+    assert_eq!(eval_str("(inc 1.5)", &env).unwrap(), Value::Number(2.5));
+    assert_eq!(eval_str("(dec (inc 0))", &env).unwrap(), Value::Number(0.0));
+    assert!(matches!(eval_str("(inc :a)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(dec 1 2)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+
+    // They are values like any other function
+    assert_eq!(eval_str("(apply inc [41])", &env).unwrap(), Value::Number(42.0));
+    assert_eq!(eval_str("((fn [f x] (f (f x))) dec 5)", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(let [step inc] (step 9))", &env).unwrap(), Value::Number(10.0));
+}
+
+#[test]
+fn test_abs() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(abs -3)", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(abs 2.5)", &env).unwrap(), Value::Number(2.5));
+    assert_eq!(eval_str("(abs 0)", &env).unwrap(), Value::Number(0.0));
+    assert!(matches!(eval_str("(abs \"3\")", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_min_and_max() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(min 3 1 2)", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(max 3 1 2)", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(min -3 1 -5)", &env).unwrap(), Value::Number(-5.0));
+    assert_eq!(eval_str("(max -3 -1 -5)", &env).unwrap(), Value::Number(-1.0));
+    assert_eq!(eval_str("(max 1 2.5 2)", &env).unwrap(), Value::Number(2.5));
+    assert_eq!(eval_str("(min 7)", &env).unwrap(), Value::Number(7.0));
+    assert!(matches!(eval_str("(max)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+
+    match eval_str("(min 1 2 :three)", &env) {
+        Err(EvalError::TypeError { expected, .. }) => assert_eq!(expected, "number at argument 3"),
+        other => panic!("expected a type error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_min_and_max_with_nan() {
+    let env = standard_env();
+    let max = env.borrow().get("max").unwrap();
+    let min = env.borrow().get("min").unwrap();
+    let numbers = |ns: &[f64]| ns.iter().map(|n| Value::Number(*n)).collect::<Vec<_>>();
+
+    // NaN never wins a comparison, so a later argument replaces it
+    assert_eq!(reader::call(&max, numbers(&[1.0, f64::NAN, 2.0]), &env).unwrap(), Value::Number(2.0));
+    assert_eq!(reader::call(&min, numbers(&[1.0, f64::NAN, 2.0]), &env).unwrap(), Value::Number(2.0));
+    match reader::call(&max, numbers(&[1.0, f64::NAN]), &env).unwrap() {
+        Value::Number(n) => assert!(n.is_nan()),
+        other => panic!("expected a number, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sqrt_and_pow() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(sqrt 16)", &env).unwrap(), Value::Number(4.0));
+    assert_eq!(eval_str("(sqrt 2)", &env).unwrap(), Value::Number(2f64.sqrt()));
+    match eval_str("(sqrt -1)", &env).unwrap() {
+        Value::Number(n) => assert!(n.is_nan()),
+        other => panic!("expected NaN, got {:?}", other),
+    }
+
+    assert_eq!(eval_str("(pow 2 10)", &env).unwrap(), Value::Number(1024.0));
+    assert_eq!(eval_str("(pow 4 0.5)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(pow 2 -1)", &env).unwrap(), Value::Number(0.5));
+    assert!(matches!(eval_str("(pow 2)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+    assert!(matches!(eval_str("(sqrt :a)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_rounding() {
+    let env = standard_env();
+
+    // Each row is an argument and the expected floor, ceil, round and trunc
+    let table = [
+        ("2.3", [2.0, 3.0, 2.0, 2.0]),
+        ("2.5", [2.0, 3.0, 3.0, 2.0]),
+        ("2.7", [2.0, 3.0, 3.0, 2.0]),
+        ("-2.5", [-3.0, -2.0, -3.0, -2.0]),
+        ("-2.3", [-3.0, -2.0, -2.0, -2.0]),
+        ("0.5", [0.0, 1.0, 1.0, 0.0]),
+        ("4", [4.0, 4.0, 4.0, 4.0]),
+        ("100000000000000000000.5", [1e20, 1e20, 1e20, 1e20]),
+    ];
+
+    for (arg, expected) in table {
+        for (op, expected) in ["floor", "ceil", "round", "trunc"].iter().zip(expected) {
+            let source = format!("({} {})", op, arg);
+            assert_eq!(eval_str(&source, &env).unwrap(), Value::Number(expected), "{}", source);
+        }
+    }
+
+    assert!(matches!(eval_str("(round \"2.5\")", &env), Err(EvalError::TypeError { .. })));
+}
+
+/// Evaluates a form the given number of times, collecting the results
+fn sample(source: &str, times: usize, env: &Rc<RefCell<Environment>>) -> Vec<Value> {
+    (0..times).map(|_| eval_str(source, env).unwrap()).collect()
+}
+
+#[test]
+fn test_seeded_random_numbers_repeat() {
+    let env = standard_env();
+
+    env.borrow().context().seed_rng(42);
+    let first = sample("(rand)", 20, &env);
+    env.borrow().context().seed_rng(42);
+    assert_eq!(sample("(rand)", 20, &env), first);
+
+    // Seeding from Citrine does the same
+    eval_str("(set-rand-seed! 42)", &env).unwrap();
+    assert_eq!(sample("(rand)", 20, &env), first);
+
+    eval_str("(set-rand-seed! 43)", &env).unwrap();
+    assert_ne!(sample("(rand)", 20, &env), first);
+
+    // Each interpreter has its own generator
+    let other = standard_env();
+    env.borrow().context().seed_rng(7);
+    other.borrow().context().seed_rng(7);
+    let from_env = sample("(rand-int 1000)", 5, &env);
+    let from_other = sample("(rand-int 1000)", 5, &other);
+    assert_eq!(from_env, from_other);
+
+    assert!(eval_str("(set-rand-seed! 1.5)", &env).is_err());
+}
+
+#[test]
+fn test_random_numbers_are_in_range() {
+    let env = standard_env();
+
+    for value in sample("(rand)", 1000, &env) {
+        assert!(matches!(value, Value::Number(n) if (0.0..1.0).contains(&n)), "{:?}", value);
+    }
+    for value in sample("(rand 10)", 1000, &env) {
+        assert!(matches!(value, Value::Number(n) if (0.0..10.0).contains(&n)), "{:?}", value);
+    }
+
+    // rand-int covers the whole range, and nothing outside it
+    let mut seen = [false; 5];
+    for value in sample("(rand-int 5)", 1000, &env) {
+        match value {
+            Value::Number(n) if n.fract() == 0.0 && (0.0..5.0).contains(&n) => seen[n as usize] = true,
+            other => panic!("expected an integer in [0, 5), got {:?}", other),
+        }
+    }
+    assert!(seen.iter().all(|seen| *seen));
+
+    // The bound must be a positive integer, or [0, n) would be empty or not
+    // hold whole numbers
+    for source in ["(rand-int 0)", "(rand-int -5)", "(rand-int 2.5)", "(rand-int nil)"] {
+        assert!(matches!(eval_str(source, &env), Err(EvalError::TypeError { .. })), "{}", source);
+    }
+    assert_eq!(eval_str("(rand-int 1)", &env).unwrap(), Value::Number(0.0));
+}
+
+#[test]
+fn test_rand_nth() {
+    let env = standard_env();
+
+    for value in sample("(rand-nth [:a :b :c])", 100, &env) {
+        assert!(
+            ["a", "b", "c"].iter().any(|k| value == Value::Keyword(k.to_string())),
+            "{:?}",
+            value
+        );
+    }
+    assert_eq!(eval_str("(rand-nth '(1))", &env).unwrap(), Value::Number(1.0));
+    assert!(matches!(eval_str("(rand-nth [])", &env), Err(EvalError::IndexOutOfBounds { .. })));
+    assert!(matches!(eval_str("(rand-nth 5)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_numeric_predicates() {
+    let env = standard_env();
+
+    // Each row is an argument and whether even?, odd?, zero?, pos? and neg? hold for it
+    let table = [
+        ("0", [true, false, true, false, false]),
+        ("1", [false, true, false, true, false]),
+        ("2", [true, false, false, true, false]),
+        ("-3", [false, true, false, false, true]),
+        ("-4", [true, false, false, false, true]),
+        ("0.0", [true, false, true, false, false]),
+    ];
+
+    for (arg, expected) in table {
+        for (op, expected) in ["even?", "odd?", "zero?", "pos?", "neg?"].iter().zip(expected) {
+            let source = format!("({} {})", op, arg);
+            assert_eq!(eval_str(&source, &env).unwrap(), Value::Boolean(expected), "{}", source);
+        }
+    }
+
+    // zero?, pos? and neg? take any number
+    assert_eq!(eval_str("(pos? 0.5)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(neg? -0.5)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(zero? 0.5)", &env).unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn test_numeric_predicate_errors() {
+    let env = standard_env();
+
+    assert!(matches!(eval_str("(even? 2.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(odd? 2.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(zero? nil)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(pos? 1 2)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+
+    // They are function values, to pass to other functions
+    assert_eq!(eval_str("(apply even? [4])", &env).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn test_count_and_empty() {
+    let env = standard_env();
+
+    // Each row is a value, its count and whether it is empty
+    let table = [
+        ("nil", 0.0, true),
+        ("'()", 0.0, true),
+        ("'(1 2)", 2.0, false),
+        ("[]", 0.0, true),
+        ("[1 2 3]", 3.0, false),
+        ("{}", 0.0, true),
+        ("{:a 1 :b 2}", 2.0, false),
+        ("#{}", 0.0, true),
+        ("#{1 2 3 4}", 4.0, false),
+        ("\"\"", 0.0, true),
+        ("\"hello\"", 5.0, false),
+        // Characters are counted, not bytes
+        ("\"héllo wörld\"", 11.0, false),
+        ("\"日本\"", 2.0, false),
+    ];
+
+    for (value, count, empty) in table {
+        let source = format!("(count {})", value);
+        assert_eq!(eval_str(&source, &env).unwrap(), Value::Number(count), "{}", source);
+        let source = format!("(empty? {})", value);
+        assert_eq!(eval_str(&source, &env).unwrap(), Value::Boolean(empty), "{}", source);
+    }
+
+    for source in ["(count 1)", "(count :a)", "(empty? 0)", "(empty? 'a)"] {
+        assert!(matches!(eval_str(source, &env), Err(EvalError::TypeError { .. })), "{}", source);
+    }
+}
+
+#[test]
+fn test_conj() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(conj [1 2] 3)", "[1 2 3]"),
+        ("(conj [1 2] 3 4 5)", "[1 2 3 4 5]"),
+        ("(conj '(2 3) 1)", "'(1 2 3)"),
+        ("(conj '(3) 2 1)", "'(1 2 3)"),
+        ("(conj #{1} 2 1 3)", "#{1 2 3}"),
+        ("(conj nil 1)", "'(1)"),
+        ("(conj nil 1 2)", "'(2 1)"),
+        ("(conj {:a 1} [:b 2])", "{:a 1 :b 2}"),
+        ("(conj {:a 1} [:a 3] [:c 4])", "{:a 3 :c 4}"),
+        ("(conj {:a 1} {:b 2 :c 3})", "{:a 1 :b 2 :c 3}"),
+        ("(conj [1])", "[1]"),
+    ]);
+
+    // The collection is not modified
+    eval_str("(def v [1 2])", &env).unwrap();
+    eval_str("(conj v 3)", &env).unwrap();
+    assert_eq!(eval_str("v", &env).unwrap(), eval_str("[1 2]", &env).unwrap());
+}
+
+#[test]
+fn test_conj_errors() {
+    let env = standard_env();
+
+    let sources = [
+        "(conj {:a 1} :b)",
+        "(conj {:a 1} [:b])",
+        "(conj {:a 1} [:b 2 3])",
+        "(conj 1 2)",
+        "(conj \"ab\" \"c\")",
+    ];
+    for source in sources {
+        assert!(matches!(eval_str(source, &env), Err(EvalError::TypeError { .. })), "{}", source);
+    }
+    assert!(matches!(eval_str("(conj)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_nth() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(nth [10 20 30] 0)", &env).unwrap(), Value::Number(10.0));
+    assert_eq!(eval_str("(nth [10 20 30] 2)", &env).unwrap(), Value::Number(30.0));
+    assert_eq!(eval_str("(nth '(10 20 30) 1)", &env).unwrap(), Value::Number(20.0));
+    assert_eq!(eval_str("(nth \"héllo\" 1)", &env).unwrap(), Value::String("é".to_string()));
+
+    // With a default, out of bounds indices return it
+    assert_eq!(eval_str("(nth [10 20] 5 :none)", &env).unwrap(), Value::Keyword("none".to_string()));
+    assert_eq!(eval_str("(nth [10 20] -1 :none)", &env).unwrap(), Value::Keyword("none".to_string()));
+    assert_eq!(eval_str("(nth nil 0 :none)", &env).unwrap(), Value::Keyword("none".to_string()));
+    assert_eq!(eval_str("(nth [10 20] 1 :none)", &env).unwrap(), Value::Number(20.0));
+}
+
+#[test]
+fn test_nth_errors() {
+    let env = standard_env();
+
+    assert!(matches!(
+        eval_str("(nth [10 20 30] 3)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 3, length: 3 })
+    ));
+    assert!(matches!(
+        eval_str("(nth '(10 20 30) -1)", &env),
+        Err(EvalError::IndexOutOfBounds { index: -1, length: 3 })
+    ));
+    assert!(matches!(
+        eval_str("(nth \"ab\" 2)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 2, length: 2 })
+    ));
+    assert!(matches!(eval_str("(nth [1] 0.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(nth #{1} 0)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(nth [1])", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_second_last_and_butlast() {
+    let env = standard_env();
+
+    // Each row is an argument and the expected second, last and butlast
+    let table = [
+        ("[1 2 3]", ["2", "3", "'(1 2)"]),
+        ("'(1 2 3)", ["2", "3", "'(1 2)"]),
+        ("\"abc\"", ["\"b\"", "\"c\"", "'(\"a\" \"b\")"]),
+        ("[1]", ["nil", "1", "nil"]),
+        ("[]", ["nil", "nil", "nil"]),
+        ("nil", ["nil", "nil", "nil"]),
+    ];
+
+    for (arg, expected) in table {
+        for (op, expected) in ["second", "last", "butlast"].iter().zip(expected) {
+            let source = format!("({} {})", op, arg);
+            assert_eq!(eval_str(&source, &env).unwrap(), eval_str(expected, &env).unwrap(), "{}", source);
+        }
+    }
+
+    assert!(matches!(eval_str("(last 5)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_reverse() {
+    let env = standard_env();
+
+    // Each row is an argument and the list it reverses to
+    let table = [
+        ("'(1 2 3)", "'(3 2 1)"),
+        ("[1 2 3]", "'(3 2 1)"),
+        ("\"abc\"", "'(\"c\" \"b\" \"a\")"),
+        ("[1]", "'(1)"),
+        ("[]", "'()"),
+        ("'()", "'()"),
+        ("\"\"", "'()"),
+        ("nil", "'()"),
+        ("{:a 1}", "'([:a 1])"),
+    ];
+
+    for (arg, expected) in table {
+        let source = format!("(reverse {})", arg);
+        assert_eq!(eval_str(&source, &env).unwrap(), eval_str(expected, &env).unwrap(), "{}", source);
+    }
+
+    // Sets come out in some order, but with all their elements
+    assert_eq!(eval_str("(count (reverse #{1 2 3}))", &env).unwrap(), Value::Number(3.0));
+    assert!(matches!(eval_str("(reverse 1)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_take_and_drop() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(take 2 [1 2 3])", "'(1 2)"),
+        ("(take 5 '(1 2 3))", "'(1 2 3)"),
+        ("(take 0 [1 2 3])", "'()"),
+        ("(take -1 [1 2 3])", "'()"),
+        ("(take 2 \"abc\")", "'(\"a\" \"b\")"),
+        ("(take 2 nil)", "'()"),
+        ("(drop 2 [1 2 3])", "'(3)"),
+        ("(drop 5 '(1 2 3))", "'()"),
+        ("(drop 0 [1 2 3])", "'(1 2 3)"),
+        ("(drop -1 [1 2 3])", "'(1 2 3)"),
+    ]);
+
+    assert!(matches!(eval_str("(take :a [1])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(drop 1 2)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(take 1)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_take_while_and_drop_while() {
+    let env = standard_env();
+    eval_str("(defn small? [n] (< n 3))", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(take-while small? [1 2 3 1])", "'(1 2)"),
+        ("(drop-while small? [1 2 3 1])", "'(3 1)"),
+        ("(take-while even? '(2 4 5 6))", "'(2 4)"),
+        ("(drop-while even? '(2 4 5 6))", "'(5 6)"),
+        ("(take-while (fn [x] (> x 10)) [1 2])", "'()"),
+        ("(drop-while (fn [x] (> x 10)) [1 2])", "'(1 2)"),
+        ("(take-while :active [{:active true :n 1} {:active true :n 2} {:n 3}])",
+            "'({:active true :n 1} {:active true :n 2})"),
+        ("(drop-while :active [{:active true} {:n 3} {:active true}])", "'({:n 3} {:active true})"),
+        ("(take-while even? [])", "'()"),
+    ]);
+}
+
+#[test]
+fn test_take_while_stops_calling_the_predicate() {
+    let env = standard_env();
+    eval_str("(def calls 0)", &env).unwrap();
+    eval_str("(defn counted-small? [n] (set! calls (inc calls)) (< n 3))", &env).unwrap();
+
+    eval_str("(take-while counted-small? [1 2 3 4 5])", &env).unwrap();
+    assert_eq!(eval_str("calls", &env).unwrap(), Value::Number(3.0));
+
+    eval_str("(set! calls 0)", &env).unwrap();
+    eval_str("(drop-while counted-small? [1 2 3 4 5])", &env).unwrap();
+    assert_eq!(eval_str("calls", &env).unwrap(), Value::Number(3.0));
+
+    // Errors raised by the predicate are returned
+    assert!(matches!(eval_str("(take-while (fn [x] (throw :bad)) [1])", &env), Err(EvalError::Thrown(_))));
+}
+
+#[test]
+fn test_map() {
+    let env = standard_env();
+    eval_str("(defn square [x] (* x x))", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(map inc [1 2 3])", "'(2 3 4)"),
+        ("(map square '(1 2 3))", "'(1 4 9)"),
+        ("(map (fn [x] [x x]) [1 2])", "'([1 1] [2 2])"),
+        ("(map + [1 2] [10 20 30])", "'(11 22)"),
+        ("(map + [1 2 3] [10 20] [100 200 300])", "'(111 222)"),
+        ("(map :name [{:name \"a\"} {:name \"b\"} {}])", "'(\"a\" \"b\" nil)"),
+        ("(map inc [])", "'()"),
+        ("(map inc nil)", "'()"),
+        ("(map even? [1 2])", "'(false true)"),
+    ]);
+}
+
+#[test]
+fn test_map_errors() {
+    let env = standard_env();
+    eval_str("(def calls 0)", &env).unwrap();
+    eval_str("(defn fail-on-two [x] (set! calls (inc calls)) (if (= x 2) (throw :two) x))", &env).unwrap();
+
+    // The first error aborts the map
+    assert!(matches!(
+        &eval_str("(map fail-on-two [1 2 3])", &env),
+        Err(EvalError::Thrown(Value::Keyword(k))) if k == "two"
+    ));
+    assert_eq!(eval_str("calls", &env).unwrap(), Value::Number(2.0));
+
+    assert!(matches!(eval_str("(map inc [1 :a])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(map inc 5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(map inc)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_filter_and_remove() {
+    let env = standard_env();
+    eval_str("(defn small? [n] (< n 3))", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(filter even? [1 2 3 4])", "'(2 4)"),
+        ("(remove even? [1 2 3 4])", "'(1 3)"),
+        ("(filter small? '(1 5 2 6))", "'(1 2)"),
+        ("(remove small? '(1 5 2 6))", "'(5 6)"),
+        ("(filter (fn [x] (> x 10)) [1 2])", "'()"),
+        ("(filter :admin [{:admin true :n 1} {:n 2} {:admin nil :n 3}])", "'({:admin true :n 1})"),
+        ("(filter #{:a :b} [:a :c :b :d])", "'(:a :b)"),
+        ("(remove #{:a :b} [:a :c :b :d])", "'(:c :d)"),
+        ("(filter even? nil)", "'()"),
+        ("(filter (fn [c] (= c \"a\")) \"banana\")", "'(\"a\" \"a\" \"a\")"),
+        // Maps are filtered as [key value] entries
+        ("(filter (fn [[k v]] (= v 2)) {:a 1 :b 2})", "'([:b 2])"),
+    ]);
+
+    // Errors raised by the predicate are returned
+    assert!(matches!(eval_str("(filter even? [2 2.5])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(remove (fn [x] (throw x)) [:bad])", &env), Err(EvalError::Thrown(_))));
+    assert!(matches!(eval_str("(filter even? 4)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_reduce() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(reduce + [1 2 3 4])", "10"),
+        ("(reduce + 100 [1 2 3 4])", "110"),
+        ("(reduce (fn [acc x] (conj acc (* x x))) [] '(1 2 3))", "[1 4 9]"),
+        ("(reduce (fn [m x] (conj m [x (inc x)])) {} [1 2])", "{1 2 2 3}"),
+        ("(reduce max [3 9 2])", "9"),
+        // An empty collection calls f with no arguments, or returns the initial value
+        ("(reduce + [])", "0"),
+        ("(reduce + nil)", "0"),
+        ("(reduce + 5 [])", "5"),
+        // A single element is returned as is
+        ("(reduce (fn [a b] (throw :called)) [7])", "7"),
+    ]);
+
+    assert!(matches!(eval_str("(reduce max [])", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+    assert!(matches!(eval_str("(reduce + [1 :a])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(reduce +)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_reduced_ends_reduce() {
+    let env = standard_env();
+    eval_str("(def calls 0)", &env).unwrap();
+    eval_str(
+        "(defn sum-to-ten [acc x] (set! calls (inc calls)) (if (>= (+ acc x) 10) (reduced (+ acc x)) (+ acc x)))",
+        &env,
+    )
+    .unwrap();
+
+    assert_eq!(eval_str("(reduce sum-to-ten [1 2 3 4 5 6 7])", &env).unwrap(), Value::Number(10.0));
+    assert_eq!(eval_str("calls", &env).unwrap(), Value::Number(3.0));
+
+    assert_eq!(eval_str("(reduced? (reduced 1))", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(reduced? 1)", &env).unwrap(), Value::Boolean(false));
+    assert_eq!(eval_str("(deref (reduced 1))", &env).unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_reduce_large_vector() {
+    let env = standard_env();
+    let numbers = (0..1_000_000).map(|n| Value::Number(n as f64)).collect();
+    env.borrow_mut().set("numbers".to_string(), Value::Vector(numbers));
+
+    assert_eq!(eval_str("(reduce + numbers)", &env).unwrap(), Value::Number(499_999_500_000.0));
+    assert_eq!(
+        eval_str("(reduce (fn [acc n] (+ acc n)) 0 numbers)", &env).unwrap(),
+        Value::Number(499_999_500_000.0)
+    );
+}
+
+#[test]
+fn test_some_every_and_not_any() {
+    let env = standard_env();
+    eval_str("(defn small? [n] (< n 3))", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(some even? [1 3 4 5])", "true"),
+        ("(some even? [1 3 5])", "nil"),
+        ("(some (fn [x] (if (> x 1) (* x 10))) [1 2 3])", "20"),
+        ("(some #{:b :c} [:a :c :b])", ":c"),
+        ("(some #{:z} [:a :b])", "nil"),
+        ("(some :id [{:n 1} {:id 7} {:id 8}])", "7"),
+        ("(some even? [])", "nil"),
+        ("(every? small? [1 2])", "true"),
+        ("(every? small? [1 2 3])", "false"),
+        ("(every? :id [{:id 1} {:id 2}])", "true"),
+        ("(every? even? [])", "true"),
+        ("(not-any? small? [3 4])", "true"),
+        ("(not-any? small? [3 1])", "false"),
+        ("(not-any? #{:a} [:b :c])", "true"),
+        ("(not-any? even? nil)", "true"),
+    ]);
+}
+
+#[test]
+fn test_some_every_and_not_any_stop_early() {
+    let env = standard_env();
+    eval_str("(def calls 0)", &env).unwrap();
+    eval_str("(defn counted-even? [n] (set! calls (inc calls)) (even? n))", &env).unwrap();
+
+    // Each row is a form and how many times it calls the predicate
+    let table = [
+        ("(some counted-even? [1 2 3 4])", 2.0),
+        ("(every? counted-even? [2 3 4 6])", 2.0),
+        ("(not-any? counted-even? [1 4 5 6])", 2.0),
+        ("(every? counted-even? [2 4 6])", 3.0),
+    ];
+
+    for (source, calls) in table {
+        eval_str("(set! calls 0)", &env).unwrap();
+        eval_str(source, &env).unwrap();
+        assert_eq!(eval_str("calls", &env).unwrap(), Value::Number(calls), "{}", source);
+    }
+}
+
+#[test]
+fn test_sort() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(sort [3 1 2])", "'(1 2 3)"),
+        ("(sort (list 2.5 -1 0))", "(list -1 0 2.5)"),
+        ("(sort [\"pear\" \"apple\" \"fig\"])", "'(\"apple\" \"fig\" \"pear\")"),
+        ("(sort [:b :a :c])", "'(:a :b :c)"),
+        ("(sort [2 nil 1])", "'(nil 1 2)"),
+        ("(sort [[1 2] [1] [0 5]])", "'([1] [0 5] [1 2])"),
+        ("(sort [])", "'()"),
+        ("(sort nil)", "'()"),
+        // Comparators may return a number or a boolean
+        ("(sort (fn [a b] (- b a)) [3 1 2])", "'(3 2 1)"),
+        ("(sort > [3 1 2])", "'(3 2 1)"),
+        ("(sort < [3 1 2 1])", "'(1 1 2 3)"),
+    ]);
+}
+
+#[test]
+fn test_sort_by() {
+    let env = standard_env();
+    eval_str(
+        "(def people [{:name \"ann\" :age 40} {:name \"bob\" :age 25} {:name \"cy\" :age 40} {:name \"di\" :age 31}])",
+        &env,
+    )
+    .unwrap();
+
+    // Equal keys keep their order
+    assert_eq!(
+        eval_str("(map :name (sort-by :age people))", &env).unwrap(),
+        eval_str("'(\"bob\" \"di\" \"ann\" \"cy\")", &env).unwrap()
+    );
+    assert_eq!(
+        eval_str("(map :name (sort-by :age > people))", &env).unwrap(),
+        eval_str("'(\"ann\" \"cy\" \"di\" \"bob\")", &env).unwrap()
+    );
+    assert_eq!(
+        eval_str("(sort-by (fn [s] (count s)) [\"ccc\" \"a\" \"bb\"])", &env).unwrap(),
+        eval_str("'(\"a\" \"bb\" \"ccc\")", &env).unwrap()
+    );
+}
+
+#[test]
+fn test_sort_errors() {
+    let env = standard_env();
+
+    assert!(matches!(eval_str("(sort [1 \"a\" 2])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(sort ['(1) '(2)])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(sort-by :k [{:k 1} {:k :a}])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(
+        &eval_str("(sort (fn [a b] (throw :cmp)) [2 1])", &env),
+        Err(EvalError::Thrown(Value::Keyword(k))) if k == "cmp"
+    ));
+    assert!(matches!(eval_str("(sort 5)", &env), Err(EvalError::TypeError { .. })));
+
+    // An inconsistent comparator gives some order rather than a panic
+    assert_eq!(eval_str("(count (sort (fn [a b] true) [5 3 8 1 9 2 7]))", &env).unwrap(), Value::Number(7.0));
+}
+
+#[test]
+fn test_function_builders() {
+    let env = standard_env();
+    eval_str("(defn add3 [a b c] (+ a b c))", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("((partial + 1 2) 3)", "6"),
+        ("((partial add3 1) 2 3)", "6"),
+        ("((partial list))", "'()"),
+        ("((comp str inc) 4)", "\"5\""),
+        ("((comp list inc) 4)", "'(5)"),
+        ("((comp inc inc *) 2 3)", "8"),
+        ("((comp) 7)", "7"),
+        ("((comp :a :b) {:b {:a 1}})", "1"),
+        ("((constantly 42))", "42"),
+        ("((constantly 42) 1 2 3)", "42"),
+        ("(map (constantly :x) [1 2])", "'(:x :x)"),
+        ("(identity [1 2])", "[1 2]"),
+        ("(map (complement even?) [1 2 3])", "'(true false true)"),
+        ("((complement :done) {:done 1})", "false"),
+        ("(filter (complement #{2}) [1 2 3])", "'(1 3)"),
+    ]);
+}
+
+#[test]
+fn test_str() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(str)", &env).unwrap(), Value::String(String::new()));
+    assert_eq!(eval_str("(str \"héllo\")", &env).unwrap(), Value::String("héllo".to_string()));
+    assert_eq!(eval_str("(str \"n=\" 5 \", \" 2.5)", &env).unwrap(), Value::String("n=5, 2.5".to_string()));
+    assert_eq!(eval_str("(str :k nil 'sym)", &env).unwrap(), Value::String(":ksym".to_string()));
+    assert_eq!(eval_str("(str [1 \"a\"])", &env).unwrap(), Value::String("[1 \"a\"]".to_string()));
+}
+
+#[test]
+fn test_function_builder_errors() {
+    let env = standard_env();
+
+    // Errors come from the call of the built function
+    assert!(matches!(eval_str("((partial + 1) :a)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("((comp inc) :a)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("((complement even?) 1.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("((comp) 1 2)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+    assert!(matches!(eval_str("(partial)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_get() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(get {:a 1 :b 2} :a)", "1"),
+        ("(get {:a 1} :z)", "nil"),
+        ("(get {:a 1} :z :none)", ":none"),
+        ("(get {:a nil} :a :none)", "nil"),
+        ("(get {\"k\" [1]} \"k\")", "[1]"),
+        ("(get [10 20 30] 1)", "20"),
+        ("(get [10 20 30] 3)", "nil"),
+        ("(get [10 20 30] -1 :none)", ":none"),
+        ("(get [10 20 30] :a :none)", ":none"),
+        ("(get #{:a :b} :a)", ":a"),
+        ("(get #{:a :b} :c :none)", ":none"),
+        ("(get \"héllo\" 1)", "\"é\""),
+        ("(get \"abc\" 5 :none)", ":none"),
+        ("(get nil :a)", "nil"),
+        ("(get nil :a :none)", ":none"),
+        ("(get 42 :a :none)", ":none"),
+    ]);
+    assert!(matches!(eval_str("(get {})", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_get_in() {
+    let env = standard_env();
+    eval_str("(def config {:servers [{:host \"a\" :ports [80 443]} {:host \"b\"}] :debug false})", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(get-in config [:servers 0 :host])", "\"a\""),
+        ("(get-in config [:servers 0 :ports 1])", "443"),
+        ("(get-in config '(:servers 1 :host))", "\"b\""),
+        ("(get-in config [:debug])", "false"),
+        ("(get-in config [])", "config"),
+        // Paths that dead-end give the default
+        ("(get-in config [:servers 1 :ports 0])", "nil"),
+        ("(get-in config [:servers 1 :ports 0] 8080)", "8080"),
+        ("(get-in config [:servers 5 :host] \"none\")", "\"none\""),
+        ("(get-in config [:debug :level] :none)", ":none"),
+        ("(get-in nil [:a :b] :none)", ":none"),
+    ]);
+    assert!(matches!(eval_str("(get-in config :servers)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_assoc() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(assoc {:a 1} :b 2)", "{:a 1 :b 2}"),
+        ("(assoc {:a 1} :a 3)", "{:a 3}"),
+        ("(assoc {} :a 1 :b 2 :c 3)", "{:a 1 :b 2 :c 3}"),
+        ("(assoc nil :a 1)", "{:a 1}"),
+        ("(assoc [1 2 3] 0 :x)", "[:x 2 3]"),
+        ("(assoc [1 2 3] 3 4)", "[1 2 3 4]"),
+        ("(assoc [] 0 :x 1 :y)", "[:x :y]"),
+    ]);
+
+    // The original is not changed
+    eval_str("(def m {:a 1})", &env).unwrap();
+    eval_str("(assoc m :a 2)", &env).unwrap();
+    assert_eq!(eval_str("m", &env).unwrap(), eval_str("{:a 1}", &env).unwrap());
+}
+
+#[test]
+fn test_assoc_errors() {
+    let env = standard_env();
+
+    assert!(matches!(
+        eval_str("(assoc [1 2 3] 4 :x)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 4, length: 3 })
+    ));
+    assert!(matches!(
+        eval_str("(assoc [1 2 3] -1 :x)", &env),
+        Err(EvalError::IndexOutOfBounds { index: -1, length: 3 })
+    ));
+    assert!(matches!(eval_str("(assoc [1] :a 2)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(assoc '(1) 0 2)", &env), Err(EvalError::TypeError { .. })));
+    assert!(eval_str("(assoc {} :a 1 :b)", &env).is_err());
+    assert!(matches!(eval_str("(assoc {} :a)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_update() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(update {:n 1} :n inc)", "{:n 2}"),
+        ("(update {:n 1} :n + 10 100)", "{:n 111}"),
+        ("(update {} :n (fn [n] (if n (inc n) 0)))", "{:n 0}"),
+        ("(update [1 2 3] 1 * 10)", "[1 20 3]"),
+        ("(update {:xs [1]} :xs conj 2 3)", "{:xs [1 2 3]}"),
+    ]);
+    assert!(matches!(eval_str("(update {} :n inc)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_assoc_in_and_update_in() {
+    let env = standard_env();
+    eval_str("(def db {:users [{:name \"ann\" :visits 1}]})", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(assoc-in db [:users 0 :name] \"bo\")", "{:users [{:name \"bo\" :visits 1}]}"),
+        ("(assoc-in {} [:a :b :c] 1)", "{:a {:b {:c 1}}}"),
+        ("(assoc-in [] [0 :a] 1)", "[{:a 1}]"),
+        ("(update-in db [:users 0 :visits] inc)", "{:users [{:name \"ann\" :visits 2}]}"),
+        ("(update-in db [:users 0 :visits] + 5)", "{:users [{:name \"ann\" :visits 6}]}"),
+        ("(update-in {} [:counts :a] (fn [n] (if n (inc n) 1)))", "{:counts {:a 1}}"),
+        ("(update-in {:a 1} [] (fn [m] (conj m [:b 2])))", "{:a 1 :b 2}"),
+    ]);
+
+    assert!(matches!(
+        eval_str("(assoc-in db [:users 5 :name] \"x\")", &env),
+        Err(EvalError::IndexOutOfBounds { index: 5, length: 1 })
+    ));
+    assert!(matches!(eval_str("(assoc-in {} :a 1)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_dissoc() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(dissoc {:a 1 :b 2} :a)", "{:b 2}"),
+        ("(dissoc {:a 1 :b 2 :c 3} :a :c)", "{:b 2}"),
+        ("(dissoc {:a 1} :z)", "{:a 1}"),
+        ("(dissoc {:a 1 :b 2} :a :b)", "{}"),
+        ("(dissoc {:a 1})", "{:a 1}"),
+        ("(dissoc {} :a)", "{}"),
+        ("(dissoc nil :a)", "nil"),
+    ]);
+
+    assert!(matches!(eval_str("(dissoc [1 2] 0)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(dissoc '(1 2) 0)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(dissoc)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_keys_and_vals() {
+    let env = standard_env();
+    eval_str("(def m {:a 1 :b 2 :c 3})", &env).unwrap();
+
+    assert_evals(&env, &[
+        ("(sort (keys {:a 1 :b 2 :c 3}))", "'(:a :b :c)"),
+        ("(sort (vals {:a 1 :b 2 :c 3}))", "'(1 2 3)"),
+        ("(keys {})", "nil"),
+        ("(vals {})", "nil"),
+        ("(keys nil)", "nil"),
+        // keys and vals list the entries in the same order
+        ("(= (map (fn [k] (get m k)) (keys m)) (vals m))", "true"),
+    ]);
+
+    assert!(matches!(eval_str("(keys [1 2])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(vals '(1 2))", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_contains() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(contains? {:a 1} :a)", "true"),
+        ("(contains? {:a nil} :a)", "true"),
+        ("(contains? {:a 1} :b)", "false"),
+        ("(contains? #{1 2} 2)", "true"),
+        ("(contains? #{1 2} 3)", "false"),
+        // Vectors are checked by index, not by value
+        ("(contains? [:a :b] 1)", "true"),
+        ("(contains? [:a :b] 2)", "false"),
+        ("(contains? [:a :b] :a)", "false"),
+        ("(contains? [10 20] 10)", "false"),
+        ("(contains? \"abc\" 2)", "true"),
+        ("(contains? nil :a)", "false"),
+    ]);
+
+    assert!(matches!(eval_str("(contains? '(1 2) 0)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(contains? {:a 1})", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_find() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(find {:a 1 :b 2} :a)", "[:a 1]"),
+        ("(find {:a nil} :a)", "[:a nil]"),
+        ("(find {:a 1} :b)", "nil"),
+        ("(find [:x :y] 1)", "[1 :y]"),
+        ("(find nil :a)", "nil"),
+    ]);
+
+    assert!(matches!(eval_str("(find #{1} 1)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_merge() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(merge {:a 1} {:b 2})", "{:a 1 :b 2}"),
+        ("(merge {:a 1 :b 1} {:b 2} {:b 3 :c 3})", "{:a 1 :b 3 :c 3}"),
+        ("(merge {:a 1} nil {:a 2})", "{:a 2}"),
+        ("(merge nil {:a 1})", "{:a 1}"),
+        ("(merge {:a 1})", "{:a 1}"),
+        ("(merge)", "nil"),
+        ("(merge nil nil)", "nil"),
+    ]);
+
+    assert!(matches!(eval_str("(merge {:a 1} [1 2])", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_merge_with() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(merge-with + {:a 1 :b 2} {:a 10 :c 3})", "{:a 11 :b 2 :c 3}"),
+        ("(merge-with + {:a 1} {:a 10} {:a 100})", "{:a 111}"),
+        ("(merge-with - {:a 10} {:a 1})", "{:a 9}"),
+        ("(merge-with conj {:xs [1]} {:xs 2})", "{:xs [1 2]}"),
+        ("(merge-with + {:a 1} nil)", "{:a 1}"),
+        ("(merge-with +)", "nil"),
+    ]);
+
+    // Errors from the combining function are passed on
+    let result = eval_str("(merge-with (fn [a b] (throw \"clash\")) {:a 1} {:a 2})", &env);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("clash"));
+
+    // The function is only called for keys in more than one map
+    assert_eq!(
+        eval_str("(merge-with (fn [a b] (throw \"clash\")) {:a 1} {:b 2})", &env).unwrap(),
+        eval_str("{:a 1 :b 2}", &env).unwrap()
+    );
+}
+
+#[test]
+fn test_select_keys() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(select-keys {:a 1 :b 2 :c 3} [:a :c])", "{:a 1 :c 3}"),
+        ("(select-keys {:a 1 :b 2} [:a :z])", "{:a 1}"),
+        ("(select-keys {:a nil} [:a])", "{:a nil}"),
+        ("(select-keys {:a 1} [])", "{}"),
+        ("(select-keys {:a 1} nil)", "{}"),
+        ("(select-keys nil [:a])", "{}"),
+        ("(select-keys {\"host\" \"x\" \"port\" 80} '(\"port\"))", "{\"port\" 80}"),
+    ]);
+
+    assert!(matches!(eval_str("(select-keys [1 2] [0])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(select-keys {:a 1} :a)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_zipmap() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(zipmap [:a :b] [1 2])", "{:a 1 :b 2}"),
+        ("(zipmap [:a :b :c] [1 2])", "{:a 1 :b 2}"),
+        ("(zipmap [:a] '(1 2 3))", "{:a 1}"),
+        ("(zipmap [:a :b :a] [1 2 3])", "{:a 3 :b 2}"),
+        ("(zipmap [] [1 2])", "{}"),
+        ("(zipmap [:a] [])", "{}"),
+        ("(zipmap nil nil)", "{}"),
+    ]);
+
+    assert!(matches!(eval_str("(zipmap 1 [1])", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_union_intersection_difference() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(union #{1 2} #{3})", "#{1 2 3}"),
+        ("(union #{1 2} #{2 3})", "#{1 2 3}"),
+        ("(union #{1 2} #{1 2})", "#{1 2}"),
+        ("(union #{1} #{2} #{3} #{1 4})", "#{1 2 3 4}"),
+        ("(union #{:a})", "#{:a}"),
+        ("(union)", "#{}"),
+        ("(intersection #{1 2} #{3})", "#{}"),
+        ("(intersection #{1 2} #{2 3})", "#{2}"),
+        ("(intersection #{1 2} #{1 2})", "#{1 2}"),
+        ("(intersection #{1 2 3} #{2 3 4} #{3 4 5})", "#{3}"),
+        ("(intersection #{1 2})", "#{1 2}"),
+        ("(difference #{1 2} #{3})", "#{1 2}"),
+        ("(difference #{1 2} #{2 3})", "#{1}"),
+        ("(difference #{1 2} #{1 2})", "#{}"),
+        ("(difference #{1 2 3 4} #{1} #{4})", "#{2 3}"),
+        ("(difference #{1 2})", "#{1 2}"),
+        // Elements are kept as they are
+        ("(union #{\"a\" [1 2]} #{:k (quote s)})", "#{\"a\" [1 2] :k (quote s)}"),
+    ]);
+
+    assert!(matches!(eval_str("(intersection)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+    assert!(matches!(eval_str("(difference)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+    assert!(matches!(eval_str("(union #{1} [2])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(intersection '(1) #{1})", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(difference #{1} nil)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_set_operations_on_collection_elements() {
+    let env = standard_env();
+
+    // Maps and sets are elements like any other value, equal when their
+    // contents are
+    assert_evals(&env, &[
+        ("(= #{{:a 1}} #{{:a 1}})", "true"),
+        ("(count (union #{{:a 1}} #{{:a 1}}))", "1"),
+        ("(union #{{:a 1}} #{{:a 1}})", "#{{:a 1}}"),
+        ("(intersection #{{:a 1} {:b 2}} #{{:b 2}})", "#{{:b 2}}"),
+        ("(difference #{{:a 1} {:b 2}} #{{:a 1}})", "#{{:b 2}}"),
+        ("(count (union #{#{1 2}} #{#{2 1}}))", "1"),
+        ("(subset? #{{:a 1 :b 2}} #{{:b 2 :a 1} {:c 3}})", "true"),
+        ("(get {{:a 1} 2} {:a 1})", "2"),
+        ("(contains? #{#{1 2}} #{2 1})", "true"),
+    ]);
+}
+
+#[test]
+fn test_subset_and_superset() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(subset? #{1} #{1 2})", "true"),
+        ("(subset? #{1 2} #{1 2})", "true"),
+        ("(subset? #{} #{1})", "true"),
+        ("(subset? #{1 3} #{1 2})", "false"),
+        ("(subset? #{1} #{2})", "false"),
+        ("(superset? #{1 2} #{1})", "true"),
+        ("(superset? #{1 2} #{1 2})", "true"),
+        ("(superset? #{1} #{1 2})", "false"),
+        ("(superset? #{1} #{2})", "false"),
+    ]);
+
+    assert!(matches!(eval_str("(subset? #{1} [1 2])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(superset? #{1})", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_subs() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(subs \"hello\" 1)", "\"ello\""),
+        ("(subs \"hello\" 1 3)", "\"el\""),
+        ("(subs \"hello\" 0 5)", "\"hello\""),
+        ("(subs \"hello\" 5)", "\"\""),
+        ("(subs \"hello\" 2 2)", "\"\""),
+        ("(subs \"\" 0)", "\"\""),
+        // Indices count characters, not bytes
+        ("(subs \"héllo\" 1 2)", "\"é\""),
+        ("(subs \"héllo\" 2)", "\"llo\""),
+        ("(subs \"日本語\" 1 3)", "\"本語\""),
+    ]);
+
+    assert!(matches!(
+        eval_str("(subs \"héllo\" 6)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 6, length: 5 })
+    ));
+    assert!(matches!(
+        eval_str("(subs \"hello\" 3 2)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 2, length: 5 })
+    ));
+    assert!(matches!(
+        eval_str("(subs \"hello\" 1 9)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 9, length: 5 })
+    ));
+    assert!(matches!(
+        eval_str("(subs \"hello\" -1)", &env),
+        Err(EvalError::IndexOutOfBounds { index: -1, length: 5 })
+    ));
+    assert!(matches!(eval_str("(subs :hello 1)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(subs \"hello\" 1.5)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_case_and_trim() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(upper-case \"hello World\")", "\"HELLO WORLD\""),
+        ("(upper-case \"héllo\")", "\"HÉLLO\""),
+        ("(upper-case \"straße\")", "\"STRASSE\""),
+        ("(upper-case \"\")", "\"\""),
+        ("(lower-case \"Hello WORLD\")", "\"hello world\""),
+        ("(lower-case \"ÉCOLE\")", "\"école\""),
+        ("(capitalize \"hello WORLD\")", "\"Hello world\""),
+        ("(capitalize \"élan\")", "\"Élan\""),
+        ("(capitalize \"a\")", "\"A\""),
+        ("(capitalize \"\")", "\"\""),
+        ("(trim \"  hi there \t\n\")", "\"hi there\""),
+        ("(trim \"   \")", "\"\""),
+        ("(triml \"  hi  \")", "\"hi  \""),
+        ("(trimr \"  hi  \")", "\"  hi\""),
+        ("(trim \"\u{3000}hé\u{3000}\")", "\"hé\""),
+    ]);
+
+    assert!(matches!(eval_str("(upper-case 1)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(trim nil)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(capitalize \"a\" \"b\")", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_split() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(split \"a,b,c\" \",\")", "[\"a\" \"b\" \"c\"]"),
+        ("(split \"a,b,,c,\" \",\")", "[\"a\" \"b\" \"\" \"c\" \"\"]"),
+        ("(split \"a::b::c\" \"::\")", "[\"a\" \"b\" \"c\"]"),
+        ("(split \"abc\" \",\")", "[\"abc\"]"),
+        ("(split \"\" \",\")", "[\"\"]"),
+        ("(split \"a,b,c\" \",\" 2)", "[\"a\" \"b,c\"]"),
+        ("(split \"a,b,c\" \",\" 1)", "[\"a,b,c\"]"),
+        ("(split \"é,ü\" \",\")", "[\"é\" \"ü\"]"),
+        ("(split \"héllo\" \"\")", "[\"h\" \"é\" \"l\" \"l\" \"o\"]"),
+        ("(split \"héllo\" \"\" 3)", "[\"h\" \"é\" \"llo\"]"),
+        ("(split \"\" \"\")", "[\"\"]"),
+    ]);
+
+    assert!(matches!(eval_str("(split \"a,b\" \",\" 0)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(split \"a,b\" :x)", &env), Err(EvalError::TypeError { .. })));
+}
+
+#[test]
+fn test_join_and_replace() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(join [\"a\" \"b\" \"c\"])", "\"abc\""),
+        ("(join \", \" [\"a\" \"b\" \"c\"])", "\"a, b, c\""),
+        ("(join \"-\" [1 2.5 :k nil \"s\"])", "\"1-2.5-:k--s\""),
+        ("(join \"-\" [])", "\"\""),
+        ("(join \"-\" nil)", "\"\""),
+        ("(join \"·\" '(\"é\" \"ü\"))", "\"é·ü\""),
+        ("(join \"\" \"abc\")", "\"abc\""),
+        ("(replace \"a-b-c\" \"-\" \"+\")", "\"a+b+c\""),
+        ("(replace \"hello\" \"l\" \"\")", "\"heo\""),
+        ("(replace \"hello\" \"x\" \"y\")", "\"hello\""),
+        ("(replace \"héllo héllo\" \"é\" \"e\")", "\"hello hello\""),
+        ("(replace \"\" \"a\" \"b\")", "\"\""),
+        ("(replace (join \",\" (split \"a b c\" \" \")) \",\" \";\")", "\"a;b;c\""),
+    ]);
+
+    assert!(matches!(eval_str("(join 1 [1 2])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(replace \"abc\" :a \"b\")", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(replace \"abc\" \"a\")", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_string_predicates() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(starts-with? \"hello\" \"he\")", "true"),
+        ("(starts-with? \"hello\" \"lo\")", "false"),
+        ("(starts-with? \"héllo\" \"hé\")", "true"),
+        ("(starts-with? \"hello\" \"\")", "true"),
+        ("(starts-with? \"\" \"a\")", "false"),
+        ("(ends-with? \"hello\" \"lo\")", "true"),
+        ("(ends-with? \"hello\" \"he\")", "false"),
+        ("(ends-with? \"日本語\" \"語\")", "true"),
+        ("(includes? \"hello\" \"ell\")", "true"),
+        ("(includes? \"hello\" \"xyz\")", "false"),
+        ("(includes? \"naïve\" \"ïv\")", "true"),
+        ("(includes? \"\" \"\")", "true"),
+        ("(blank? nil)", "true"),
+        ("(blank? \"\")", "true"),
+        ("(blank? \"  \t\n\")", "true"),
+        ("(blank? \"\u{3000}\")", "true"),
+        ("(blank? \" a \")", "false"),
+    ]);
+
+    assert!(matches!(eval_str("(starts-with? nil \"a\")", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(includes? \"abc\" :b)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(blank? [])", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(ends-with? \"abc\")", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_index_of() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(index-of \"banana\" \"an\")", "1"),
+        ("(index-of \"banana\" \"an\" 2)", "3"),
+        ("(index-of \"banana\" \"an\" 4)", "nil"),
+        ("(index-of \"banana\" \"x\")", "nil"),
+        ("(index-of \"banana\" \"b\" -3)", "0"),
+        ("(index-of \"banana\" \"a\" 10)", "nil"),
+        ("(index-of \"banana\" \"\")", "0"),
+        ("(index-of \"\" \"a\")", "nil"),
+        // Indices count characters, not bytes
+        ("(index-of \"héllo wörld\" \"wö\")", "6"),
+        ("(index-of \"日本語の日本\" \"日本\" 1)", "4"),
+        ("(last-index-of \"banana\" \"an\")", "3"),
+        ("(last-index-of \"banana\" \"an\" 2)", "1"),
+        ("(last-index-of \"banana\" \"an\" 3)", "3"),
+        ("(last-index-of \"banana\" \"an\" 0)", "nil"),
+        ("(last-index-of \"banana\" \"b\" -1)", "nil"),
+        ("(last-index-of \"banana\" \"a\" 100)", "5"),
+        ("(last-index-of \"aaa\" \"aa\" 0)", "0"),
+        ("(last-index-of \"aaaa\" \"aa\" 1)", "1"),
+        ("(last-index-of \"banana\" \"x\")", "nil"),
+        ("(last-index-of \"banana\" \"\")", "6"),
+        ("(last-index-of \"日本語の日本\" \"日本\")", "4"),
+        ("(last-index-of \"日本語の日本\" \"日本\" 3)", "0"),
+    ]);
+
+    assert!(matches!(eval_str("(index-of nil \"a\")", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(index-of \"abc\" \"a\" 1.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(last-index-of \"abc\" 1)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(index-of \"abc\")", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
+
+#[test]
+fn test_format() {
+    let env = standard_env();
+
+    assert_evals(&env, &[
+        ("(format \"plain\")", "\"plain\""),
+        ("(format \"%s scored %d points (%.2f%%)\" \"ann\" 42 87.5)", "\"ann scored 42 points (87.50%)\""),
+        ("(format \"%s %s [%s]\" :k 1.5 nil)", "\":k 1.5 []\""),
+        ("(format \"%s\" \"héllo\")", "\"héllo\""),
+        ("(format \"%d\" -7)", "\"-7\""),
+        ("(format \"%d\" 10000000000)", "\"10000000000\""),
+        ("(format \"%f\" 1.5)", "\"1.500000\""),
+        ("(format \"%.0f\" 2.4)", "\"2\""),
+        ("(format \"%.3f\" 2)", "\"2.000\""),
+        ("(format \"100%%\")", "\"100%\""),
+        ("(format \"%%d %d\" 1)", "\"%d 1\""),
+        // Width and flags
+        ("(format \"[%5d]\" 42)", "\"[   42]\""),
+        ("(format \"[%-5d]\" 42)", "\"[42   ]\""),
+        ("(format \"[%05d]\" -42)", "\"[-0042]\""),
+        ("(format \"[%-10s]\" \"hi\")", "\"[hi        ]\""),
+        ("(format \"[%10s]\" \"héllo\")", "\"[     héllo]\""),
+        ("(format \"[%8.2f]\" 3.14159)", "\"[    3.14]\""),
+        ("(format \"[%08.2f]\" 3.14159)", "\"[00003.14]\""),
+        ("(format \"[%2s]\" \"long\")", "\"[long]\""),
+        ("(format \"[%.2s]\" \"héllo\")", "\"[hé]\""),
+    ]);
+
+    // Strings inside collections keep their quotes
+    assert_eq!(
+        eval_str("(format \"%s\" [1 \"a\"])", &env).unwrap(),
+        Value::String("[1 \"a\"]".to_string())
+    );
+}
+
+#[test]
+fn test_format_errors() {
+    let env = standard_env();
+
+    let message = |source: &str| eval_str(source, &env).unwrap_err().to_string();
+
+    // Argument types name the directive they are for
+    assert!(matches!(eval_str("(format \"%d\" 1.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(message("(format \"%s %d\" \"a\" 1.5)").contains("directive 2 (%d)"));
+    assert!(message("(format \"%s %5d\" \"a\" \"b\")").contains("directive 2 (%5d)"));
+    assert!(message("(format \"%.2f\" :x)").contains("directive 1 (%.2f)"));
+
+    // Directives and arguments must match up
+    assert!(message("(format \"%s and %s\" 1)").contains("directive 2 (%s)"));
+    assert!(message("(format \"%s\" 1 2)").contains("1 directives, got 2 arguments"));
+    assert!(eval_str("(format \"no directives\" 1)", &env).is_err());
+
+    // Malformed templates
+    assert!(message("(format \"%q\" 1)").contains("%q"));
+    assert!(message("(format \"50%\")").contains("Incomplete"));
+    assert!(eval_str("(format \"%.2d\" 1)", &env).is_err());
+
+    // Widths and precisions are limited, including ones too large for usize
+    assert!(message("(format \"%99999999999999999999d\" 1)").contains("maximum"));
+    assert!(message("(format \"%999999999d\" 1)").contains("maximum"));
+    assert!(message("(format \"%.999999999f\" 1.5)").contains("maximum"));
+    assert_eq!(
+        eval_str("(format \"%4096d\" 1)", &env).unwrap(),
+        Value::String(format!("{:>4096}", 1))
+    );
+    assert!(matches!(eval_str("(format :x)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(format)", &env).unwrap_err().cause(), EvalError::ArityMismatch { .. }));
+}
@@ -0,0 +1,190 @@
+use citrine::{eval_str, standard_env};
+use citrine::reader::Value;
+
+fn num(n: f64) -> Value {
+    Value::Number(n)
+}
+
+fn list(items: Vec<Value>) -> Value {
+    Value::List(items)
+}
+
+/// Evaluates `(let [pattern value] body)` and returns the result
+fn check_let(pattern: &str, value: &str, body: &str, expected: Value) {
+    let env = standard_env();
+    let source = format!("(let [{} {}] {})", pattern, value, body);
+    let result = eval_str(&source, &env).unwrap_or_else(|e| panic!("{}: {}", source, e));
+    assert_eq!(result, expected, "{}", source);
+}
+
+#[test]
+fn test_sequential_patterns() {
+    // Positional elements
+    check_let("[a b]", "[1 2]", "(list a b)", list(vec![num(1.0), num(2.0)]));
+    check_let("[a b]", "'(1 2)", "(list a b)", list(vec![num(1.0), num(2.0)]));
+
+    // Missing elements bind to nil, extra elements are ignored
+    check_let("[a b c]", "[1 2]", "c", Value::Nil);
+    check_let("[a]", "[1 2 3]", "a", num(1.0));
+    check_let("[a b]", "nil", "(list a b)", list(vec![Value::Nil, Value::Nil]));
+
+    // Strings destructure into their characters
+    check_let("[a b]", "\"hi\"", "b", Value::String("i".to_string()));
+}
+
+#[test]
+fn test_sequential_rest_and_as() {
+    check_let(
+        "[a & more]",
+        "[1 2 3]",
+        "more",
+        list(vec![num(2.0), num(3.0)]),
+    );
+
+    // An empty rest binds to nil
+    check_let("[a b & more]", "[1 2]", "more", Value::Nil);
+    check_let("[a b & more]", "[1]", "more", Value::Nil);
+
+    // :as binds the whole collection
+    check_let(
+        "[a :as all]",
+        "[1 2]",
+        "all",
+        Value::Vector(vec![num(1.0), num(2.0)]),
+    );
+    check_let(
+        "[a & more :as all]",
+        "'(1 2)",
+        "(list a more all)",
+        list(vec![
+            num(1.0),
+            list(vec![num(2.0)]),
+            list(vec![num(1.0), num(2.0)]),
+        ]),
+    );
+
+    // The rest can itself be destructured
+    check_let("[a & [b c]]", "[1 2 3]", "(list a b c)", list(vec![num(1.0), num(2.0), num(3.0)]));
+}
+
+#[test]
+fn test_nested_sequential_patterns() {
+    check_let(
+        "[[a b] [c [d]]]",
+        "[[1 2] [3 [4]]]",
+        "(list a b c d)",
+        list(vec![num(1.0), num(2.0), num(3.0), num(4.0)]),
+    );
+    check_let("[[a] b]", "[nil 2]", "(list a b)", list(vec![Value::Nil, num(2.0)]));
+}
+
+#[test]
+fn test_map_keys_strs_syms() {
+    check_let("{:keys [x y]}", "{:x 1 :y 2}", "(+ x y)", num(3.0));
+    check_let("{:strs [x]}", "{\"x\" 1}", "x", num(1.0));
+    check_let("{:syms [x]}", "{'x 1}", "x", num(1.0));
+
+    // Missing keys bind to nil, and nil destructures to all nils
+    check_let("{:keys [x y]}", "{:x 1}", "y", Value::Nil);
+    check_let("{:keys [x]}", "nil", "x", Value::Nil);
+}
+
+#[test]
+fn test_map_explicit_keys_or_and_as() {
+    check_let("{a :a b :b}", "{:a 1 :b 2}", "(list a b)", list(vec![num(1.0), num(2.0)]));
+
+    // :or only applies to missing keys
+    check_let("{:keys [x y] :or {y 10}}", "{:x 1}", "(+ x y)", num(11.0));
+    check_let("{:keys [y] :or {y 10}}", "{:y 2}", "y", num(2.0));
+    check_let("{a :a :or {a (+ 1 2)}}", "{}", "a", num(3.0));
+
+    // :as binds the whole map
+    check_let(
+        "{:keys [x] :as m}",
+        "{:x 1}",
+        "(list x (= m {:x 1}))",
+        list(vec![num(1.0), Value::Boolean(true)]),
+    );
+
+    // Vectors can be destructured associatively by index
+    check_let("{a 1}", "[10 20]", "a", num(20.0));
+}
+
+#[test]
+fn test_nested_mixed_patterns() {
+    check_let(
+        "{[x y] :point {:keys [name]} :meta}",
+        "{:point [1 2] :meta {:name \"p\"}}",
+        "(list x y name)",
+        list(vec![num(1.0), num(2.0), Value::String("p".to_string())]),
+    );
+    check_let(
+        "[{:keys [a]} {:keys [b] :or {b 5}}]",
+        "[{:a 1} {}]",
+        "(+ a b)",
+        num(6.0),
+    );
+    check_let(
+        "[_ [_ {[first-item] :items}]]",
+        "[0 [0 {:items [42]}]]",
+        "first-item",
+        num(42.0),
+    );
+}
+
+#[test]
+fn test_later_bindings_see_destructured_names() {
+    check_let("[a b] [1 2] c", "(+ a b)", "c", num(3.0));
+}
+
+#[test]
+fn test_fn_param_destructuring() {
+    let env = standard_env();
+
+    eval_str("(defn sum-pair [[a b]] (+ a b))", &env).unwrap();
+    assert_eq!(eval_str("(sum-pair [1 2])", &env).unwrap(), num(3.0));
+
+    eval_str("(defn area [{:keys [w h] :or {h 1}}] (* w h))", &env).unwrap();
+    assert_eq!(eval_str("(area {:w 3 :h 4})", &env).unwrap(), num(12.0));
+    assert_eq!(eval_str("(area {:w 3})", &env).unwrap(), num(3.0));
+
+    // Patterns mixed with plain parameters
+    assert_eq!(eval_str("((fn [x [y z]] (+ x y z)) 1 [2 3])", &env).unwrap(), num(6.0));
+
+    // The rest parameter can be destructured, including as keyword arguments
+    assert_eq!(eval_str("((fn [x & [y]] (+ x y)) 1 2 3)", &env).unwrap(), num(3.0));
+    assert_eq!(eval_str("((fn [& {:keys [a b]}] (+ a b)) :a 1 :b 2)", &env).unwrap(), num(3.0));
+}
+
+#[test]
+fn test_loop_destructuring_with_recur() {
+    let env = standard_env();
+
+    let result = eval_str(
+        "(loop [[x & xs] [1 2 3] sum 0] (if (= x nil) sum (recur xs (+ sum x))))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(result, num(6.0));
+
+    eval_str("(defn sum-all [acc [x & xs]] (if (= x nil) acc (recur (+ acc x) xs)))", &env).unwrap();
+    assert_eq!(eval_str("(sum-all 0 [1 2 3 4])", &env).unwrap(), num(10.0));
+}
+
+#[test]
+fn test_destructuring_type_errors() {
+    let env = standard_env();
+
+    // Destructuring a number as a vector names the pattern
+    let err = eval_str("(let [[a b] 5] a)", &env).unwrap_err();
+    assert!(err.to_string().contains("[a b]"), "{}", err);
+
+    let err = eval_str("(let [{:keys [a]} 5] a)", &env).unwrap_err();
+    assert!(err.to_string().contains("{:keys [a]}"), "{}", err);
+
+    // Malformed patterns
+    assert!(eval_str("(let [[a &] [1]] a)", &env).is_err());
+    assert!(eval_str("(let [[a :as] [1]] a)", &env).is_err());
+    assert!(eval_str("(let [{:keys x} {}] x)", &env).is_err());
+    assert!(eval_str("(let [1 2] 1)", &env).is_err());
+}
@@ -0,0 +1,1298 @@
+use citrine::{eval_str, read_str, standard_env};
+use citrine::reader::{EvalError, Value};
+
+#[test]
+fn test_do_returns_last_value() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(do 1 2 3)", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(do (+ 1 2))", &env).unwrap(), Value::Number(3.0));
+
+    // An empty do evaluates to nil
+    assert_eq!(eval_str("(do)", &env).unwrap(), Value::Nil);
+}
+
+#[test]
+fn test_do_with_setq() {
+    let env = standard_env();
+
+    // Bindings made inside do persist in the current environment
+    let result = eval_str("(do (setq x 1) (setq y (+ x 1)) (+ x y))", &env).unwrap();
+    assert_eq!(result, Value::Number(3.0));
+    assert_eq!(env.borrow().get("x"), Some(Value::Number(1.0)));
+    assert_eq!(env.borrow().get("y"), Some(Value::Number(2.0)));
+
+    // Forms are evaluated left to right
+    let result = eval_str("(do (setq x 10) (setq x (* x 2)) x)", &env).unwrap();
+    assert_eq!(result, Value::Number(20.0));
+}
+
+#[test]
+fn test_do_stops_at_first_error() {
+    let env = standard_env();
+
+    let result = eval_str("(do (setq a 1) (undefined-fn) (setq b 2))", &env);
+    assert!(result.is_err());
+
+    // Forms before the error ran, forms after it did not
+    assert_eq!(env.borrow().get("a"), Some(Value::Number(1.0)));
+    assert_eq!(env.borrow().get("b"), None);
+}
+
+#[test]
+fn test_let_sequential_bindings() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(let [x 1 y (+ x 1)] (+ x y))", &env).unwrap(), Value::Number(3.0));
+
+    // The body is an implicit do
+    assert_eq!(eval_str("(let [x 1] (setq y 5) (+ x y))", &env).unwrap(), Value::Number(6.0));
+
+    // An empty body evaluates to nil
+    assert_eq!(eval_str("(let [x 1])", &env).unwrap(), Value::Nil);
+
+    // Bindings do not leak out of the let
+    eval_str("(let [z 1] z)", &env).unwrap();
+    assert_eq!(env.borrow().get("z"), None);
+}
+
+#[test]
+fn test_let_shadowing() {
+    let env = standard_env();
+    eval_str("(setq x 10)", &env).unwrap();
+
+    assert_eq!(eval_str("(let [x 1] x)", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(let [x (+ x 1) x (* x 2)] x)", &env).unwrap(), Value::Number(22.0));
+
+    // The outer binding is untouched
+    assert_eq!(eval_str("x", &env).unwrap(), Value::Number(10.0));
+}
+
+#[test]
+fn test_let_closure_captures_local() {
+    let env = standard_env();
+
+    eval_str("(setq add-five (let [n 5] (fn [x] (+ x n))))", &env).unwrap();
+    assert_eq!(eval_str("(add-five 1)", &env).unwrap(), Value::Number(6.0));
+    assert_eq!(env.borrow().get("n"), None);
+}
+
+#[test]
+fn test_let_errors() {
+    let env = standard_env();
+
+    // Odd number of binding forms
+    assert!(eval_str("(let [x 1 y] x)", &env).is_err());
+
+    // Non-symbol binding target
+    assert!(eval_str("(let [1 2] 1)", &env).is_err());
+
+    // Bindings must be a vector
+    assert!(eval_str("(let (x 1) x)", &env).is_err());
+}
+
+#[test]
+fn test_letfn_mutual_recursion() {
+    let env = standard_env();
+
+    let source = "(letfn [(even? [n] (if (= n 0) true (odd? (- n 1))))
+                          (odd? [n] (if (= n 0) false (even? (- n 1))))]
+                    (list (even? 101) (odd? 101)))";
+    assert_eq!(
+        eval_str(source, &env).unwrap(),
+        Value::List(vec![Value::Boolean(false), Value::Boolean(true)])
+    );
+
+    // The functions are local to the letfn, shadowing the builtins
+    assert!(matches!(&env.borrow().get("even?"), Some(Value::Function(f)) if f.is_builtin));
+}
+
+#[test]
+fn test_letfn_closures() {
+    let env = standard_env();
+
+    // Functions see the enclosing locals, and the body sees the functions
+    let source = "(let [step 2]
+                    (letfn [(up [n] (+ n step))]
+                      (up (up 1))))";
+    assert_eq!(eval_str(source, &env).unwrap(), Value::Number(5.0));
+
+    // Functions get their letfn name, and may use recur
+    let source = "(letfn [(count-down [n] (if (= n 0) :done (recur (- n 1))))]
+                    (count-down 100000))";
+    assert_eq!(eval_str(source, &env).unwrap(), Value::Keyword("done".to_string()));
+}
+
+#[test]
+fn test_letfn_errors() {
+    let env = standard_env();
+
+    // Bindings must be a vector of (name [params] body*) forms
+    assert!(eval_str("(letfn ((f [] 1)) (f))", &env).is_err());
+    assert!(eval_str("(letfn [f (fn [] 1)] (f))", &env).is_err());
+    assert!(eval_str("(letfn [(f [])] (f))", &env).is_err());
+    assert!(eval_str("(letfn [(1 [] 1)] 1)", &env).is_err());
+    assert!(eval_str("(letfn [(f x 1)] 1)", &env).is_err());
+}
+
+#[test]
+fn test_if() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(if (= 1 1) 1 2)", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(if (= 1 2) 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(if (= 1 2) 1)", &env).unwrap(), Value::Nil);
+
+    // Only the chosen branch is evaluated
+    eval_str("(if (= 1 1) (setq x 1) (setq y 2))", &env).unwrap();
+    assert_eq!(env.borrow().get("x"), Some(Value::Number(1.0)));
+    assert_eq!(env.borrow().get("y"), None);
+}
+
+#[test]
+fn test_def_binds_at_root() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(def x 42)", &env).unwrap(), Value::Symbol("x".to_string()));
+    assert_eq!(eval_str("x", &env).unwrap(), Value::Number(42.0));
+
+    // A def inside a let is visible at top level afterward
+    eval_str("(let [a 1] (def from-let (+ a 1)))", &env).unwrap();
+    assert_eq!(eval_str("from-let", &env).unwrap(), Value::Number(2.0));
+
+    // So is a def inside a function body
+    eval_str("(def define-global (fn [v] (def from-fn v)))", &env).unwrap();
+    eval_str("(define-global 7)", &env).unwrap();
+    assert_eq!(eval_str("from-fn", &env).unwrap(), Value::Number(7.0));
+}
+
+#[test]
+fn test_defn() {
+    let env = standard_env();
+
+    eval_str("(defn add [a b] (+ a b))", &env).unwrap();
+    assert_eq!(eval_str("(add 2 3)", &env).unwrap(), Value::Number(5.0));
+
+    // The docstring and the name are recorded on the function
+    eval_str("(defn square \"Squares a number\" [x] (* x x))", &env).unwrap();
+    assert_eq!(eval_str("(square 4)", &env).unwrap(), Value::Number(16.0));
+    let square = env.borrow().get("square");
+    match &square {
+        Some(Value::Function(f)) => {
+            assert_eq!(f.name, Some("square".to_string()));
+            assert_eq!(f.doc, Some("Squares a number".to_string()));
+        }
+        _ => panic!("Expected a function"),
+    }
+}
+
+#[test]
+fn test_defn_recursive() {
+    let env = standard_env();
+
+    eval_str("(defn factorial [n] (if (= n 0) 1 (* n (factorial (- n 1)))))", &env).unwrap();
+    assert_eq!(eval_str("(factorial 5)", &env).unwrap(), Value::Number(120.0));
+
+    // A recursive function defined inside a let body still sees itself
+    eval_str("(let [base 1] (defn fact2 [n] (if (= n 0) base (* n (fact2 (- n 1))))))", &env).unwrap();
+    assert_eq!(eval_str("(fact2 4)", &env).unwrap(), Value::Number(24.0));
+}
+
+#[test]
+fn test_quote_symbol() {
+    let env = standard_env();
+
+    // The symbol is returned without being looked up
+    assert_eq!(eval_str("'foo", &env).unwrap(), Value::Symbol("foo".to_string()));
+    assert_eq!(eval_str("(quote foo)", &env).unwrap(), Value::Symbol("foo".to_string()));
+}
+
+#[test]
+fn test_quote_list() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("'(1 2 3)", &env).unwrap(),
+        Value::List(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Number(3.0)
+        ])
+    );
+
+    // Symbols inside the list stay unevaluated
+    assert_eq!(
+        eval_str("'(+ a 1)", &env).unwrap(),
+        Value::List(vec![
+            Value::Symbol("+".to_string()),
+            Value::Symbol("a".to_string()),
+            Value::Number(1.0)
+        ])
+    );
+}
+
+#[test]
+fn test_quote_vector() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("'[a b (c)]", &env).unwrap(),
+        Value::Vector(vec![
+            Value::Symbol("a".to_string()),
+            Value::Symbol("b".to_string()),
+            Value::List(vec![Value::Symbol("c".to_string())])
+        ])
+    );
+}
+
+#[test]
+fn test_quote_nested() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("''foo", &env).unwrap(),
+        Value::List(vec![
+            Value::Symbol("quote".to_string()),
+            Value::Symbol("foo".to_string())
+        ])
+    );
+}
+
+#[test]
+fn test_quote_arity() {
+    let env = standard_env();
+
+    assert!(eval_str("(quote)", &env).is_err());
+    assert!(eval_str("(quote a b)", &env).is_err());
+}
+
+#[test]
+fn test_when() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(when true 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(when nil 1 2)", &env).unwrap(), Value::Nil);
+
+    // An empty body evaluates to nil
+    assert_eq!(eval_str("(when true)", &env).unwrap(), Value::Nil);
+
+    // The body is not evaluated when the test fails
+    eval_str("(when false (setq ran true))", &env).unwrap();
+    assert_eq!(env.borrow().get("ran"), None);
+}
+
+#[test]
+fn test_when_not() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(when-not false 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(when-not 0 1 2)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(when-not nil)", &env).unwrap(), Value::Nil);
+
+    eval_str("(when-not true (setq ran true))", &env).unwrap();
+    assert_eq!(env.borrow().get("ran"), None);
+}
+
+#[test]
+fn test_if_not() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(if-not false 1 2)", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(if-not true 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(if-not true 1)", &env).unwrap(), Value::Nil);
+
+    // Only the chosen branch is evaluated
+    eval_str("(if-not nil (setq then-ran true) (setq else-ran true))", &env).unwrap();
+    assert_eq!(env.borrow().get("then-ran"), Some(Value::Boolean(true)));
+    assert_eq!(env.borrow().get("else-ran"), None);
+}
+
+#[test]
+fn test_loop_recur() {
+    let env = standard_env();
+
+    let result = eval_str("(loop [i 0 acc 1] (if (= i 5) acc (recur (+ i 1) (* acc 2))))", &env).unwrap();
+    assert_eq!(result, Value::Number(32.0));
+
+    // Later bindings see earlier ones on entry
+    let result = eval_str("(loop [x 1 y (+ x 1)] (+ x y))", &env).unwrap();
+    assert_eq!(result, Value::Number(3.0));
+
+    // recur in tail position through do, let and when
+    let result = eval_str(
+        "(loop [i 0] (do (setq last-i i) (let [next (+ i 1)] (if (< i 3) (recur next) i))))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(result, Value::Number(3.0));
+}
+
+#[test]
+fn test_loop_recur_large_iteration_count() {
+    let env = standard_env();
+
+    // Summing 1..1_000_000 must not grow the Rust stack
+    let result = eval_str(
+        "(loop [i 1 sum 0] (if (> i 1000000) sum (recur (+ i 1) (+ sum i))))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(result, Value::Number(500000500000.0));
+}
+
+#[test]
+fn test_recur_in_fn() {
+    let env = standard_env();
+
+    eval_str("(defn count-up [i n] (if (< i n) (recur (+ i 1) n) i))", &env).unwrap();
+    assert_eq!(eval_str("(count-up 0 100000)", &env).unwrap(), Value::Number(100000.0));
+}
+
+#[test]
+fn test_recur_errors() {
+    let env = standard_env();
+
+    // Arity must match the number of bindings
+    assert!(eval_str("(loop [i 0] (recur 1 2))", &env).is_err());
+
+    // recur outside of tail position is rejected
+    let err = eval_str("(loop [i 0] (+ 1 (recur i)))", &env).unwrap_err();
+    assert!(err.to_string().contains("tail position"));
+    assert!(eval_str("(loop [i 0] (if (recur i) 1 2))", &env).is_err());
+
+    // recur without an enclosing loop or fn
+    assert!(eval_str("(recur 1)", &env).is_err());
+}
+
+#[test]
+fn test_tail_call_self_recursion() {
+    let env = standard_env();
+
+    eval_str("(defn count-down [n] (if (= n 0) :done (count-down (- n 1))))", &env).unwrap();
+    assert_eq!(
+        eval_str("(count-down 1000000)", &env).unwrap(),
+        Value::Keyword("done".to_string())
+    );
+}
+
+#[test]
+fn test_tail_call_mutual_recursion() {
+    let env = standard_env();
+
+    // Any call in tail position reuses the evaluation loop, not just self calls
+    eval_str("(defn my-even? [n] (if (= n 0) true (my-odd? (- n 1))))", &env).unwrap();
+    eval_str("(defn my-odd? [n] (if (= n 0) false (my-even? (- n 1))))", &env).unwrap();
+    assert_eq!(eval_str("(my-even? 100000)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(my-odd? 100001)", &env).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn test_tail_call_through_let_and_do() {
+    let env = standard_env();
+
+    eval_str(
+        "(defn sum-to [n acc] (let [next (- n 1)] (do (if (< n 1) acc (sum-to next (+ acc n))))))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(eval_str("(sum-to 100000 0)", &env).unwrap(), Value::Number(5000050000.0));
+
+    // Non-tail calls still return into the caller
+    eval_str("(defn fact [n] (if (= n 0) 1 (* n (fact (- n 1)))))", &env).unwrap();
+    assert_eq!(eval_str("(fact 10)", &env).unwrap(), Value::Number(3628800.0));
+}
+
+#[test]
+fn test_variadic_fn() {
+    let env = standard_env();
+
+    eval_str("(defn f [x & more] (list x more))", &env).unwrap();
+
+    // No rest arguments binds the rest parameter to nil
+    assert_eq!(
+        eval_str("(f 1)", &env).unwrap(),
+        Value::List(vec![Value::Number(1.0), Value::Nil])
+    );
+
+    // Several rest arguments are collected into a list
+    assert_eq!(
+        eval_str("(f 1 2 3)", &env).unwrap(),
+        Value::List(vec![
+            Value::Number(1.0),
+            Value::List(vec![Value::Number(2.0), Value::Number(3.0)])
+        ])
+    );
+
+    // Only rest arguments
+    assert_eq!(
+        eval_str("((fn [& xs] xs) 1 2)", &env).unwrap(),
+        Value::List(vec![Value::Number(1.0), Value::Number(2.0)])
+    );
+
+    // The positional parameters are still required
+    assert!(eval_str("(f)", &env).is_err());
+}
+
+#[test]
+fn test_variadic_fn_recur() {
+    let env = standard_env();
+
+    // recur passes the rest argument explicitly
+    eval_str("(defn count-args [n & xs] (if (= xs nil) n (recur (+ n 1) (rest-or-nil xs))))", &env).unwrap();
+    eval_str("(defn rest-or-nil [xs] (if (= (rest xs) (list)) nil (rest xs)))", &env).unwrap();
+    assert_eq!(eval_str("(count-args 0 :a :b :c)", &env).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_variadic_params_errors() {
+    let env = standard_env();
+
+    // & must be second to last
+    assert!(eval_str("(fn [& xs ys] xs)", &env).is_err());
+    assert!(eval_str("(fn [x &] x)", &env).is_err());
+    assert!(eval_str("(fn [& & xs] xs)", &env).is_err());
+
+    // Macros share the same parameter parsing
+    assert!(eval_str("(macro [x & rest] x)", &env).is_ok());
+    assert!(eval_str("(macro [x & a b] x)", &env).is_err());
+}
+
+#[test]
+fn test_try_without_error() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(try 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(try)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(try (+ 1 2) (catch e :caught))", &env).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_catch_thrown_value() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("(try (throw :oops) (catch e e))", &env).unwrap(),
+        Value::Keyword("oops".to_string())
+    );
+
+    // Throwing from inside a function call aborts the rest of the body
+    eval_str("(def reached false)", &env).unwrap();
+    eval_str("(defn fail [] (throw {:code 42}) (setq reached true))", &env).unwrap();
+    assert_eq!(
+        eval_str("(try (fail) (catch {:keys [code]} (+ code 1)))", &env).unwrap(),
+        Value::Number(43.0)
+    );
+    assert_eq!(eval_str("reached", &env).unwrap(), Value::Boolean(false));
+
+    // Uncaught throws surface as Thrown errors
+    assert!(matches!(
+        &eval_str("(throw :oops)", &env),
+        Err(EvalError::Thrown(Value::Keyword(k))) if k == "oops"
+    ));
+}
+
+#[test]
+fn test_catch_internal_error() {
+    let env = standard_env();
+
+    // Internal errors are caught as maps with a :type and a :message
+    assert_eq!(
+        eval_str("(try (/ 1 0) (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("division-by-zero".to_string())
+    );
+    match &eval_str("(try (/ 1 0) (catch {:keys [message]} message))", &env).unwrap() {
+        Value::String(message) => assert!(message.contains("Division by zero"), "{}", message),
+        other => panic!("expected a message string, got {:?}", other),
+    }
+    assert_eq!(
+        eval_str("(try undefined-symbol (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("unbound-symbol".to_string())
+    );
+}
+
+#[test]
+fn test_finally_always_runs() {
+    let env = standard_env();
+    eval_str("(def cleaned 0)", &env).unwrap();
+
+    // On success, finally runs and the body's value is returned
+    assert_eq!(
+        eval_str("(try :ok (finally (setq cleaned (+ cleaned 1))))", &env).unwrap(),
+        Value::Keyword("ok".to_string())
+    );
+    assert_eq!(eval_str("cleaned", &env).unwrap(), Value::Number(1.0));
+
+    // On error, finally runs after catch
+    assert_eq!(
+        eval_str("(try (throw :x) (catch e :caught) (finally (setq cleaned (+ cleaned 1))))", &env).unwrap(),
+        Value::Keyword("caught".to_string())
+    );
+    assert_eq!(eval_str("cleaned", &env).unwrap(), Value::Number(2.0));
+
+    // Without a catch, finally runs and the error propagates
+    assert!(eval_str("(try (throw :x) (finally (setq cleaned (+ cleaned 1))))", &env).is_err());
+    assert_eq!(eval_str("cleaned", &env).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_rethrow_from_catch() {
+    let env = standard_env();
+
+    let source = "(try
+                    (try (throw :inner) (catch e (throw (list :wrapped e))))
+                    (catch e e))";
+    assert_eq!(
+        eval_str(source, &env).unwrap(),
+        Value::List(vec![Value::Keyword("wrapped".to_string()), Value::Keyword("inner".to_string())])
+    );
+
+    // An error in catch still runs finally, then propagates
+    eval_str("(def cleaned false)", &env).unwrap();
+    assert!(matches!(
+        &eval_str("(try (throw :a) (catch e (throw :b)) (finally (setq cleaned true)))", &env),
+        Err(EvalError::Thrown(Value::Keyword(k))) if k == "b"
+    ));
+    assert_eq!(eval_str("cleaned", &env).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn test_try_errors() {
+    let env = standard_env();
+
+    // catch and finally must come last, in that order
+    assert!(eval_str("(try (catch e 1) 2)", &env).is_err());
+    assert!(eval_str("(try 1 (finally 2) (catch e 3))", &env).is_err());
+    assert!(eval_str("(try 1 (catch))", &env).is_err());
+    assert!(eval_str("(throw)", &env).is_err());
+}
+
+#[test]
+fn test_keyword_as_function() {
+    let env = standard_env();
+    eval_str("(def person {:name \"Ada\" :age 36})", &env).unwrap();
+
+    assert_eq!(eval_str("(:name person)", &env).unwrap(), Value::String("Ada".to_string()));
+    assert_eq!(eval_str("(:email person)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(:email person :none)", &env).unwrap(), Value::Keyword("none".to_string()));
+    assert_eq!(eval_str("(:age person 0)", &env).unwrap(), Value::Number(36.0));
+
+    // Sets are looked up by membership, anything else has no keys
+    assert_eq!(eval_str("(:a #{:a :b})", &env).unwrap(), Value::Keyword("a".to_string()));
+    assert_eq!(eval_str("(:a nil)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(:a 1 :default)", &env).unwrap(), Value::Keyword("default".to_string()));
+
+    assert!(matches!(
+        eval_str("(:a)", &env).unwrap_err().cause(),
+        EvalError::ArityMismatch { expected: 1, got: 0 }
+    ));
+    assert!(eval_str("(:a {} 1 2)", &env).is_err());
+}
+
+#[test]
+fn test_map_as_function() {
+    let env = standard_env();
+    eval_str("(def person {:name \"Ada\" \"id\" 7})", &env).unwrap();
+
+    assert_eq!(eval_str("(person :name)", &env).unwrap(), Value::String("Ada".to_string()));
+    assert_eq!(eval_str("(person \"id\")", &env).unwrap(), Value::Number(7.0));
+    assert_eq!(eval_str("(person :email)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(person :email \"n/a\")", &env).unwrap(), Value::String("n/a".to_string()));
+    assert_eq!(eval_str("({:a 1} :a)", &env).unwrap(), Value::Number(1.0));
+
+    assert!(eval_str("(person)", &env).is_err());
+}
+
+#[test]
+fn test_vector_as_function() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("([10 20 30] 1)", &env).unwrap(), Value::Number(20.0));
+    assert_eq!(eval_str("(let [v [:a :b]] (v 0))", &env).unwrap(), Value::Keyword("a".to_string()));
+
+    assert!(matches!(
+        eval_str("([10 20 30] 3)", &env),
+        Err(EvalError::IndexOutOfBounds { index: 3, length: 3 })
+    ));
+    assert!(matches!(
+        eval_str("([10 20 30] -1)", &env),
+        Err(EvalError::IndexOutOfBounds { index: -1, length: 3 })
+    ));
+    assert!(matches!(eval_str("([10] 0.5)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("([10] :a)", &env), Err(EvalError::TypeError { .. })));
+    assert!(eval_str("([10] 0 1)", &env).is_err());
+}
+
+#[test]
+fn test_set_as_function() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(#{:a :b} :a)", &env).unwrap(), Value::Keyword("a".to_string()));
+    assert_eq!(eval_str("(#{:a :b} :c)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(#{1 2} 2)", &env).unwrap(), Value::Number(2.0));
+
+    assert!(eval_str("(#{:a})", &env).is_err());
+}
+
+#[test]
+fn test_other_values_not_callable() {
+    let env = standard_env();
+
+    assert!(matches!(eval_str("(1 2)", &env).unwrap_err().cause(), EvalError::NotCallable(Value::Number(_))));
+    assert!(matches!(eval_str("(\"s\" 0)", &env).unwrap_err().cause(), EvalError::NotCallable(Value::String(_))));
+    assert!(matches!(eval_str("(nil)", &env).unwrap_err().cause(), EvalError::NotCallable(Value::Nil)));
+}
+
+#[test]
+fn test_call_from_rust_builtin() {
+    use citrine::reader::{call, Function};
+    use std::rc::Rc;
+
+    let env = standard_env();
+
+    // A builtin registered from Rust that calls its function argument twice
+    env.borrow_mut().set(
+        "twice".to_string(),
+        Value::Function(Rc::new(Function::builtin(|args, env| {
+            let once = call(&args[0], vec![args[1].clone()], env)?;
+            call(&args[0], vec![once], env)
+        }))),
+    );
+
+    eval_str("(defn add-three [x] (+ x 3))", &env).unwrap();
+    assert_eq!(eval_str("(twice add-three 1)", &env).unwrap(), Value::Number(7.0));
+    assert_eq!(eval_str("(twice (fn [x] (* x x)) 3)", &env).unwrap(), Value::Number(81.0));
+    assert_eq!(eval_str("(twice :a {:a {:a 1}})", &env).unwrap(), Value::Number(1.0));
+
+    // Errors from the called function propagate
+    assert!(eval_str("(twice (fn [] 1) 1)", &env).is_err());
+
+    // call can also be used directly on evaluated values
+    let add = eval_str("+", &env).unwrap();
+    assert_eq!(
+        call(&add, vec![Value::Number(1.0), Value::Number(2.0)], &env).unwrap(),
+        Value::Number(3.0)
+    );
+    let square = eval_str("(fn [x] (* x x))", &env).unwrap();
+    assert_eq!(call(&square, vec![Value::Number(4.0)], &env).unwrap(), Value::Number(16.0));
+    assert!(matches!(call(&Value::Nil, vec![], &env).unwrap_err().cause(), EvalError::NotCallable(Value::Nil)));
+}
+
+#[test]
+fn test_apply() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(apply + 1 2 [3 4])", &env).unwrap(), Value::Number(10.0));
+    assert_eq!(eval_str("(apply + [1 2])", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(apply + '(1 2))", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(apply list 1 nil)", &env).unwrap(), Value::List(vec![Value::Number(1.0)]));
+    assert_eq!(eval_str("(apply (fn [& xs] xs) [])", &env).unwrap(), Value::Nil);
+    assert_eq!(
+        eval_str("(apply (fn [a b & more] (list a b more)) 1 [2 3 4])", &env).unwrap(),
+        Value::List(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::List(vec![Value::Number(3.0), Value::Number(4.0)]),
+        ])
+    );
+    assert_eq!(eval_str("(apply :a [{:a 1}])", &env).unwrap(), Value::Number(1.0));
+
+    assert!(eval_str("(apply +)", &env).is_err());
+    assert!(matches!(eval_str("(apply + 1 2)", &env), Err(EvalError::TypeError { .. })));
+    assert!(matches!(eval_str("(apply 1 [2])", &env).unwrap_err().cause(), EvalError::NotCallable(_)));
+}
+
+#[test]
+fn test_dynamic_binding() {
+    let env = standard_env();
+    eval_str("(def ^:dynamic *level* 0)", &env).unwrap();
+
+    // A function defined outside the binding sees the rebound value
+    eval_str("(defn current-level [] *level*)", &env).unwrap();
+    assert_eq!(eval_str("(current-level)", &env).unwrap(), Value::Number(0.0));
+    assert_eq!(eval_str("(binding [*level* 1] (current-level))", &env).unwrap(), Value::Number(1.0));
+
+    // The root value is restored afterwards
+    assert_eq!(eval_str("(current-level)", &env).unwrap(), Value::Number(0.0));
+    assert_eq!(eval_str("*level*", &env).unwrap(), Value::Number(0.0));
+}
+
+#[test]
+fn test_nested_dynamic_bindings() {
+    let env = standard_env();
+    eval_str("(def ^:dynamic *a* :root-a)", &env).unwrap();
+    eval_str("(def ^:dynamic *b* :root-b)", &env).unwrap();
+    eval_str("(defn both [] (list *a* *b*))", &env).unwrap();
+
+    let kw = |k: &str| Value::Keyword(k.to_string());
+    let result = eval_str(
+        "(binding [*a* :outer-a]
+           (list (both)
+                 (binding [*a* :inner-a *b* :inner-b] (both))
+                 (both)))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        Value::List(vec![
+            Value::List(vec![kw("outer-a"), kw("root-b")]),
+            Value::List(vec![kw("inner-a"), kw("inner-b")]),
+            Value::List(vec![kw("outer-a"), kw("root-b")]),
+        ])
+    );
+
+    // Values are evaluated before any var is rebound
+    assert_eq!(
+        eval_str("(binding [*a* :x *b* *a*] (both))", &env).unwrap(),
+        Value::List(vec![kw("x"), kw("root-a")])
+    );
+}
+
+#[test]
+fn test_dynamic_binding_restored_after_error() {
+    let env = standard_env();
+    eval_str("(def ^:dynamic *x* 1)", &env).unwrap();
+
+    assert!(eval_str("(binding [*x* 2] (throw :fail))", &env).is_err());
+    assert_eq!(eval_str("*x*", &env).unwrap(), Value::Number(1.0));
+
+    // Also when the error is caught outside the binding
+    assert_eq!(
+        eval_str("(try (binding [*x* 2] (undefined-fn)) (catch e *x*))", &env).unwrap(),
+        Value::Number(1.0)
+    );
+    assert_eq!(
+        eval_str("(binding [*x* 3] (try (binding [*x* 4] (throw :fail)) (catch e *x*)))", &env).unwrap(),
+        Value::Number(3.0)
+    );
+}
+
+#[test]
+fn test_dynamic_binding_lexical_locals() {
+    let env = standard_env();
+    eval_str("(def ^:dynamic *x* 1)", &env).unwrap();
+
+    // Locals shadow dynamic bindings, as they shadow root bindings
+    assert_eq!(eval_str("(let [*x* :local] (binding [*x* 2] *x*))", &env).unwrap(), Value::Keyword("local".to_string()));
+
+    // Redefining the var changes its root value, not the rebound value
+    assert_eq!(eval_str("(binding [*x* 2] (def *x* 5) *x*)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("*x*", &env).unwrap(), Value::Number(5.0));
+}
+
+#[test]
+fn test_binding_errors() {
+    let env = standard_env();
+    eval_str("(def not-dynamic 1)", &env).unwrap();
+    eval_str("(def ^:dynamic *x* 1)", &env).unwrap();
+
+    match eval_str("(binding [not-dynamic 2] not-dynamic)", &env) {
+        Err(EvalError::Other(msg)) => assert!(msg.contains("not-dynamic"), "{}", msg),
+        other => panic!("expected an error, got {:?}", other),
+    }
+    assert!(eval_str("(binding [undefined 2] 1)", &env).is_err());
+    assert!(eval_str("(binding [*x*] 1)", &env).is_err());
+    assert!(eval_str("(binding (*x* 1) 1)", &env).is_err());
+
+    // ^:dynamic false does not make a var dynamic
+    eval_str("(def ^{:dynamic false} *y* 1)", &env).unwrap();
+    assert!(eval_str("(binding [*y* 2] *y*)", &env).is_err());
+}
+
+#[test]
+fn test_with_meta_evaluates_form() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("^:private [1 (+ 1 1)]", &env).unwrap(),
+        Value::Vector(vec![Value::Number(1.0), Value::Number(2.0)])
+    );
+    assert!(eval_str("(with-meta [1] 2)", &env).is_err());
+}
+
+#[test]
+fn test_recursion_limit() {
+    let env = standard_env();
+    eval_str("(defn depth [n] (if (= n 0) 0 (+ 1 (depth (- n 1)))))", &env).unwrap();
+
+    // Deep non-tail recursion fails with an error instead of overflowing the stack
+    match eval_str("(depth 1000000)", &env) {
+        Err(EvalError::RecursionLimit { depth }) => {
+            assert_eq!(depth, env.borrow().context().max_depth() + 1)
+        }
+        other => panic!("expected a recursion limit error, got {:?}", other),
+    }
+
+    // The interpreter remains usable afterwards, at full depth
+    assert_eq!(eval_str("(depth 10)", &env).unwrap(), Value::Number(10.0));
+    assert!(eval_str("(depth 1000000)", &env).is_err());
+    assert_eq!(eval_str("(depth 20)", &env).unwrap(), Value::Number(20.0));
+
+    // Tail calls do not count towards the limit
+    eval_str("(defn count-down [n] (if (= n 0) :done (count-down (- n 1))))", &env).unwrap();
+    assert_eq!(eval_str("(count-down 10000)", &env).unwrap(), Value::Keyword("done".to_string()));
+}
+
+#[test]
+fn test_recursion_limit_is_configurable() {
+    let env = standard_env();
+    eval_str("(defn depth [n] (if (= n 0) 0 (+ 1 (depth (- n 1)))))", &env).unwrap();
+
+    env.borrow().context().set_max_depth(10);
+    assert!(matches!(eval_str("(depth 20)", &env), Err(EvalError::RecursionLimit { .. })));
+    assert_eq!(eval_str("(depth 5)", &env).unwrap(), Value::Number(5.0));
+
+    // Each standard environment has its own limit
+    let other = standard_env();
+    eval_str("(defn depth [n] (if (= n 0) 0 (+ 1 (depth (- n 1)))))", &other).unwrap();
+    assert_eq!(eval_str("(depth 20)", &other).unwrap(), Value::Number(20.0));
+}
+
+#[test]
+fn test_recursion_limit_can_be_caught() {
+    let env = standard_env();
+    eval_str("(defn depth [n] (if (= n 0) 0 (+ 1 (depth (- n 1)))))", &env).unwrap();
+
+    assert_eq!(
+        eval_str("(try (depth 1000000) (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("recursion-limit".to_string())
+    );
+
+    // Through builtins that call back into Citrine functions too
+    eval_str("(defn depth-apply [n] (if (= n 0) 0 (+ 1 (apply depth-apply [(- n 1)]))))", &env).unwrap();
+    assert!(matches!(
+        eval_str("(depth-apply 1000000)", &env),
+        Err(EvalError::RecursionLimit { .. })
+    ));
+}
+
+#[test]
+fn test_recursion_through_builtins_is_bounded_by_the_depth_limit() {
+    let env = standard_env();
+    eval_str("(defn f [n] (if (= n 0) 0 (+ 1 (apply f [(- n 1)]))))", &env).unwrap();
+
+    // Each call back from apply starts an evaluator on top of the others, which
+    // is only bounded by the configurable depth limit
+    assert_eq!(eval_str("(f 2000)", &env).unwrap(), Value::Number(2000.0));
+
+    env.borrow().context().set_max_depth(100);
+    assert_eq!(eval_str("(f 20)", &env).unwrap(), Value::Number(20.0));
+    match eval_str("(f 120)", &env).unwrap_err().cause() {
+        EvalError::RecursionLimit { depth } => assert_eq!(*depth, 101),
+        other => panic!("expected a recursion limit error, got {:?}", other),
+    }
+}
+
+/// Returns the source of `(+ 1 (+ 1 ... (+ 1 0)))` nested `levels` deep
+fn nested_sum(levels: usize) -> String {
+    format!("{}0{}", "(+ 1 ".repeat(levels), ")".repeat(levels))
+}
+
+#[test]
+fn test_deeply_nested_expression() {
+    let env = standard_env();
+    env.borrow().context().set_max_depth(200_000);
+
+    // Nesting is bounded by the depth limit, not by the Rust stack, from
+    // parsing through evaluation
+    let source = nested_sum(100_000);
+    assert_eq!(eval_str(&source, &env).unwrap(), Value::Number(100_000.0));
+
+    // Past the limit it is still an error rather than an overflow
+    env.borrow().context().set_max_depth(50_000);
+    assert!(matches!(eval_str(&source, &env), Err(EvalError::RecursionLimit { .. })));
+
+    // Reading and dropping a deeply nested form does not overflow either
+    let form = read_str(&format!("'{}", source)).unwrap();
+    assert!(matches!(&form, Value::List(items) if items.len() == 2));
+    drop(form);
+}
+
+#[test]
+fn test_large_quoted_list() {
+    let env = standard_env();
+    let numbers: Vec<String> = (0..100_000).map(|i| i.to_string()).collect();
+    let source = format!("(quote ({}))", numbers.join(" "));
+    match &eval_str(&source, &env).unwrap() {
+        Value::List(items) => {
+            assert_eq!(items.len(), 100_000);
+            assert_eq!(items[99_999], Value::Number(99_999.0));
+        }
+        other => panic!("expected a list, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_set_updates_captured_local() {
+    let env = standard_env();
+    eval_str(
+        "(def counter (let [n 0] (fn [] (set! n (+ n 1)))))",
+        &env,
+    ).unwrap();
+
+    assert_eq!(eval_str("(counter)", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(counter)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(counter)", &env).unwrap(), Value::Number(3.0));
+
+    // setq creates a shadowing local instead
+    eval_str("(def total 0)", &env).unwrap();
+    eval_str("((fn [] (setq total 10)))", &env).unwrap();
+    assert_eq!(eval_str("total", &env).unwrap(), Value::Number(0.0));
+    eval_str("((fn [] (set! total 10)))", &env).unwrap();
+    assert_eq!(eval_str("total", &env).unwrap(), Value::Number(10.0));
+}
+
+#[test]
+fn test_set_unbound_symbol() {
+    let env = standard_env();
+
+    assert!(matches!(
+        eval_str("(set! nowhere 1)", &env),
+        Err(EvalError::UnboundSymbol(s)) if s == "nowhere"
+    ));
+    assert!(eval_str("nowhere", &env).is_err());
+}
+
+#[test]
+fn test_set_dynamic_binding() {
+    let env = standard_env();
+    eval_str("(def ^:dynamic *level* 0)", &env).unwrap();
+
+    assert_eq!(
+        eval_str("(binding [*level* 1] (set! *level* 2) *level*)", &env).unwrap(),
+        Value::Number(2.0)
+    );
+    // Only the binding was updated
+    assert_eq!(eval_str("*level*", &env).unwrap(), Value::Number(0.0));
+}
+
+#[test]
+fn test_comment() {
+    let env = standard_env();
+
+    // Unbound symbols in the body are never looked up
+    assert_eq!(
+        eval_str("(comment (this is (not valid) code maybe))", &env).unwrap(),
+        Value::Nil
+    );
+
+    // Side effects in the body do not run
+    eval_str("(def x 1)", &env).unwrap();
+    eval_str("(comment (def x 2) (throw :boom))", &env).unwrap();
+    assert_eq!(eval_str("x", &env).unwrap(), Value::Number(1.0));
+
+    // A comment produced by a macro expansion is ignored too
+    eval_str("(defmacro ignore [& body] `(comment ,@body))", &env).unwrap();
+    assert_eq!(eval_str("(ignore (def x 3) (undefined-fn))", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("x", &env).unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_read_string_and_eval() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("(read-string \"(+ 1 2)\")", &env).unwrap(),
+        eval_str("'(+ 1 2)", &env).unwrap()
+    );
+    assert_eq!(eval_str("(eval '(+ 1 2))", &env).unwrap(), Value::Number(3.0));
+    assert_eq!(eval_str("(eval (read-string \"(+ 1 2)\"))", &env).unwrap(), Value::Number(3.0));
+
+    // eval sees the root environment, not the caller's locals
+    eval_str("(def x 1)", &env).unwrap();
+    assert_eq!(eval_str("(let [x 2] (eval 'x))", &env).unwrap(), Value::Number(1.0));
+
+    // Malformed input is a catchable error
+    assert!(matches!(
+        eval_str("(read-string \"(+ 1\")", &env),
+        Err(EvalError::SyntaxError(_))
+    ));
+    assert_eq!(
+        eval_str("(try (read-string \"(+ 1\") (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("syntax-error".to_string())
+    );
+}
+
+#[test]
+fn test_resolve_and_bound() {
+    let env = standard_env();
+    eval_str("(def answer 42)", &env).unwrap();
+
+    assert_eq!(eval_str("(resolve 'answer)", &env).unwrap(), Value::Number(42.0));
+    assert_eq!(eval_str("(resolve 'missing)", &env).unwrap(), Value::Nil);
+    assert_eq!(eval_str("(let [local 1] (resolve 'local))", &env).unwrap(), Value::Number(1.0));
+
+    assert_eq!(eval_str("(bound? 'answer)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(bound? '+)", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(bound? 'missing)", &env).unwrap(), Value::Boolean(false));
+    assert!(eval_str("(bound? \"answer\")", &env).is_err());
+}
+
+#[test]
+fn test_env_keys() {
+    let env = standard_env();
+    eval_str("(def answer 42)", &env).unwrap();
+
+    let keys = |code: &str| match &eval_str(code, &env).unwrap() {
+        Value::Vector(keys) => keys.clone(),
+        other => panic!("expected a vector, got {:?}", other),
+    };
+    let has = |keys: &[Value], name: &str| keys.contains(&Value::Symbol(name.to_string()));
+
+    // Builtins and globals are visible everywhere, locals only in their scope
+    let global = keys("(env-keys)");
+    assert!(has(&global, "+") && has(&global, "answer"));
+    assert!(!has(&global, "local"));
+
+    let local = keys("(let [local 1 answer 2] (env-keys))");
+    assert!(has(&local, "+") && has(&local, "answer") && has(&local, "local"));
+
+    // Sorted, and a shadowed name only appears once
+    let mut sorted = local.clone();
+    sorted.sort_by_key(|key| key.to_string());
+    assert_eq!(local, sorted);
+    assert_eq!(local.iter().filter(|key| **key == Value::Symbol("answer".to_string())).count(), 1);
+
+    // The same names are available from Rust
+    assert_eq!(env.borrow().all_keys().len(), global.len());
+}
+
+#[test]
+fn test_call_errors_name_the_call() {
+    let env = standard_env();
+    eval_str("(def foo 1)", &env).unwrap();
+    eval_str("(defn one-arg [x] x)", &env).unwrap();
+
+    let error = eval_str("(foo 1 2)", &env).unwrap_err();
+    assert!(matches!(
+        &error,
+        EvalError::Call { function, args, .. } if function == "foo" && args == "1 2"
+    ));
+    assert_eq!(error.to_string(), "Not callable: 1 in call (foo 1 2)");
+
+    // A map literal written with parens instead of braces
+    let error = eval_str("(:a 1 :b 2)", &env).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Arity mismatch: expected 2 arguments, got 3 in call (:a 1 :b 2)"
+    );
+
+    let error = eval_str("(one-arg (+ 1 2) 3)", &env).unwrap_err();
+    assert!(matches!(error.cause(), EvalError::ArityMismatch { expected: 1, got: 2 }));
+    assert!(error.to_string().ends_with("in call (one-arg (+ 1 2) 3)"));
+
+    // Long argument forms are truncated
+    let error = eval_str("(one-arg \"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\" 2)", &env).unwrap_err();
+    assert!(error.to_string().ends_with("...)"));
+
+    // The call in the body of a function is the one reported
+    eval_str("(defn calls-foo [] (foo))", &env).unwrap();
+    assert!(matches!(
+        eval_str("(calls-foo)", &env).unwrap_err(),
+        EvalError::Call { function, .. } if function == "foo"
+    ));
+
+    // catch sees the type of the underlying error
+    assert_eq!(
+        eval_str("(try (foo) (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("not-callable".to_string())
+    );
+}
+
+#[test]
+fn test_call_errors_from_callbacks_name_the_callback() {
+    let env = standard_env();
+    eval_str("(defn two-args [a b] a)", &env).unwrap();
+
+    // The function apply calls has the wrong arity, not apply
+    let error = eval_str("(apply (fn [a b] a) [1])", &env).unwrap_err();
+    assert!(matches!(error.cause(), EvalError::ArityMismatch { expected: 2, got: 1 }));
+    assert_eq!(error.to_string(), "Arity mismatch: expected 2 arguments, got 1 in call (#<function> 1)");
+
+    let error = eval_str("(apply two-args [1])", &env).unwrap_err();
+    assert!(matches!(
+        &error,
+        EvalError::Call { function, args, .. } if function == "two-args" && args == "1"
+    ));
+
+    // So do builtins and values that are called back
+    let error = eval_str("(apply boolean [1 2])", &env).unwrap_err();
+    assert!(matches!(&error, EvalError::Call { function, .. } if function != "apply"));
+    let error = eval_str("(apply 5 [1 2])", &env).unwrap_err();
+    assert!(matches!(
+        &error,
+        EvalError::Call { function, args, .. } if function == "5" && args == "1 2"
+    ));
+    let error = eval_str("(apply :a [{} 1 2])", &env).unwrap_err();
+    assert!(matches!(&error, EvalError::Call { function, .. } if function == ":a"));
+
+    // Errors of the builtin's own call still name it
+    let error = eval_str("(apply two-args)", &env).unwrap_err();
+    assert!(matches!(
+        &error,
+        EvalError::Call { function, args, .. } if function == "apply" && args == "two-args"
+    ));
+}
+
+#[test]
+fn test_delay_runs_body_once() {
+    let env = standard_env();
+    eval_str("(def runs 0)", &env).unwrap();
+    eval_str("(def d (delay (set! runs (+ runs 1)) :done))", &env).unwrap();
+
+    // The body does not run until the delay is forced
+    assert_eq!(eval_str("runs", &env).unwrap(), Value::Number(0.0));
+    assert_eq!(eval_str("(realized? d)", &env).unwrap(), Value::Boolean(false));
+
+    for _ in 0..3 {
+        assert_eq!(eval_str("(force d)", &env).unwrap(), Value::Keyword("done".to_string()));
+    }
+    assert_eq!(eval_str("(deref d)", &env).unwrap(), Value::Keyword("done".to_string()));
+    assert_eq!(eval_str("runs", &env).unwrap(), Value::Number(1.0));
+    assert_eq!(eval_str("(realized? d)", &env).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn test_delay_closes_over_locals() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("(force (let [x 20] (delay (+ x 1))))", &env).unwrap(),
+        Value::Number(21.0)
+    );
+    assert_eq!(eval_str("(force (delay))", &env).unwrap(), Value::Nil);
+
+    // Forcing anything else returns it, but only delays can be dereferenced
+    assert_eq!(eval_str("(force 5)", &env).unwrap(), Value::Number(5.0));
+    assert!(eval_str("(deref 5)", &env).is_err());
+    assert_eq!(eval_str("(delay? (delay 1))", &env).unwrap(), Value::Boolean(true));
+    assert_eq!(eval_str("(delay? 1)", &env).unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn test_failed_delay_stays_pending() {
+    let env = standard_env();
+    eval_str("(def attempts 0)", &env).unwrap();
+    eval_str("(def d (delay (set! attempts (+ attempts 1)) (throw :failed)))", &env).unwrap();
+
+    assert!(eval_str("(force d)", &env).is_err());
+    assert!(eval_str("(force d)", &env).is_err());
+    assert_eq!(eval_str("attempts", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(realized? d)", &env).unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn test_if_let() {
+    let env = standard_env();
+    eval_str("(def m {:a 1 :b false})", &env).unwrap();
+
+    assert_eq!(eval_str("(if-let [x (:a m)] (+ x 1) :none)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(if-let [x (:c m)] (+ x 1) :none)", &env).unwrap(), Value::Keyword("none".to_string()));
+    assert_eq!(eval_str("(if-let [x (:c m)] x)", &env).unwrap(), Value::Nil);
+
+    // false is falsey too
+    assert_eq!(eval_str("(if-let [x (:b m)] :bound :unbound)", &env).unwrap(), Value::Keyword("unbound".to_string()));
+
+    // The binding does not leak into the else branch
+    assert!(matches!(
+        eval_str("(if-let [y nil] 1 y)", &env),
+        Err(EvalError::UnboundSymbol(s)) if s == "y"
+    ));
+
+    // Patterns are destructured
+    assert_eq!(eval_str("(if-let [[a b] [1 2]] (+ a b))", &env).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_when_let() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(when-let [x 5] (setq y x) (* x 2))", &env).unwrap(), Value::Number(10.0));
+    assert_eq!(eval_str("(when-let [x false] (throw :unreachable))", &env).unwrap(), Value::Nil);
+}
+
+#[test]
+fn test_if_let_malformed_bindings() {
+    let env = standard_env();
+
+    assert!(matches!(eval_str("(if-let [x 1 y 2] x)", &env), Err(EvalError::SyntaxError(_))));
+    assert!(matches!(eval_str("(when-let [x] x)", &env), Err(EvalError::SyntaxError(_))));
+    assert!(matches!(eval_str("(if-let (x 1) x)", &env), Err(EvalError::SyntaxError(_))));
+    assert!(eval_str("(if-let [x 1])", &env).is_err());
+}
+
+#[test]
+fn test_assert() {
+    let env = standard_env();
+
+    assert_eq!(eval_str("(assert (= 1 1))", &env).unwrap(), Value::Nil);
+
+    let error = eval_str("(assert (= 1 2))", &env).unwrap_err();
+    assert_eq!(error.to_string(), "Assert failed: (= 1 2)");
+    let error = eval_str("(assert (= 1 2) \"numbers differ\")", &env).unwrap_err();
+    assert_eq!(error.to_string(), "Assert failed: numbers differ: (= 1 2)");
+
+    assert_eq!(
+        eval_str("(try (assert false) (catch {:keys [type]} type))", &env).unwrap(),
+        Value::Keyword("assertion-failed".to_string())
+    );
+
+    // Checks can be turned off
+    assert_eq!(eval_str("(binding [*assert* false] (assert false))", &env).unwrap(), Value::Nil);
+    assert!(eval_str("(assert false)", &env).is_err());
+}
+
+#[test]
+fn test_pre_and_post_conditions() {
+    let env = standard_env();
+    eval_str("(defn halve [x] {:pre [(> x 0)] :post [(< % x)]} (/ x 2))", &env).unwrap();
+    eval_str("(defn broken [x] {:post [(> % x)]} (- x 1))", &env).unwrap();
+
+    assert_eq!(eval_str("(halve 8)", &env).unwrap(), Value::Number(4.0));
+    assert_eq!(
+        eval_str("(halve 0)", &env).unwrap_err().to_string(),
+        "Assert failed: (> x 0)"
+    );
+    assert_eq!(
+        eval_str("(broken 1)", &env).unwrap_err().to_string(),
+        "Assert failed: (> % x)"
+    );
+
+    // Also on anonymous functions, and with destructured parameters
+    assert_eq!(
+        eval_str("((fn [[a b]] {:pre [(< a b)]} (- b a)) [1 3])", &env).unwrap(),
+        Value::Number(2.0)
+    );
+    assert!(eval_str("((fn [[a b]] {:pre [(< a b)]} (- b a)) [3 1])", &env).is_err());
+
+    // A map that is the whole body is returned
+    assert_eq!(
+        eval_str("((fn [] {:pre [false]}))", &env).unwrap(),
+        eval_str("{:pre [false]}", &env).unwrap()
+    );
+
+    // Conditions are skipped while *assert* is false
+    assert_eq!(eval_str("(binding [*assert* false] (halve 0))", &env).unwrap(), Value::Number(0.0));
+}
+
+#[test]
+fn test_named_fn() {
+    let env = standard_env();
+
+    assert_eq!(
+        eval_str("((fn fact [n] (if (= n 0) 1 (* n (fact (- n 1))))) 5)", &env).unwrap(),
+        Value::Number(120.0)
+    );
+    assert_eq!(env.borrow().get("fact"), None);
+
+    // The name shadows outer bindings only inside the function
+    eval_str("(def f :outer)", &env).unwrap();
+    assert!(matches!(eval_str("((fn f [] f))", &env).unwrap(), Value::Function(_)));
+    assert_eq!(eval_str("f", &env).unwrap(), Value::Keyword("outer".to_string()));
+
+    // The name is kept as the function's name
+    match &eval_str("(fn named [a] a)", &env).unwrap() {
+        Value::Function(f) => assert_eq!(f.name.as_deref(), Some("named")),
+        other => panic!("expected a function, got {:?}", other),
+    }
+}
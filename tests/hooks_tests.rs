@@ -0,0 +1,128 @@
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+use citrine::{eval_str, standard_env};
+use citrine::reader::{Environment, EvalError, EvalHooks, Tracer, Value};
+
+/// Counts the forms evaluated
+#[derive(Default)]
+struct Counter {
+    before: Cell<usize>,
+    after: Cell<usize>,
+}
+
+impl EvalHooks for Counter {
+    fn before_eval(&self, _form: &Value, _env: &Rc<RefCell<Environment>>, _depth: usize) -> Result<(), EvalError> {
+        self.before.set(self.before.get() + 1);
+        Ok(())
+    }
+
+    fn after_eval(&self, _form: &Value, _result: &Result<Value, EvalError>, _duration: Duration) -> Result<(), EvalError> {
+        self.after.set(self.after.get() + 1);
+        Ok(())
+    }
+}
+
+/// Aborts evaluation when a symbol is about to be evaluated
+struct Forbid(&'static str);
+
+impl EvalHooks for Forbid {
+    fn before_eval(&self, form: &Value, _env: &Rc<RefCell<Environment>>, _depth: usize) -> Result<(), EvalError> {
+        match form {
+            Value::Symbol(s) if s == self.0 => Err(EvalError::Other(format!("{} is forbidden", s))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A writer whose output the test can read back
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_counting_hook() {
+    let env = standard_env();
+    let counter = Rc::new(Counter::default());
+    env.borrow().context().set_hooks(Some(counter.clone()));
+
+    // The call, the `+` symbol, 1, the nested call, `*`, 2 and 3
+    assert_eq!(eval_str("(+ 1 (* 2 3))", &env).unwrap(), Value::Number(7.0));
+    assert_eq!(counter.before.get(), 7);
+    assert_eq!(counter.after.get(), 7);
+
+    // Removing the hooks stops the counting
+    env.borrow().context().set_hooks(None);
+    assert_eq!(eval_str("(+ 1 (* 2 3))", &env).unwrap(), Value::Number(7.0));
+    assert_eq!(counter.before.get(), 7);
+}
+
+#[test]
+fn test_hook_aborts_evaluation() {
+    let env = standard_env();
+    env.borrow().context().set_hooks(Some(Rc::new(Forbid("secret"))));
+
+    eval_str("(def secret 42)", &env).unwrap();
+    match eval_str("(+ 1 secret)", &env) {
+        Err(EvalError::Aborted(error)) => {
+            assert!(matches!(*error, EvalError::Other(message) if message == "secret is forbidden"))
+        }
+        other => panic!("expected an abort, got {:?}", other),
+    }
+    assert_eq!(eval_str("(+ 1 2)", &env).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_try_does_not_catch_hook_aborts() {
+    let env = standard_env();
+    env.borrow().context().set_hooks(Some(Rc::new(Forbid("secret"))));
+
+    eval_str("(def secret 42)", &env).unwrap();
+    eval_str("(def cleaned false)", &env).unwrap();
+    let result = eval_str("(try (+ 1 secret) (catch e nil) (finally (def cleaned true)))", &env);
+    assert!(matches!(result, Err(EvalError::Aborted(_))), "{:?}", result);
+    // The finally clause still runs on the way out
+    assert_eq!(eval_str("cleaned", &env).unwrap(), Value::Boolean(true));
+
+    // Aborts from inside a function called in the try body are not caught either
+    eval_str("(defn peek [] (+ 1 secret))", &env).unwrap();
+    let result = eval_str("(try (peek) (catch e :caught))", &env);
+    assert!(matches!(result, Err(EvalError::Aborted(_))), "{:?}", result);
+
+    // Other errors are still caught
+    assert_eq!(eval_str("(try (throw :oops) (catch e e))", &env).unwrap(), Value::Keyword("oops".to_string()));
+}
+
+#[test]
+fn test_tracer_prints_calls() {
+    let env = standard_env();
+    let buffer = SharedBuffer::default();
+    env.borrow().context().set_hooks(Some(Rc::new(Tracer::new(Box::new(buffer.clone())))));
+
+    eval_str("(+ 1 (* 2 3))", &env).unwrap();
+    let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+    assert_eq!(output, "(+ 1 (* 2 3))\n  (* 2 3)\n  => 6\n=> 7\n");
+}
+
+#[test]
+fn test_set_trace() {
+    let env = standard_env();
+    let context = env.borrow().context();
+
+    context.set_trace(true);
+    assert!(context.has_hooks());
+    context.set_trace(false);
+    assert!(!context.has_hooks());
+}
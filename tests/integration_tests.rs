@@ -175,7 +175,7 @@ fn test_data_structures() {
     
     // Test list
     let result = eval_str("(list 1 2 3)", &env).unwrap();
-    match result {
+    match &result {
         Value::List(items) => {
             assert_eq!(items.len(), 3);
             assert_eq!(items[0], Value::Number(1.0));
@@ -187,7 +187,7 @@ fn test_data_structures() {
     
     // Test vector
     let result = eval_str("[1 2 3]", &env).unwrap();
-    match result {
+    match &result {
         Value::Vector(items) => {
             assert_eq!(items.len(), 3);
             assert_eq!(items[0], Value::Number(1.0));
@@ -199,7 +199,7 @@ fn test_data_structures() {
     
     // Test map
     let result = eval_str("{:a 1 :b 2}", &env).unwrap();
-    match result {
+    match &result {
         Value::Map(map) => {
             assert_eq!(map.len(), 2);
             assert_eq!(
@@ -216,7 +216,7 @@ fn test_data_structures() {
     
     // Test set
     let result = eval_str("#{1 2 3}", &env).unwrap();
-    match result {
+    match &result {
         Value::Set(set) => {
             assert_eq!(set.len(), 3);
             assert!(set.contains(&Value::Number(1.0)));
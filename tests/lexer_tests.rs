@@ -94,3 +94,75 @@ fn test_lexer_complex() {
     assert_eq!(lexer.next_token().kind, TokenKind::Eof);
 }
 
+
+#[test]
+fn test_lexer_ampersand_symbol() {
+    let input = "[x & more]";
+    let mut lexer = Lexer::new(input);
+
+    assert_eq!(lexer.next_token().kind, TokenKind::LeftBracket);
+    assert_eq!(lexer.next_token().kind, TokenKind::Symbol); // x
+    let token = lexer.next_token();
+    assert_eq!(token.kind, TokenKind::Symbol);
+    assert_eq!(token.text, "&");
+    assert_eq!(lexer.next_token().kind, TokenKind::Symbol); // more
+    assert_eq!(lexer.next_token().kind, TokenKind::RightBracket);
+    assert_eq!(lexer.next_token().kind, TokenKind::Eof);
+}
+
+#[test]
+fn test_lexer_auto_gensym_symbol() {
+    let input = "x# #{1}";
+    let mut lexer = Lexer::new(input);
+
+    let token = lexer.next_token();
+    assert_eq!(token.kind, TokenKind::Symbol);
+    assert_eq!(token.text, "x#");
+    assert_eq!(lexer.next_token().kind, TokenKind::HashLeftBrace);
+}
+
+#[test]
+fn test_lexer_dotted_symbol() {
+    let input = "my.util 1.5";
+    let mut lexer = Lexer::new(input);
+
+    let token = lexer.next_token();
+    assert_eq!(token.kind, TokenKind::Symbol);
+    assert_eq!(token.text, "my.util");
+    let token = lexer.next_token();
+    assert_eq!(token.kind, TokenKind::Number);
+    assert_eq!(token.text, "1.5");
+}
+
+#[test]
+fn test_lexer_comparison_symbols() {
+    let mut lexer = Lexer::new("<= >= < > =");
+
+    for expected in ["<=", ">=", "<", ">", "="] {
+        let token = lexer.next_token();
+        assert_eq!(token.kind, TokenKind::Symbol);
+        assert_eq!(token.text, expected);
+    }
+    assert_eq!(lexer.next_token().kind, TokenKind::Eof);
+}
+
+#[test]
+fn test_lexer_negative_numbers() {
+    // A minus sign directly followed by a digit starts a number
+    let mut lexer = Lexer::new("-7 -2.5 - 7 -foo -> x-1");
+
+    for (kind, text) in [
+        (TokenKind::Number, "-7"),
+        (TokenKind::Number, "-2.5"),
+        (TokenKind::Symbol, "-"),
+        (TokenKind::Number, "7"),
+        (TokenKind::Symbol, "-foo"),
+        (TokenKind::Symbol, "->"),
+        (TokenKind::Symbol, "x-1"),
+    ] {
+        let token = lexer.next_token();
+        assert_eq!(token.kind, kind);
+        assert_eq!(token.text, text);
+    }
+    assert_eq!(lexer.next_token().kind, TokenKind::Eof);
+}
@@ -0,0 +1,153 @@
+use citrine::{eval_str, eval_str_with_limits, standard_env};
+use citrine::reader::{EvalError, Limits, Value};
+
+fn steps(max_steps: usize) -> Limits {
+    Limits {
+        max_steps: Some(max_steps),
+        max_depth: None,
+    }
+}
+
+#[test]
+fn test_infinite_loop_exhausts_budget() {
+    let env = standard_env();
+
+    assert!(matches!(
+        eval_str_with_limits("(loop [] (recur))", &env, steps(10_000)),
+        Err(EvalError::BudgetExceeded { steps: 10_000 })
+    ));
+
+    // Infinite tail recursion too
+    eval_str("(defn spin [n] (spin (+ n 1)))", &env).unwrap();
+    assert!(matches!(
+        eval_str_with_limits("(spin 0)", &env, steps(10_000)),
+        Err(EvalError::BudgetExceeded { .. })
+    ));
+}
+
+#[test]
+fn test_exponential_blowup_exhausts_budget() {
+    let env = standard_env();
+    eval_str("(defn fib [n] (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))", &env).unwrap();
+
+    assert!(matches!(
+        eval_str_with_limits("(fib 40)", &env, steps(50_000)),
+        Err(EvalError::BudgetExceeded { .. })
+    ));
+}
+
+#[test]
+fn test_generous_budget_does_not_affect_result() {
+    let env = standard_env();
+    eval_str("(defn fib [n] (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))", &env).unwrap();
+
+    let unlimited = eval_str("(fib 15)", &env).unwrap();
+    let limited = eval_str_with_limits("(fib 15)", &env, steps(1_000_000)).unwrap();
+    assert_eq!(limited, unlimited);
+    assert_eq!(limited, Value::Number(610.0));
+
+    // The budget only applies to the call it was given for
+    assert_eq!(env.borrow().context().fuel(), None);
+    eval_str("(loop [i 0] (if (< i 100000) (recur (+ i 1)) i))", &env).unwrap();
+}
+
+#[test]
+fn test_budget_counts_evaluated_forms() {
+    let env = standard_env();
+
+    // A literal is a single step, and (+ 1 2) is four: the call, +, 1 and 2
+    assert_eq!(eval_str_with_limits("1", &env, steps(1)).unwrap(), Value::Number(1.0));
+    assert!(eval_str_with_limits("(+ 1 2)", &env, steps(3)).is_err());
+    assert_eq!(eval_str_with_limits("(+ 1 2)", &env, steps(4)).unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_budget_honored_in_callbacks_from_builtins() {
+    let env = standard_env();
+    eval_str("(defn spin [] (loop [] (recur)))", &env).unwrap();
+
+    assert!(matches!(
+        eval_str_with_limits("(apply spin [])", &env, steps(1_000)),
+        Err(EvalError::BudgetExceeded { .. })
+    ));
+
+    // Collection builtins take a step for each element, even when the
+    // function they call is a builtin that evaluates no forms
+    eval_str("(def xs [1 2 3 4 5 6 7 8 9 10])", &env).unwrap();
+    assert!(matches!(
+        eval_str_with_limits("(map inc xs)", &env, steps(8)),
+        Err(EvalError::BudgetExceeded { .. })
+    ));
+    assert!(eval_str_with_limits("(map inc xs)", &env, steps(20)).is_ok());
+    assert!(matches!(
+        eval_str_with_limits("(reduce + xs)", &env, steps(8)),
+        Err(EvalError::BudgetExceeded { .. })
+    ));
+    assert_eq!(eval_str_with_limits("(reduce + xs)", &env, steps(20)).unwrap(), Value::Number(55.0));
+}
+
+#[test]
+fn test_budget_exceeded_cannot_be_caught_away() {
+    let env = standard_env();
+
+    // Catching the error does not refill the budget, so the loop stops anyway
+    let result = eval_str_with_limits(
+        "(loop [] (try (loop [] (recur)) (catch e nil)) (recur))",
+        &env,
+        steps(1_000),
+    );
+    assert!(matches!(result, Err(EvalError::BudgetExceeded { .. })));
+}
+
+#[test]
+fn test_depth_limit() {
+    let env = standard_env();
+    eval_str("(defn depth [n] (if (= n 0) 0 (+ 1 (depth (- n 1)))))", &env).unwrap();
+    let default_depth = env.borrow().context().max_depth();
+
+    let limits = Limits {
+        max_steps: None,
+        max_depth: Some(10),
+    };
+    assert!(matches!(
+        eval_str_with_limits("(depth 20)", &env, limits),
+        Err(EvalError::RecursionLimit { .. })
+    ));
+    assert_eq!(eval_str_with_limits("(depth 5)", &env, limits).unwrap(), Value::Number(5.0));
+
+    // The previous limit is restored afterwards
+    assert_eq!(env.borrow().context().max_depth(), default_depth);
+    assert_eq!(eval_str("(depth 20)", &env).unwrap(), Value::Number(20.0));
+}
+
+/// Checks that a form over the 100 elements of `xs` takes a step per element
+fn assert_charged_per_element(source: &str) {
+    let env = standard_env();
+    let elements: Vec<String> = (1..=100).map(|n| n.to_string()).collect();
+    eval_str(&format!("(def xs [{}])", elements.join(" ")), &env).unwrap();
+
+    assert!(
+        matches!(eval_str_with_limits(source, &env, steps(50)), Err(EvalError::BudgetExceeded { .. })),
+        "{}",
+        source
+    );
+    assert!(eval_str_with_limits(source, &env, steps(1_000)).is_ok(), "{}", source);
+}
+
+#[test]
+fn test_collection_builtins_take_a_step_per_element() {
+    assert_charged_per_element("(take-while pos? xs)");
+    assert_charged_per_element("(drop-while pos? xs)");
+    assert_charged_per_element("(map inc xs)");
+    assert_charged_per_element("(map + xs xs)");
+    assert_charged_per_element("(filter pos? xs)");
+    assert_charged_per_element("(remove zero? xs)");
+    assert_charged_per_element("(reduce + xs)");
+    assert_charged_per_element("(reduce + 0 xs)");
+    assert_charged_per_element("(some neg? xs)");
+    assert_charged_per_element("(every? pos? xs)");
+    assert_charged_per_element("(not-any? neg? xs)");
+    assert_charged_per_element("(sort xs)");
+    assert_charged_per_element("(sort > xs)");
+    assert_charged_per_element("(sort-by - xs)");
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use citrine::{eval_str, standard_env};
+use citrine::reader::{EvalError, SourceLoader, Value};
+
+/// Serves sources from memory
+struct MemoryLoader {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemoryLoader {
+    fn new(files: &[(&str, &str)]) -> Self {
+        MemoryLoader {
+            files: files
+                .iter()
+                .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SourceLoader for MemoryLoader {
+    fn read_source(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    }
+}
+
+/// Creates an empty temporary directory for a test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("citrine-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_require_between_files() {
+    let dir = temp_dir("require");
+    fs::create_dir_all(dir.join("my")).unwrap();
+    fs::write(dir.join("my/util.ctr"), "(defn double [x] (* x 2))\n(def util-loads 1)").unwrap();
+    fs::write(
+        dir.join("main.ctr"),
+        "(require 'my.util)\n(defn quadruple [x] (double (double x)))\n(quadruple 3)",
+    )
+    .unwrap();
+
+    let env = standard_env();
+    env.borrow().context().set_load_path(vec![dir.clone()]);
+
+    let main = dir.join("main.ctr").display().to_string();
+    let result = eval_str(&format!("(load-file \"{}\")", main), &env).unwrap();
+    assert_eq!(result, Value::Number(12.0));
+    assert_eq!(eval_str("(double 5)", &env).unwrap(), Value::Number(10.0));
+    assert!(env.borrow().context().is_loaded("my.util"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_require_is_idempotent() {
+    let env = standard_env();
+    env.borrow().context().set_loader(Rc::new(MemoryLoader::new(&[(
+        "./counter.ctr",
+        "(setq loads (+ loads 1))",
+    )])));
+
+    eval_str("(def loads 0)", &env).unwrap();
+    assert_eq!(eval_str("(require 'counter)", &env).unwrap(), Value::Nil);
+    eval_str("(require 'counter 'counter)", &env).unwrap();
+    assert_eq!(eval_str("loads", &env).unwrap(), Value::Number(1.0));
+
+    // load-file always evaluates the file again
+    eval_str("(load-file \"./counter.ctr\")", &env).unwrap();
+    assert_eq!(eval_str("loads", &env).unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn test_require_searches_load_path_in_order() {
+    let env = standard_env();
+    env.borrow().context().set_loader(Rc::new(MemoryLoader::new(&[
+        ("lib/a/b.ctr", "(def found :lib)"),
+        ("vendor/a/b.ctr", "(def found :vendor)"),
+    ])));
+    env.borrow().context().set_load_path(vec![
+        PathBuf::from("src"),
+        PathBuf::from("lib"),
+        PathBuf::from("vendor"),
+    ]);
+
+    eval_str("(require 'a.b)", &env).unwrap();
+    assert_eq!(eval_str("found", &env).unwrap(), Value::Keyword("lib".to_string()));
+}
+
+#[test]
+fn test_require_not_found_lists_attempted_paths() {
+    let env = standard_env();
+    env.borrow().context().set_loader(Rc::new(MemoryLoader::new(&[])));
+    env.borrow().context().set_load_path(vec![PathBuf::from("src"), PathBuf::from("lib")]);
+
+    match eval_str("(require 'missing.lib)", &env) {
+        Err(EvalError::Other(msg)) => {
+            assert!(msg.contains("missing.lib"), "{}", msg);
+            assert!(msg.contains(&Path::new("src").join("missing/lib.ctr").display().to_string()), "{}", msg);
+            assert!(msg.contains(&Path::new("lib").join("missing/lib.ctr").display().to_string()), "{}", msg);
+        }
+        other => panic!("expected a not found error, got {:?}", other),
+    }
+    assert!(!env.borrow().context().is_loaded("missing.lib"));
+
+    assert!(eval_str("(load-file \"nowhere.ctr\")", &env).is_err());
+}
+
+#[test]
+fn test_circular_require() {
+    let env = standard_env();
+    env.borrow().context().set_loader(Rc::new(MemoryLoader::new(&[
+        ("./a.ctr", "(require 'b)"),
+        ("./b.ctr", "(require 'c)"),
+        ("./c.ctr", "(require 'a)"),
+    ])));
+
+    match eval_str("(require 'a)", &env) {
+        Err(EvalError::Other(msg)) => assert!(msg.contains("a -> b -> c -> a"), "{}", msg),
+        other => panic!("expected a circular require error, got {:?}", other),
+    }
+
+    // A failed require is not recorded as loaded, and can be retried
+    assert!(!env.borrow().context().is_loaded("a"));
+    assert!(eval_str("(require 'a)", &env).is_err());
+}
+
+#[test]
+fn test_load_errors() {
+    let env = standard_env();
+    env.borrow().context().set_loader(Rc::new(MemoryLoader::new(&[("./bad.ctr", "(undefined-fn)")])));
+
+    assert!(matches!(eval_str("(require 'bad)", &env), Err(EvalError::UnboundSymbol(_))));
+    assert!(eval_str("(require 1)", &env).is_err());
+    assert!(eval_str("(load-file)", &env).is_err());
+    assert!(eval_str("(load-file 'bad)", &env).is_err());
+}
@@ -0,0 +1,240 @@
+use citrine::{eval_str, standard_env};
+use citrine::reader::{EvalError, Value};
+
+#[test]
+fn test_quasiquote() {
+    let env = standard_env();
+    eval_str("(def x 2)", &env).unwrap();
+    eval_str("(def xs '(3 4))", &env).unwrap();
+
+    assert_eq!(
+        eval_str("`(1 ,x ,@xs)", &env).unwrap(),
+        Value::List(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Number(3.0),
+            Value::Number(4.0),
+        ])
+    );
+
+    // Unquoted symbols stay quoted, and vectors splice too
+    assert_eq!(
+        eval_str("`[a ,x ,@nil]", &env).unwrap(),
+        Value::Vector(vec![Value::Symbol("a".to_string()), Value::Number(2.0)])
+    );
+    assert_eq!(
+        eval_str("`(a (b ,x))", &env).unwrap(),
+        Value::List(vec![
+            Value::Symbol("a".to_string()),
+            Value::List(vec![Value::Symbol("b".to_string()), Value::Number(2.0)]),
+        ])
+    );
+
+    // Only sequences can be spliced
+    assert!(eval_str("`(1 ,@x)", &env).is_err());
+}
+
+#[test]
+fn test_unless_macro() {
+    let env = standard_env();
+    eval_str("(def unless (macro [test & body] `(if ,test nil (do ,@body))))", &env).unwrap();
+
+    assert_eq!(eval_str("(unless false 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(unless true 1 2)", &env).unwrap(), Value::Nil);
+
+    // The body of a branch that is not taken is never evaluated
+    assert_eq!(eval_str("(unless true (undefined-fn))", &env).unwrap(), Value::Nil);
+}
+
+#[test]
+fn test_macro_arguments_are_not_evaluated() {
+    let env = standard_env();
+
+    // A macro that returns its argument form quoted sees the form, not its value
+    eval_str("(def quote-it (macro [form] (list 'quote form)))", &env).unwrap();
+    assert_eq!(
+        eval_str("(quote-it (undefined-fn 1))", &env).unwrap(),
+        Value::List(vec![Value::Symbol("undefined-fn".to_string()), Value::Number(1.0)])
+    );
+
+    // A macro that evaluates its argument twice
+    eval_str("(def counter 0)", &env).unwrap();
+    eval_str("(def twice (macro [form] `(do ,form ,form)))", &env).unwrap();
+    eval_str("(twice (setq counter (+ counter 1)))", &env).unwrap();
+    assert_eq!(eval_str("counter", &env).unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn test_macro_expansion_uses_caller_environment() {
+    let env = standard_env();
+    eval_str("(def my-when (macro [test & body] `(if ,test (do ,@body) nil)))", &env).unwrap();
+
+    assert_eq!(eval_str("(let [y 5] (my-when true y))", &env).unwrap(), Value::Number(5.0));
+    assert_eq!(
+        eval_str("((fn [n] (my-when (= n 1) (+ n 1))) 1)", &env).unwrap(),
+        Value::Number(2.0)
+    );
+}
+
+#[test]
+fn test_macro_expanding_to_macro() {
+    let env = standard_env();
+    eval_str("(def inner (macro [x] `(+ ,x 1)))", &env).unwrap();
+    eval_str("(def outer (macro [x] `(inner ,x)))", &env).unwrap();
+
+    assert_eq!(eval_str("(outer 1)", &env).unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn test_macro_in_tail_position_of_loop() {
+    let env = standard_env();
+    eval_str("(def unless (macro [test & body] `(if ,test nil (do ,@body))))", &env).unwrap();
+
+    // Each iteration expands the macro again, which must not count towards the limit
+    let result = eval_str(
+        "(loop [i 0] (unless (= i 5000) (recur (+ i 1))))",
+        &env,
+    )
+    .unwrap();
+    assert_eq!(result, Value::Nil);
+}
+
+#[test]
+fn test_macro_expansion_limit() {
+    let env = standard_env();
+    eval_str("(def forever (macro [] '(forever)))", &env).unwrap();
+
+    match eval_str("(forever)", &env) {
+        Err(EvalError::Other(msg)) => assert!(msg.contains("expansion limit"), "{}", msg),
+        other => panic!("expected expansion limit error, got {:?}", other),
+    }
+
+    // The limit is configurable per environment
+    env.borrow().context().set_max_macro_expansions(2);
+    eval_str("(def a (macro [] '(b)))", &env).unwrap();
+    eval_str("(def b (macro [] '(c)))", &env).unwrap();
+    eval_str("(def c (macro [] 1))", &env).unwrap();
+    assert!(eval_str("(a)", &env).is_err());
+    assert_eq!(eval_str("(b)", &env).unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_macro_arity() {
+    let env = standard_env();
+    eval_str("(def one-arg (macro [x] x))", &env).unwrap();
+
+    assert!(matches!(
+        eval_str("(one-arg 1 2)", &env),
+        Err(EvalError::ArityMismatch { expected: 1, got: 2 })
+    ));
+}
+
+#[test]
+fn test_defmacro() {
+    let env = standard_env();
+
+    // defmacro returns the symbol and installs a named macro in the root environment
+    let result = eval_str("(let [x 1] (defmacro my-when \"Like when\" [test & body] `(if ,test (do ,@body) nil)))", &env).unwrap();
+    assert_eq!(result, Value::Symbol("my-when".to_string()));
+
+    let my_when = env.borrow().get("my-when");
+    match &my_when {
+        Some(Value::Macro(mac)) => {
+            assert_eq!(mac.name.as_deref(), Some("my-when"));
+            assert_eq!(mac.doc.as_deref(), Some("Like when"));
+        }
+        other => panic!("expected a macro, got {:?}", other),
+    }
+    assert_eq!(format!("{}", eval_str("my-when", &env).unwrap()), "#<macro my-when>");
+
+    assert_eq!(eval_str("(my-when true 1 2)", &env).unwrap(), Value::Number(2.0));
+    assert_eq!(eval_str("(my-when false (undefined-fn))", &env).unwrap(), Value::Nil);
+}
+
+#[test]
+fn test_defmacro_bootstrap() {
+    let env = standard_env();
+    eval_str("(defmacro unless [test & body] `(if ,test nil (do ,@body)))", &env).unwrap();
+
+    // Thread-first, expanding recursively one form at a time
+    eval_str(
+        "(defmacro -> [x & forms]
+           (if (= forms nil)
+             x
+             (let [[form & more] forms
+                   [f & args] form]
+               `(-> (,f ,x ,@args) ,@more))))",
+        &env,
+    )
+    .unwrap();
+
+    assert_eq!(eval_str("(unless (= 1 2) :yes)", &env).unwrap(), Value::Keyword("yes".to_string()));
+    assert_eq!(eval_str("(-> 1 (+ 2) (* 3) (- 1))", &env).unwrap(), Value::Number(8.0));
+    assert_eq!(eval_str("(-> 5)", &env).unwrap(), Value::Number(5.0));
+    assert_eq!(
+        eval_str("(let [n 10] (-> n (- 4) (unless :never)))", &env).unwrap(),
+        Value::Nil
+    );
+}
+
+#[test]
+fn test_defmacro_names_expansion_limit() {
+    let env = standard_env();
+    eval_str("(defmacro forever [] '(forever))", &env).unwrap();
+
+    match eval_str("(forever)", &env) {
+        Err(EvalError::Other(msg)) => assert!(msg.contains("forever"), "{}", msg),
+        other => panic!("expected expansion limit error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_gensym() {
+    let env = standard_env();
+
+    let a = eval_str("(gensym)", &env).unwrap();
+    let b = eval_str("(gensym)", &env).unwrap();
+    assert_ne!(a, b);
+    match &a {
+        Value::Symbol(name) => assert!(name.starts_with("G__"), "{}", name),
+        other => panic!("expected a symbol, got {:?}", other),
+    }
+
+    match &eval_str("(gensym \"tmp\")", &env).unwrap() {
+        Value::Symbol(name) => assert!(name.starts_with("tmp"), "{}", name),
+        other => panic!("expected a symbol, got {:?}", other),
+    }
+
+    assert!(eval_str("(gensym 1)", &env).is_err());
+}
+
+#[test]
+fn test_auto_gensym() {
+    let env = standard_env();
+    eval_str("(defmacro square [x] `(let [v# ,x] (* v# v#)))", &env).unwrap();
+
+    // Both uses of v# in the template expand to the same symbol
+    let expansion = eval_str("`(let [v# 1] (* v# v#))", &env).unwrap();
+    let items = match &expansion {
+        Value::List(items) => items,
+        other => panic!("expected a list, got {:?}", other),
+    };
+    let bound = match &items[1] {
+        Value::Vector(bindings) => bindings[0].clone(),
+        other => panic!("expected a vector, got {:?}", other),
+    };
+    assert_eq!(
+        items[2],
+        Value::List(vec![Value::Symbol("*".to_string()), bound.clone(), bound.clone()])
+    );
+    assert_ne!(bound, Value::Symbol("v#".to_string()));
+
+    // The generated symbol does not capture the caller's v
+    assert_eq!(eval_str("(let [v 3] (square v))", &env).unwrap(), Value::Number(9.0));
+
+    // Nested expansions of the macro get different symbols and do not collide
+    assert_eq!(eval_str("(square (square 2))", &env).unwrap(), Value::Number(16.0));
+    let first = eval_str("`v#", &env).unwrap();
+    let second = eval_str("`v#", &env).unwrap();
+    assert_ne!(first, second);
+}
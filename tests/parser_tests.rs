@@ -1,4 +1,4 @@
-use citrine::parse;
+use citrine::{parse, parse_with_errors};
 use expect_test::{expect, Expect};
 
 fn check(input: &str, expected_tree: Expect) {
@@ -184,3 +184,52 @@ fn test_parse_complex() {
     );
 }
 
+
+#[test]
+fn test_parse_comment_form() {
+    // The body of a comment form is kept in full for tooling
+    check(
+        "(comment (this is (not valid)))",
+        expect![[r#"
+            Root@0..27
+              List@0..27
+                LeftParen@0..1 "("
+                SymbolLit@1..8
+                  Symbol@1..8 "comment"
+                List@8..26
+                  LeftParen@8..9 "("
+                  SymbolLit@9..13
+                    Symbol@9..13 "this"
+                  SymbolLit@13..15
+                    Symbol@13..15 "is"
+                  List@15..25
+                    LeftParen@15..16 "("
+                    SymbolLit@16..19
+                      Symbol@16..19 "not"
+                    SymbolLit@19..24
+                      Symbol@19..24 "valid"
+                    RightParen@24..25 ")"
+                  RightParen@25..26 ")"
+                RightParen@26..27 ")"
+              Eof@27..27 ""
+        "#]],
+    );
+}
+
+#[test]
+fn test_parse_recovers_from_errors() {
+    // The stray delimiter is kept in an error node and parsing carries on
+    let (syntax, errors) = parse_with_errors(") 1");
+    assert_eq!(errors.len(), 1);
+    expect![[r#"
+        Root@0..2
+          Error@0..1
+            RightParen@0..1 ")"
+          NumberLit@1..2
+            Number@1..2 "1"
+          Eof@2..2 ""
+    "#]].assert_eq(&format!("{:#?}", syntax));
+
+    let (_, errors) = parse_with_errors("(+ 1 2)");
+    assert!(errors.is_empty());
+}
@@ -1,5 +1,5 @@
 use citrine::{read_str, eval_str, standard_env};
-use citrine::reader::Value;
+use citrine::reader::{EvalError, Value};
 
 #[test]
 fn test_read_number() {
@@ -55,7 +55,7 @@ fn test_read_vector() {
 fn test_read_map() {
     let value = read_str("{:a 1 :b 2}").unwrap();
     
-    if let Value::Map(map) = value {
+    if let Value::Map(map) = &value {
         assert_eq!(map.len(), 2);
         assert_eq!(map.get(&Value::Keyword("a".to_string())), Some(&Value::Number(1.0)));
         assert_eq!(map.get(&Value::Keyword("b".to_string())), Some(&Value::Number(2.0)));
@@ -68,7 +68,7 @@ fn test_read_map() {
 fn test_read_set() {
     let value = read_str("#{1 2 3}").unwrap();
     
-    if let Value::Set(set) = value {
+    if let Value::Set(set) = &value {
         assert_eq!(set.len(), 3);
         assert!(set.contains(&Value::Number(1.0)));
         assert!(set.contains(&Value::Number(2.0)));
@@ -94,7 +94,7 @@ fn test_read_quote() {
 fn test_read_backtick() {
     let value = read_str("`(1 2 ,x)").unwrap();
     
-    if let Value::List(items) = value {
+    if let Value::List(items) = &value {
         assert_eq!(items[0], Value::Symbol("quasiquote".to_string()));
         
         if let Value::List(inner) = &items[1] {
@@ -151,7 +151,7 @@ fn test_eval_fn() {
     let env = standard_env();
     let result = eval_str("(fn [x] (+ x 1))", &env).unwrap();
     
-    if let Value::Function(f) = result {
+    if let Value::Function(f) = &result {
         assert_eq!(f.params, vec!["x".to_string()]);
         assert_eq!(f.body.len(), 1);
     } else {
@@ -164,7 +164,7 @@ fn test_eval_macro() {
     let env = standard_env();
     let result = eval_str("(macro [x] (quote x))", &env).unwrap();
     
-    if let Value::Macro(m) = result {
+    if let Value::Macro(m) = &result {
         assert_eq!(m.params, vec!["x".to_string()]);
         assert_eq!(m.body.len(), 1);
     } else {
@@ -195,7 +195,7 @@ fn test_eval_map() {
     
     let result = eval_str("{:a 1 :b x}", &env).unwrap();
     
-    if let Value::Map(result_map) = result {
+    if let Value::Map(result_map) = &result {
         assert_eq!(result_map.len(), 2);
         assert_eq!(result_map.get(&Value::Keyword("a".to_string())), Some(&Value::Number(1.0)));
         assert_eq!(result_map.get(&Value::Keyword("b".to_string())), Some(&Value::Number(42.0)));
@@ -211,7 +211,7 @@ fn test_eval_set() {
     
     let result = eval_str("#{1 x}", &env).unwrap();
     
-    if let Value::Set(result_set) = result {
+    if let Value::Set(result_set) = &result {
         assert_eq!(result_set.len(), 2);
         assert!(result_set.contains(&Value::Number(1.0)));
         assert!(result_set.contains(&Value::Number(42.0)));
@@ -220,3 +220,43 @@ fn test_eval_set() {
     }
 }
 
+
+#[test]
+fn test_read_literals() {
+    assert_eq!(read_str("nil").unwrap(), Value::Nil);
+    assert_eq!(read_str("true").unwrap(), Value::Boolean(true));
+    assert_eq!(read_str("false").unwrap(), Value::Boolean(false));
+}
+
+#[test]
+fn test_read_meta() {
+    // ^:keyword is shorthand for {:keyword true}
+    assert_eq!(
+        read_str("^:dynamic *x*").unwrap(),
+        read_str("(with-meta *x* {:dynamic true})").unwrap()
+    );
+
+    // ^Symbol is shorthand for {:tag Symbol}
+    assert_eq!(
+        read_str("^String s").unwrap(),
+        read_str("(with-meta s {:tag String})").unwrap()
+    );
+
+    assert_eq!(
+        read_str("^{:doc 1} [x]").unwrap(),
+        read_str("(with-meta [x] {:doc 1})").unwrap()
+    );
+
+    assert!(read_str("^1 x").is_err());
+}
+
+#[test]
+fn test_read_malformed() {
+    for input in ["(+ 1", "(+ 1 2))", ")", "[1 2", "\"abc", "{:a}"] {
+        assert!(
+            matches!(read_str(input), Err(EvalError::SyntaxError(_))),
+            "{} should not read",
+            input
+        );
+    }
+}
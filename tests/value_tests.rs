@@ -0,0 +1,155 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use citrine::{eval_str, standard_env};
+use citrine::reader::{Function, Macro, Value};
+
+#[test]
+fn test_truthiness_table() {
+    let env = standard_env();
+    let function = Value::Function(Rc::new(Function::new(vec![], vec![], env.clone())));
+    let mac = Value::Macro(Rc::new(Macro::new(vec![], vec![], env.clone())));
+
+    // Only nil and false are falsey
+    let table = vec![
+        (Value::Nil, false),
+        (Value::Boolean(false), false),
+        (Value::Boolean(true), true),
+        (Value::Number(0.0), true),
+        (Value::Number(-1.5), true),
+        (Value::String(String::new()), true),
+        (Value::String("false".to_string()), true),
+        (Value::Symbol("nil".to_string()), true),
+        (Value::Keyword("false".to_string()), true),
+        (Value::List(vec![]), true),
+        (Value::Vector(vec![]), true),
+        (Value::Map(HashMap::new()), true),
+        (Value::Set(HashSet::new()), true),
+        (function, true),
+        (mac, true),
+    ];
+
+    for (value, truthy) in table {
+        assert_eq!(value.is_truthy(), truthy, "{:?}", value);
+    }
+}
+
+#[test]
+fn test_conditions_use_truthiness() {
+    let env = standard_env();
+
+    let cases = [
+        ("nil", false),
+        ("false", false),
+        ("true", true),
+        ("0", true),
+        ("\"\"", true),
+        ("'()", true),
+        ("[]", true),
+        ("{}", true),
+        ("#{}", true),
+        ("(fn [] nil)", true),
+    ];
+
+    for (form, truthy) in cases {
+        let expected = Value::Boolean(truthy);
+        let checks = [
+            format!("(boolean {})", form),
+            format!("(not (not {}))", form),
+            format!("(if {} true false)", form),
+            format!("(if-not {} false true)", form),
+            format!("(if (when {} true) true false)", form),
+            format!("(if (when-not {} true) false true)", form),
+        ];
+        for check in checks {
+            assert_eq!(eval_str(&check, &env).unwrap(), expected, "{}", check);
+        }
+    }
+}
+
+#[test]
+fn test_boolean_arity() {
+    let env = standard_env();
+
+    assert!(eval_str("(boolean)", &env).is_err());
+    assert!(eval_str("(boolean 1 2)", &env).is_err());
+}
+
+#[test]
+fn test_len_and_try_iter() {
+    let env = standard_env();
+
+    assert_eq!(Value::Nil.len(), Some(0));
+    assert_eq!(Value::String("héllo".to_string()).len(), Some(5));
+    assert_eq!(eval_str("[1 2 3]", &env).unwrap().len(), Some(3));
+    assert_eq!(eval_str("{:a 1 :b 2}", &env).unwrap().len(), Some(2));
+    assert_eq!(Value::Number(1.0).len(), None);
+    assert_eq!(Value::Nil.is_empty(), Some(true));
+    assert_eq!(Value::Keyword("a".to_string()).is_empty(), None);
+
+    let chars: Vec<Value> = Value::String("hé".to_string()).try_iter().unwrap().collect();
+    assert_eq!(chars, vec![Value::String("h".to_string()), Value::String("é".to_string())]);
+    let entries: Vec<Value> = eval_str("{:a 1}", &env).unwrap().try_iter().unwrap().collect();
+    assert_eq!(entries, vec![eval_str("[:a 1]", &env).unwrap()]);
+    assert_eq!(Value::Nil.try_iter().unwrap().count(), 0);
+    assert!(Value::Number(1.0).try_iter().is_none());
+}
+
+fn hash_of(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn test_delay_hash_matches_equality() {
+    let env = standard_env();
+    let delay = eval_str("(delay (+ 1 2))", &env).unwrap();
+    let other = eval_str("(delay (+ 1 2))", &env).unwrap();
+
+    // Clones of a delay are equal and hash alike, other delays are not equal
+    assert_eq!(delay.clone(), delay);
+    assert_eq!(hash_of(&delay.clone()), hash_of(&delay));
+    assert_ne!(delay, other);
+
+    let set = Value::Set([delay.clone(), delay, other].into_iter().collect());
+    assert!(matches!(&set, Value::Set(items) if items.len() == 2));
+}
+
+#[test]
+fn test_map_and_set_hash_matches_equality() {
+    let env = standard_env();
+
+    // Equal maps and sets hash alike, whatever order their entries were added in
+    for (a, b) in [("{:a 1 :b 2 :c 3}", "{:c 3 :b 2 :a 1}"), ("#{1 2 3 [4]}", "#{[4] 3 2 1}"), ("{#{1} {:a 1}}", "{#{1} {:a 1}}")] {
+        let a = eval_str(a, &env).unwrap();
+        let b = eval_str(b, &env).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+    }
+
+    let set = Value::Set(
+        ["{:a 1}", "{:a 1}", "{:a 2}"]
+            .into_iter()
+            .map(|source| eval_str(source, &env).unwrap())
+            .collect(),
+    );
+    assert_eq!(set.len(), Some(2));
+}
+
+#[test]
+fn test_reduced_hash_matches_equality() {
+    let env = standard_env();
+    let a = eval_str("(reduced [1 2])", &env).unwrap();
+    let b = eval_str("(reduced [1 2])", &env).unwrap();
+
+    // Reduced values are equal when what they wrap is, and then hash alike
+    assert_eq!(a, b);
+    assert_eq!(hash_of(&a), hash_of(&b));
+    assert_ne!(a, eval_str("(reduced [1 3])", &env).unwrap());
+
+    let set = Value::Set([a, b].into_iter().collect());
+    assert_eq!(set.len(), Some(1));
+}